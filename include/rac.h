@@ -0,0 +1,49 @@
+/* C interface to rac, built into target/release/librac.so with
+ * `cargo rustc --release --lib --features ffi --crate-type cdylib`
+ *
+ * Addresses are 6-byte arrays. Every function returns RAC_OK, or one of the
+ * negative RAC_E* codes on failure. */
+
+#ifndef RAC_H
+#define RAC_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define RAC_OK 0
+/* An argument is null or isn't valid UTF-8 */
+#define RAC_EINVAL (-1)
+/* The text isn't a MAC address */
+#define RAC_EPARSE (-2)
+/* The buffer is too small for the result */
+#define RAC_ERANGE (-3)
+/* `ip` couldn't change the address (or couldn't be run) */
+#define RAC_EBACKEND (-4)
+/* Not allowed: no CAP_NET_ADMIN, or /etc/rac/restrictions.conf refuses it */
+#define RAC_EPERM (-5)
+/* There is no interface with this name */
+#define RAC_ENODEV (-6)
+/* Another process is changing the interface, or it was changed too recently */
+#define RAC_EBUSY (-7)
+/* The change went through, but the interface reports another address */
+#define RAC_EVERIFY (-8)
+
+/* Bytes rac_format writes: XX:XX:XX:XX:XX:XX and the NUL */
+#define RAC_FORMAT_LEN 18
+
+/* Parse a MAC address (':' or '-' separated) */
+int rac_parse(const char *text, uint8_t addr[6]);
+
+/* Format an address the way rac prints it, NUL-terminated */
+int rac_format(const uint8_t addr[6], char *buf, size_t len);
+
+/* Generate a random unicast, locally administered address */
+int rac_generate_random(uint8_t addr[6]);
+
+/* Set the address of an interface: down, change and back up, like `rac set`,
+ * within /etc/rac/restrictions.conf and recorded in rac's history. A failed
+ * change brings the interface back up. Runs `ip` directly, so the caller
+ * needs CAP_NET_ADMIN. */
+int rac_set_addr(const char *interface, const uint8_t addr[6]);
+
+#endif
@@ -0,0 +1,168 @@
+//! Moving rac's state to another machine (`rac export`, `rac import`)
+//!
+//! The document is one flat JSON object: every file of the state directory by
+//! its path in there (the history, the saved hostname, the network
+//! assignments, each pool as `pools/<name>`), with the secret of the stable
+//! addresses in hex, and the configuration file as `config`.
+//!
+//! ```text
+//! {"format":"rac-state-1","config":"[general]\n...","history":"1760000000 wlan0 set ...\n",...}
+//! ```
+
+use std::{
+	fmt::Write as _,
+	fs::{self, OpenOptions},
+	io::{self, Write},
+	os::unix::fs::OpenOptionsExt,
+	path::{Path, PathBuf},
+};
+
+use crate::{config::Config, json, pool};
+
+/// What the document says it is, changed whenever the layout does
+const FORMAT: &str = "rac-state-1";
+
+/// The files of the state directory that are plain text
+const TEXT_FILES: &[&str] = &["hostname", "networks"];
+
+/// Read a file, `None` if it doesn't exist
+fn read(path: &Path) -> io::Result<Option<Vec<u8>>> {
+	match fs::read(path) {
+		Ok(bytes) => Ok(Some(bytes)),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+		Err(e) => Err(e),
+	}
+}
+
+fn text(bytes: Vec<u8>) -> String { String::from_utf8_lossy(&bytes).into_owned() }
+
+/// Everything in the state directory, and the configuration file at `config`
+pub fn export(state_dir: &Path, config: &Path) -> io::Result<String> {
+	let mut document = json::Object::new().str("format", FORMAT);
+	if let Some(bytes) = read(config)? {
+		document = document.str("config", &text(bytes));
+	}
+	if let Some(bytes) = read(&state_dir.join("history"))? {
+		document = document.str("history", &text(bytes));
+	}
+	for name in TEXT_FILES {
+		if let Some(bytes) = read(&state_dir.join(name))? {
+			document = document.str(name, &text(bytes));
+		}
+	}
+	if let Some(bytes) = read(&state_dir.join("secret"))? {
+		let hex = bytes.iter().fold(String::new(), |mut hex, b| {
+			let _ = write!(hex, "{:02x}", b);
+			hex
+		});
+		document = document.str("secret", &hex);
+	}
+	for name in pool::names(state_dir)? {
+		if let Some(bytes) = read(&state_dir.join("pools").join(&name))? {
+			document = document.str(&format!("pools/{}", name), &text(bytes));
+		}
+	}
+	Ok(document.finish())
+}
+
+fn invalid(e: String) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, e) }
+
+/// Where each part of a document goes, with its content
+fn files(state_dir: &Path, config: &Path, document: &str) -> io::Result<Vec<(PathBuf, Vec<u8>)>> {
+	let fields = json::parse_object(document)
+		.ok_or_else(|| invalid("not a document from `rac export`".to_string()))?;
+	match fields.iter().find(|(key, _)| key == "format") {
+		Some((_, format)) if format == FORMAT => {}
+		Some((_, format)) => return Err(invalid(format!("unsupported format '{}'", format))),
+		None => return Err(invalid("not a document from `rac export`".to_string())),
+	}
+	// The state goes where the configuration coming along keeps it
+	let state_dir = match fields.iter().find(|(key, _)| key == "config") {
+		Some((_, text)) => {
+			Config::parse(text)
+				.map_err(|(line, e)| invalid(format!("its configuration, line {}: {}", line, e)))?
+				.general
+				.state_dir
+		}
+		None => state_dir.to_path_buf(),
+	};
+	let mut files = Vec::new();
+	for (key, value) in fields {
+		let path = match key.as_str() {
+			"format" => continue,
+			"config" => config.to_path_buf(),
+			"history" => state_dir.join("history"),
+			"secret" => {
+				let secret = (0..value.len())
+					.step_by(2)
+					.map(|i| {
+						value
+							.get(i..i + 2)
+							.and_then(|b| u8::from_str_radix(b, 16).ok())
+					})
+					.collect::<Option<Vec<u8>>>()
+					.filter(|secret| secret.len() == 32)
+					.ok_or_else(|| invalid("the secret isn't 32 bytes of hex".to_string()))?;
+				files.push((state_dir.join("secret"), secret));
+				continue;
+			}
+			name if TEXT_FILES.contains(&name) => state_dir.join(name),
+			name => match name.strip_prefix("pools/") {
+				Some(pool) if !pool.is_empty() && !pool.starts_with('.') && !pool.contains('/') => {
+					state_dir.join("pools").join(pool)
+				}
+				_ => return Err(invalid(format!("unknown part '{}'", name))),
+			},
+		};
+		files.push((path, value.into_bytes()));
+	}
+	Ok(files)
+}
+
+/// Write the parts of a document where they go (the state into the state
+/// directory of the configuration in it, if there's one), refusing to replace
+/// existing files unless `force`. Nothing is written if any part is invalid or
+/// in the way. Returns the files written.
+pub fn import(
+	state_dir: &Path,
+	config: &Path,
+	document: &str,
+	force: bool,
+) -> io::Result<Vec<PathBuf>> {
+	let files = files(state_dir, config, document)?;
+	if !force {
+		let existing: Vec<String> = files
+			.iter()
+			.filter(|(path, _)| path.exists())
+			.map(|(path, _)| path.display().to_string())
+			.collect();
+		if !existing.is_empty() {
+			return Err(io::Error::new(
+				io::ErrorKind::AlreadyExists,
+				format!(
+					"{} already exist(s), pass --force to replace them",
+					existing.join(", ")
+				),
+			));
+		}
+	}
+	for (path, content) in &files {
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		// Like `stable::secret`, the secret is only for root to read
+		let mode = if path.ends_with("secret") {
+			0o600
+		} else {
+			0o644
+		};
+		OpenOptions::new()
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.mode(mode)
+			.open(path)?
+			.write_all(content)?;
+	}
+	Ok(files.into_iter().map(|(path, _)| path).collect())
+}
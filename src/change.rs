@@ -0,0 +1,170 @@
+//! Changing the address of an interface, for rac and the C interface alike
+//!
+//! A change is only made if the restrictions allow it, under the interface's
+//! lock and outside its cooldown. The link is taken down, given the address
+//! (and a new name, if asked) and brought back up, then checked for the
+//! address, since some drivers accept a change without applying it. A change
+//! that fails once the link is down brings it back up: the link never stays
+//! down for a change that didn't happen.
+
+use std::{
+	io,
+	time::{Duration, Instant},
+};
+
+use crate::{
+	cooldown,
+	error::{self, RacError},
+	escalate, history, lock, netlink, restrictions, timeout, HwAddr, MacAddr,
+};
+
+/// How long to wait for the carrier to come back after changing an address
+const CARRIER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How an address change treats the interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+	/// Take it down, change the address and bring it back up
+	Bounce,
+	/// Take it down and change the address (`--no-up`)
+	LeaveDown,
+	/// Change the address while it's up (`--no-down`)
+	Live,
+}
+
+/// What an address change did
+pub struct Changed {
+	/// The address before
+	pub old:        Option<MacAddr>,
+	/// How long the link was down when it was bounced: until its carrier was
+	/// back, if it had one
+	pub downtime:   Option<Duration>,
+	/// Why the change couldn't be recorded in the history, which isn't worth
+	/// failing it over
+	pub unrecorded: Option<io::Error>,
+}
+
+/// Run `sudo ip [args...]` (or through pkexec, see `--escalate`)
+pub fn ip(args: &[&str]) -> error::Result<()> {
+	let output = timeout::output(escalate::command("ip").args(args)).map_err(escalate::missing)?;
+	if !output.status.success() {
+		let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+		// Either sudo itself refused, or ip ran without CAP_NET_ADMIN
+		if escalate::refused(&stderr) || stderr.contains("Operation not permitted") {
+			return Err(RacError::PermissionDenied(stderr));
+		}
+		return Err(RacError::Backend(stderr));
+	}
+	Ok(())
+}
+
+/// Run `sudo ip link set [interface] [args...]`
+pub fn ip_link(inter: &str, args: &[&str]) -> error::Result<()> {
+	ip(&[&["link", "set", inter], args].concat())
+}
+
+/// An interface as it is now
+fn link_of(inter: &str) -> io::Result<Option<netlink::Link>> {
+	Ok(netlink::Socket::new(0)?
+		.links()?
+		.into_iter()
+		.find(|link| link.name == inter))
+}
+
+/// Check that an interface really ended up with an address, some drivers
+/// accept a change without applying it
+pub fn verify(inter: &str, addr: MacAddr) -> error::Result<()> {
+	let actual = link_of(inter)?.and_then(|link| link.address);
+	if actual.as_ref().and_then(HwAddr::mac) != Some(addr) {
+		return Err(RacError::Verification {
+			inter: inter.to_string(),
+			expected: addr,
+			actual,
+		});
+	}
+	Ok(())
+}
+
+/// Change the address of an interface, and its name to `rename` if given
+/// (which needs it down). `mode` is how the address came about, for the
+/// restrictions, and `progress` is told each step as it starts.
+pub fn apply(
+	inter: &str,
+	addr: MacAddr,
+	how: Change,
+	kind: history::Kind,
+	mode: restrictions::Mode,
+	rename: Option<&str>,
+	progress: &mut dyn FnMut(&str),
+) -> error::Result<Changed> {
+	restrictions::check(inter, mode)?;
+	let _lock = lock::interface(inter)?;
+	// Under the lock, so a change made meanwhile counts
+	cooldown::check(inter, addr, kind)?;
+	let link = link_of(inter)?.ok_or_else(|| RacError::NoInterface(inter.to_string()))?;
+	let (had_carrier, was_up, old) = (link.has_carrier(), link.is_up(), link.mac());
+
+	let started = Instant::now();
+	if how != Change::Live {
+		// sudo ip link set [interface] down
+		progress("down");
+		ip_link(inter, &["down"])?;
+	}
+	let mut name = inter;
+	let result = (|| {
+		// sudo ip link set [interface] address [MAC address]
+		progress("set");
+		ip_link(name, &["address", &addr.to_string()]).map_err(|e| match e {
+			RacError::Backend(e) if how == Change::Live => RacError::Backend(format!(
+				"{} can't change its address while up ({})",
+				name, e
+			)),
+			e => e,
+		})?;
+		// sudo ip link set [interface] name [name]
+		if let Some(rename) = rename {
+			progress("rename");
+			ip_link(name, &["name", rename])?;
+			name = rename;
+		}
+		if how == Change::Bounce {
+			// sudo ip link set [interface] up
+			progress("up");
+			ip_link(name, &["up"])?;
+		}
+		verify(name, addr)
+	})();
+	if let Err(e) = result {
+		// Failing to come back up is left out, the change's error says more
+		if was_up && how != Change::Live {
+			ip_link(name, &["up"]).ok();
+		}
+		return Err(e);
+	}
+	let unrecorded = match old.filter(|old| *old != addr) {
+		Some(old) => history::record(name, kind, old, addr).err(),
+		None => None,
+	};
+	if how != Change::Bounce {
+		return Ok(Changed {
+			old,
+			downtime: None,
+			unrecorded,
+		});
+	}
+
+	if had_carrier {
+		while started.elapsed() < CARRIER_TIMEOUT.min(timeout::get()) {
+			if link_of(name)?.is_some_and(|link| link.has_carrier()) {
+				break;
+			}
+			progress("carrier");
+			std::thread::sleep(Duration::from_millis(50));
+		}
+	}
+	Ok(Changed {
+		old,
+		downtime: Some(started.elapsed()),
+		unrecorded,
+	})
+}
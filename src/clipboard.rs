@@ -0,0 +1,78 @@
+//! Copying to the clipboard (`rac -r --copy`)
+//!
+//! wl-copy under Wayland and xclip under X11 are tried first. Without either
+//! (e.g. over SSH), the text is sent to the terminal as an OSC 52 sequence,
+//! which most terminal emulators put on the local clipboard.
+
+use std::{
+	env,
+	fs::OpenOptions,
+	io::{self, Write},
+	process::{Command, Stdio},
+};
+
+/// Run a clipboard tool with the text on stdin, `None` if it isn't installed
+fn pipe(program: &str, args: &[&str], text: &str) -> io::Result<Option<()>> {
+	let mut child = match Command::new(program)
+		.args(args)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.spawn()
+	{
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+		result => result?,
+	};
+	child
+		.stdin
+		.take()
+		.expect("stdin is piped")
+		.write_all(text.as_bytes())?;
+	if !child.wait()?.success() {
+		return Err(io::Error::other(format!("{} failed", program)));
+	}
+	Ok(Some(()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+	const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut out = String::new();
+	for chunk in bytes.chunks(3) {
+		let n = chunk
+			.iter()
+			.enumerate()
+			.fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+		for i in 0..4 {
+			if i <= chunk.len() {
+				out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+			} else {
+				out.push('=');
+			}
+		}
+	}
+	out
+}
+
+/// Put text on the clipboard, returning how
+pub fn copy(text: &str) -> io::Result<&'static str> {
+	if env::var_os("WAYLAND_DISPLAY").is_some() && pipe("wl-copy", &[], text)?.is_some() {
+		return Ok("wl-copy");
+	}
+	if env::var_os("DISPLAY").is_some()
+		&& pipe("xclip", &["-selection", "clipboard"], text)?.is_some()
+	{
+		return Ok("xclip");
+	}
+	// The terminal rather than stdout, which may be piped somewhere
+	let mut tty = OpenOptions::new()
+		.write(true)
+		.open("/dev/tty")
+		.map_err(|_| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				"no wl-copy, xclip or terminal to copy with",
+			)
+		})?;
+	write!(tty, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+	Ok("OSC 52")
+}
@@ -0,0 +1,1036 @@
+//! Configuration file (`/etc/rac/rac.conf`)
+//!
+//! The format is a small subset of TOML: `[section]` headers followed by
+//! `key = value` lines, where a value is a quoted string, a boolean, or a
+//! list of strings (`["a", "b"]`). `#` starts a comment.
+
+use std::{
+	collections::{BTreeMap, HashSet},
+	fs, io,
+	net::SocketAddr,
+	path::{Path, PathBuf},
+	str::FromStr,
+	time::Duration,
+};
+
+use clap::ArgEnum;
+
+use crate::{
+	cooldown, escalate, history, hooks, logfile, oui, parse_hex_bytes, restrictions,
+	schedule::{self, Schedule},
+	stable, timeout,
+	value::Value,
+	MacAddr,
+};
+
+/// Default location of the configuration file
+pub const DEFAULT_PATH: &str = "/etc/rac/rac.conf";
+
+/// How often to try generating an address that isn't blacklisted
+const GENERATE_ATTEMPTS: usize = 1000;
+
+/// Default path of the daemon's control socket
+pub const DEFAULT_CONTROL_SOCKET: &str = "/run/rac.sock";
+
+/// Default path of the daemon's PID file
+pub const DEFAULT_PID_FILE: &str = "/run/rac.pid";
+
+/// Default number of past changes whose addresses aren't generated again
+pub const DEFAULT_AVOID_RECENT: usize = 10;
+
+pub use crate::history::DEFAULT_STATE_DIR;
+
+/// How a new address is chosen for an interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+	/// Fully random, locally administered unicast address
+	Random,
+	/// Keep the vendor part (first three bytes) and randomize the rest
+	KeepOui,
+	/// Use the given vendor part and randomize the rest
+	Oui([u8; 3]),
+	/// Use the (universally administered) prefix of a popular vendor
+	Mimic(oui::Category),
+	/// Randomize the bits set in `mask`, keeping the others from `base` (or
+	/// the current address)
+	Masked {
+		base: Option<MacAddr>,
+		mask: [u8; 6],
+	},
+	/// Derive the address from a secret, the interface and the network it's
+	/// on, so it's the same every time on a given network
+	StablePerNetwork,
+	/// Derive the address from a secret, the interface and the local date, so
+	/// it changes every day but stays the same within one
+	StablePerDay,
+}
+
+impl Policy {
+	/// What the policy is called, for output
+	pub fn name(&self) -> &'static str {
+		match self {
+			Policy::Random => "random",
+			Policy::KeepOui => "keep-oui",
+			Policy::Oui(_) => "vendor prefix",
+			Policy::Mimic(_) => "vendor-mimic",
+			Policy::Masked { base: Some(_), .. } => "template",
+			Policy::Masked { base: None, .. } => "mask",
+			Policy::StablePerNetwork => "stable-per-network",
+			Policy::StablePerDay => "stable-per-day",
+		}
+	}
+
+	/// How the addresses it generates come about, for the restrictions
+	pub fn mode(&self) -> restrictions::Mode {
+		match self {
+			Policy::StablePerNetwork | Policy::StablePerDay => restrictions::Mode::Stable,
+			_ => restrictions::Mode::Random,
+		}
+	}
+
+	/// Generate an address for an interface currently using `current`
+	pub fn generate(&self, current: Option<MacAddr>) -> MacAddr {
+		let mut addr = MacAddr::random();
+		match (self, current) {
+			(Policy::KeepOui, Some(current)) => {
+				addr.bytes[..3].copy_from_slice(&current.bytes[..3])
+			}
+			(Policy::Oui(oui), _) => addr.bytes[..3].copy_from_slice(oui),
+			(Policy::Mimic(category), _) => {
+				addr.bytes[..3].copy_from_slice(&category.random_prefix())
+			}
+			(Policy::Masked { base, mask }, current) => {
+				let base = base.or(current).map_or([0; 6], |base| base.bytes);
+				let random: [u8; 6] = rand::random();
+				for i in 0..6 {
+					addr.bytes[i] = base[i] & !mask[i] | random[i] & mask[i];
+				}
+				// A randomized multicast bit stays clear, a randomized local bit set
+				addr.bytes[0] &= !(mask[0] & 0x01);
+				addr.bytes[0] |= mask[0] & 0x02;
+			}
+			_ => {}
+		}
+		addr
+	}
+}
+
+impl FromStr for Policy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"full" | "random" => Ok(Policy::Random),
+			"keep-oui" => Ok(Policy::KeepOui),
+			"vendor-mimic" => Ok(Policy::Mimic(oui::Category::Consumer)),
+			"stable-per-network" => Ok(Policy::StablePerNetwork),
+			"stable-per-day" => Ok(Policy::StablePerDay),
+			_ => Err(format!(
+				"unknown policy '{}' (expected full, keep-oui, vendor-mimic, stable-per-network, \
+				 stable-per-day or one from a [policy.<name>] section)",
+				s
+			)),
+		}
+	}
+}
+
+/// Settings shared by every mode that picks addresses on its own
+#[derive(Debug)]
+pub struct General {
+	/// Policy used when randomizing interfaces automatically
+	pub policy:       Policy,
+	/// Interfaces that are never changed automatically
+	pub exclude:      Vec<String>,
+	/// Where persistent state is kept
+	pub state_dir:    PathBuf,
+	/// Prefixes (or whole addresses) that are never generated
+	pub blacklist:    Vec<Vec<u8>>,
+	/// OUI database to use instead of the system one
+	pub oui_db:       Option<PathBuf>,
+	/// How many of an interface's past changes random addresses never repeat
+	pub avoid_recent: usize,
+	/// How long an external command may take
+	pub timeout:      Duration,
+	/// Where the hooks run after every change are
+	pub hooks_dir:    PathBuf,
+	/// How the commands that need root are run
+	pub escalate:     escalate::Method,
+	/// The least time between changes to an interface
+	pub cooldown:     Duration,
+}
+
+impl Default for General {
+	fn default() -> General {
+		General {
+			policy:       Policy::Random,
+			exclude:      Vec::new(),
+			state_dir:    PathBuf::from(DEFAULT_STATE_DIR),
+			blacklist:    Vec::new(),
+			oui_db:       None,
+			avoid_recent: DEFAULT_AVOID_RECENT,
+			timeout:      timeout::DEFAULT,
+			hooks_dir:    PathBuf::from(hooks::DEFAULT_DIR),
+			escalate:     escalate::Method::Sudo,
+			cooldown:     Duration::ZERO,
+		}
+	}
+}
+
+/// Parse a blacklist entry: a prefix or a whole address
+fn parse_blacklisted(entry: &str) -> Result<Vec<u8>, String> {
+	match parse_hex_bytes(entry) {
+		Some(bytes) if (1..=6).contains(&bytes.len()) => Ok(bytes),
+		_ => Err(format!("invalid blacklist entry '{}'", entry)),
+	}
+}
+
+/// Settings for `rac daemon`
+#[derive(Debug)]
+pub struct Daemon {
+	/// Randomize interfaces that appear while the daemon is running
+	pub hotplug:              bool,
+	/// Export the D-Bus API on the system bus
+	pub dbus:                 bool,
+	/// Ask polkit whether D-Bus callers may change interfaces
+	pub polkit:               bool,
+	/// Where to listen for `rac ctl`, if at all
+	pub control_socket:       Option<PathBuf>,
+	/// Where to serve Prometheus metrics, if at all
+	pub metrics:              Option<SocketAddr>,
+	/// Where to write the daemon's PID, if at all
+	pub pid_file:             Option<PathBuf>,
+	/// Put back the addresses interfaces had before the daemon changed them
+	/// when it's stopped
+	pub restore_on_exit:      bool,
+	/// Randomize interfaces again whenever they lose their carrier
+	pub rotate_on_disconnect: bool,
+	/// Give interfaces the address assigned to the network they join
+	pub per_network:          bool,
+	/// When to rotate interfaces (`[schedule]`), in the file's order
+	pub schedules:            Vec<(String, Schedule)>,
+	/// Where to log events, if at all
+	pub log_file:             Option<PathBuf>,
+	pub log_format:           logfile::Format,
+	/// Start a new log file once it would grow past this many bytes
+	pub log_max_size:         Option<u64>,
+	/// Start a new log file once it's this old
+	pub log_rotate:           Option<Duration>,
+	/// How many old log files to keep
+	pub log_keep:             usize,
+}
+
+impl Default for Daemon {
+	fn default() -> Daemon {
+		Daemon {
+			hotplug:              true,
+			dbus:                 false,
+			polkit:               false,
+			control_socket:       Some(PathBuf::from(DEFAULT_CONTROL_SOCKET)),
+			metrics:              None,
+			pid_file:             Some(PathBuf::from(DEFAULT_PID_FILE)),
+			restore_on_exit:      false,
+			rotate_on_disconnect: false,
+			per_network:          false,
+			schedules:            Vec::new(),
+			log_file:             None,
+			log_format:           logfile::Format::Text,
+			log_max_size:         None,
+			log_rotate:           None,
+			log_keep:             logfile::DEFAULT_KEEP,
+		}
+	}
+}
+
+/// Settings for a single interface (`[interface.<name>]`), overriding
+/// [`General`]
+#[derive(Debug, Default)]
+pub struct Interface {
+	/// Policy used instead of the general one
+	pub policy:   Option<Policy>,
+	/// Always use this address instead of generating one
+	pub address:  Option<MacAddr>,
+	/// Never change the interface automatically
+	pub exclude:  bool,
+	/// Cooldown used instead of the general one
+	pub cooldown: Option<Duration>,
+}
+
+/// What an alias (`[alias]`) stands for
+#[derive(Debug, Clone)]
+pub enum Alias {
+	/// An interface by name
+	Name(String),
+	/// Whichever interface has this permanent address (`permanent:<MAC>`)
+	Permanent(MacAddr),
+}
+
+impl FromStr for Alias {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.strip_prefix("permanent:") {
+			Some(addr) => addr
+				.parse()
+				.map(Alias::Permanent)
+				.map_err(|e| format!("invalid address '{}': {}", addr, e)),
+			None if s.is_empty() => Err("an alias needs an interface name".to_string()),
+			None => Ok(Alias::Name(s.to_string())),
+		}
+	}
+}
+
+/// A named policy (`[policy.<name>]`): one of the built-in strategies with
+/// its settings
+#[derive(Debug)]
+struct PolicyDef {
+	/// Line of its first key, for errors
+	line:     usize,
+	strategy: Policy,
+	oui:      Option<[u8; 3]>,
+	mimic:    Option<oui::Category>,
+}
+
+impl PolicyDef {
+	fn build(&self) -> Result<Policy, String> {
+		match (self.strategy, self.oui, self.mimic) {
+			(strategy, None, None) => Ok(strategy),
+			(Policy::Random, Some(oui), None) => Ok(Policy::Oui(oui)),
+			(Policy::Mimic(_), None, Some(category)) => Ok(Policy::Mimic(category)),
+			(_, Some(_), _) => Err("'oui' only works with the 'full' strategy".to_string()),
+			(_, None, Some(_)) => {
+				Err("'mimic' only works with the 'vendor-mimic' strategy".to_string())
+			}
+		}
+	}
+}
+
+/// A problem [`Config::check`] found
+#[derive(Debug)]
+pub struct Problem {
+	/// Line of the file it's on
+	pub line:    usize,
+	/// Whether it keeps the configuration from loading, rather than being a
+	/// warning
+	pub error:   bool,
+	pub message: String,
+}
+
+/// The result of [`Config::parse_all`]
+#[derive(Default)]
+struct Parsed {
+	config:   Config,
+	errors:   Vec<(usize, String)>,
+	warnings: Vec<(usize, String)>,
+	/// Line of each section's first header
+	headers:  BTreeMap<String, usize>,
+	/// Line each key of each section is first set on
+	keys:     BTreeMap<(String, String), usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+	pub general:    General,
+	pub daemon:     Daemon,
+	pub interfaces: BTreeMap<String, Interface>,
+	/// Named policies (`[policy.<name>]`)
+	pub policies:   BTreeMap<String, Policy>,
+	/// Friendly names for interfaces (`[alias]`)
+	pub aliases:    BTreeMap<String, Alias>,
+}
+
+impl Config {
+	/// Whether an interface has been excluded from automatic changes
+	pub fn is_excluded(&self, inter: &str) -> bool {
+		self.general.exclude.iter().any(|name| name == inter)
+			|| self.interfaces.get(inter).is_some_and(|i| i.exclude)
+	}
+
+	/// The policy used for an interface when changing it automatically
+	pub fn policy_for(&self, inter: &str) -> Policy {
+		self.interfaces
+			.get(inter)
+			.and_then(|i| i.policy)
+			.unwrap_or(self.general.policy)
+	}
+
+	/// A policy by name, named ones first, then the built-in ones
+	pub fn policy(&self, name: &str) -> Result<Policy, String> {
+		match self.policies.get(name) {
+			Some(policy) => Ok(*policy),
+			None => name.parse(),
+		}
+	}
+
+	/// The address an interface always gets, if it has one
+	pub fn fixed_address(&self, inter: &str) -> Option<MacAddr> {
+		self.interfaces.get(inter).and_then(|i| i.address)
+	}
+
+	/// The address to give an interface currently using `current`: its fixed
+	/// address if it has one, otherwise one generated with its policy
+	pub fn address_for(&self, inter: &str, current: Option<MacAddr>) -> io::Result<MacAddr> {
+		match self.fixed_address(inter) {
+			Some(addr) => Ok(addr),
+			None => self.generate(self.policy_for(inter), Some(inter), current),
+		}
+	}
+
+	/// How the address `address_for` gives an interface comes about
+	pub fn mode_for(&self, inter: &str) -> restrictions::Mode {
+		match self.fixed_address(inter) {
+			Some(_) => restrictions::Mode::Address,
+			None => self.policy_for(inter).mode(),
+		}
+	}
+
+	/// Whether an address matches a blacklisted prefix or address
+	pub fn is_blacklisted(&self, addr: MacAddr) -> bool {
+		self.general
+			.blacklist
+			.iter()
+			.any(|prefix| addr.bytes.starts_with(prefix))
+	}
+
+	/// The addresses an interface had over its last `avoid_recent` changes,
+	/// which networks may still remember
+	fn recent_addresses(&self, inter: &str) -> HashSet<MacAddr> {
+		let entries = history::entries().unwrap_or_default();
+		entries
+			.iter()
+			.rev()
+			.filter(|entry| entry.inter == inter)
+			.take(self.general.avoid_recent)
+			.flat_map(|entry| [entry.old, entry.new])
+			.collect()
+	}
+
+	/// Generate an address for `inter` (if it's known yet) with `policy`,
+	/// regenerating blacklisted ones
+	pub fn generate(
+		&self,
+		policy: Policy,
+		inter: Option<&str>,
+		current: Option<MacAddr>,
+	) -> io::Result<MacAddr> {
+		let mut addresses: Box<dyn Iterator<Item = MacAddr>> = match policy {
+			Policy::StablePerNetwork | Policy::StablePerDay => {
+				let inter = inter.ok_or_else(|| {
+					io::Error::new(
+						io::ErrorKind::InvalidInput,
+						"stable policies need an interface",
+					)
+				})?;
+				let secret = stable::secret(&self.general.state_dir)?;
+				let scope = match policy {
+					Policy::StablePerNetwork => stable::network(inter)?,
+					_ => stable::today(),
+				};
+				Box::new(
+					(0..GENERATE_ATTEMPTS)
+						.map(move |attempt| stable::address(&secret, &[inter, &scope], attempt)),
+				)
+			}
+			Policy::Masked { base: None, .. } if current.is_none() => {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"a mask needs an interface to keep the other bits of the address from",
+				))
+			}
+			_ => Box::new((0..GENERATE_ATTEMPTS).map(|_| policy.generate(current))),
+		};
+		// Stable addresses are meant to come back, random ones aren't
+		let recent = match (policy, inter) {
+			(Policy::StablePerNetwork | Policy::StablePerDay, _) | (_, None) => HashSet::new(),
+			(_, Some(inter)) => self.recent_addresses(inter),
+		};
+		// Without a history to avoid only the blacklist can turn addresses
+		// down, otherwise say which of the two took the last one
+		let mut recently_used = false;
+		let addr = addresses
+			.find(|addr| {
+				let blacklisted = self.is_blacklisted(*addr);
+				recently_used = !blacklisted && recent.contains(addr);
+				!blacklisted && !recently_used
+			})
+			.ok_or_else(|| {
+				io::Error::other(match (recent.is_empty(), recently_used) {
+					(true, _) => {
+						"every generated address was blacklisted, check the blacklist".to_string()
+					}
+					(false, last) => format!(
+						"every generated address was blacklisted or used in the interface's last \
+						 {} changes (the last one {}), check the blacklist and avoid_recent",
+						self.general.avoid_recent,
+						if last {
+							"was used recently"
+						} else {
+							"was blacklisted"
+						}
+					),
+				})
+			})?;
+		Ok(addr)
+	}
+}
+
+impl Config {
+	/// Load the configuration file at `path`, or the default location.
+	///
+	/// A missing default file is not an error, an explicitly given one is.
+	pub fn load(path: Option<&Path>) -> io::Result<Config> {
+		let text = match path {
+			Some(path) => fs::read_to_string(path)?,
+			None => match fs::read_to_string(DEFAULT_PATH) {
+				Ok(text) => text,
+				Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+				Err(e) => return Err(e),
+			},
+		};
+		let name = path.map_or(DEFAULT_PATH.into(), |p| p.display().to_string());
+		Config::parse(&text).map_err(|(line, e)| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("{}:{}: {}", name, line, e),
+			)
+		})
+	}
+
+	/// Parse configuration text, returning the line number of the first error
+	pub fn parse(text: &str) -> Result<Config, (usize, String)> {
+		let parsed = Config::parse_all(text);
+		match parsed.errors.into_iter().next() {
+			Some(e) => Err(e),
+			None => Ok(parsed.config),
+		}
+	}
+
+	/// Parse configuration text, going on past the lines with errors
+	fn parse_all(text: &str) -> Parsed {
+		let mut parsed = Parsed::default();
+		let config = &mut parsed.config;
+		let mut section = String::new();
+		// Policies are looked up once every [policy.<name>] section is read
+		let mut definitions: BTreeMap<String, PolicyDef> = BTreeMap::new();
+		let mut references: Vec<(usize, String, String)> = Vec::new();
+
+		for (nth, line) in text.lines().enumerate() {
+			let line = line.split('#').next().unwrap_or_default().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let err = |e: String| (nth + 1, e);
+
+			if let Some(header) = line.strip_prefix('[') {
+				// Keep going in the section it was meant to start
+				section = header.trim_end_matches(']').trim().to_string();
+				parsed.headers.entry(section.clone()).or_insert(nth + 1);
+				if !header.ends_with(']') {
+					parsed
+						.errors
+						.push(err("unterminated section header".to_string()));
+				}
+				continue;
+			}
+			let mut line = || -> Result<(), (usize, String)> {
+				let (key, raw) = line
+					.split_once('=')
+					.ok_or_else(|| err("expected 'key = value'".to_string()))?;
+				let (key, value) = (key.trim(), Value::parse(raw.trim()).map_err(err)?);
+				if let Some(first) = parsed
+					.keys
+					.insert((section.clone(), key.to_string()), nth + 1)
+				{
+					parsed
+						.keys
+						.insert((section.clone(), key.to_string()), first);
+					parsed.warnings.push(err(format!(
+						"'{}' is set again in [{}] (first on line {})",
+						key, section, first
+					)));
+				}
+
+				match (section.as_str(), key) {
+					("general", "policy") => references.push((
+						nth + 1,
+						section.clone(),
+						value.as_str().map_err(err)?.to_string(),
+					)),
+					("general", "exclude") => {
+						config.general.exclude = value.into_list().map_err(err)?
+					}
+					("general", "blacklist") => {
+						config.general.blacklist = value
+							.into_list()
+							.and_then(|list| {
+								list.iter().map(|entry| parse_blacklisted(entry)).collect()
+							})
+							.map_err(err)?
+					}
+					("general", "oui_db") => {
+						config.general.oui_db = Some(value.as_str().map_err(err)?.into())
+					}
+					("general", "avoid_recent") => {
+						config.general.avoid_recent = value.as_int().map_err(err)? as usize
+					}
+					("general", "timeout") => {
+						let timeout = value.as_str().map_err(err)?;
+						config.general.timeout =
+							schedule::parse_interval(timeout).ok_or_else(|| {
+								err(format!("invalid timeout '{}', expected e.g. 30s", timeout))
+							})?
+					}
+					("general", "hooks_dir") => {
+						config.general.hooks_dir = value.as_str().map_err(err)?.into()
+					}
+					("general", "escalate") => {
+						let method = value.as_str().map_err(err)?;
+						config.general.escalate =
+							escalate::Method::from_str(method, true).map_err(|_| {
+								err(format!(
+									"invalid escalate '{}' (sudo, pkexec or none)",
+									method
+								))
+							})?
+					}
+					("general", "cooldown") => {
+						let cooldown = value.as_str().map_err(err)?;
+						config.general.cooldown = cooldown::parse(cooldown).ok_or_else(|| {
+							err(format!(
+								"invalid cooldown '{}', expected e.g. 5m or 0",
+								cooldown
+							))
+						})?
+					}
+					("general", "state_dir") => {
+						config.general.state_dir = value.as_str().map_err(err)?.into()
+					}
+					("daemon", "hotplug") => {
+						config.daemon.hotplug = value.as_bool().map_err(err)?
+					}
+					("daemon", "dbus") => config.daemon.dbus = value.as_bool().map_err(err)?,
+					("daemon", "polkit") => config.daemon.polkit = value.as_bool().map_err(err)?,
+					// An empty path turns the control socket off
+					("daemon", "control_socket") => {
+						config.daemon.control_socket = Some(value.as_str().map_err(err)?)
+							.filter(|path| !path.is_empty())
+							.map(PathBuf::from)
+					}
+					("daemon", "pid_file") => {
+						config.daemon.pid_file = Some(value.as_str().map_err(err)?)
+							.filter(|path| !path.is_empty())
+							.map(PathBuf::from)
+					}
+					("daemon", "restore_on_exit") => {
+						config.daemon.restore_on_exit = value.as_bool().map_err(err)?
+					}
+					("daemon", "rotate_on_disconnect") => {
+						config.daemon.rotate_on_disconnect = value.as_bool().map_err(err)?
+					}
+					("daemon", "per_network") => {
+						config.daemon.per_network = value.as_bool().map_err(err)?
+					}
+					// An empty path turns the log file off
+					("daemon", "log_file") => {
+						config.daemon.log_file = Some(value.as_str().map_err(err)?)
+							.filter(|path| !path.is_empty())
+							.map(PathBuf::from)
+					}
+					("daemon", "log_format") => {
+						let format = value.as_str().map_err(err)?;
+						config.daemon.log_format =
+							logfile::Format::from_str(format, true).map_err(|_| {
+								err(format!("invalid log format '{}' (text or json)", format))
+							})?
+					}
+					("daemon", "log_max_size") => {
+						let size = value.as_str().map_err(err)?;
+						config.daemon.log_max_size =
+							Some(logfile::parse_size(size).ok_or_else(|| {
+								err(format!("invalid log size '{}', expected e.g. 10M", size))
+							})?)
+					}
+					("daemon", "log_rotate") => {
+						let interval = value.as_str().map_err(err)?;
+						config.daemon.log_rotate =
+							Some(schedule::parse_interval(interval).ok_or_else(|| {
+								err(format!("invalid interval '{}', expected e.g. 1d", interval))
+							})?)
+					}
+					("daemon", "log_keep") => {
+						config.daemon.log_keep = value.as_int().map_err(err)? as usize
+					}
+					("daemon", "metrics") => {
+						let addr = value.as_str().map_err(err)?;
+						config.daemon.metrics = Some(addr.parse().map_err(|_| {
+							err(format!(
+								"invalid metrics address '{}' (e.g. '127.0.0.1:9102')",
+								addr
+							))
+						})?)
+					}
+					(section, "policy") if section.starts_with("interface.") => {
+						match value.as_str().map_err(err)? {
+							"exclude" => config.interface(section).exclude = true,
+							"fixed" => {
+								return Err(err(
+									"a fixed address is set with 'address = \"...\"'".to_string()
+								))
+							}
+							policy => {
+								references.push((nth + 1, section.to_string(), policy.to_string()))
+							}
+						}
+					}
+					(section, "address") if section.starts_with("interface.") => {
+						let addr = value.as_str().map_err(err)?;
+						config.interface(section).address = Some(
+							addr.parse()
+								.map_err(|e| err(format!("invalid address '{}': {}", addr, e)))?,
+						)
+					}
+					(section, "cooldown") if section.starts_with("interface.") => {
+						let cooldown = value.as_str().map_err(err)?;
+						config.interface(section).cooldown =
+							Some(cooldown::parse(cooldown).ok_or_else(|| {
+								err(format!(
+									"invalid cooldown '{}', expected e.g. 5m or 0",
+									cooldown
+								))
+							})?)
+					}
+					(section, "exclude") if section.starts_with("interface.") => {
+						config.interface(section).exclude = value.as_bool().map_err(err)?
+					}
+					(section, "schedule") if section.starts_with("interface.") => {
+						let inter = &section["interface.".len()..];
+						let schedule =
+							parse_schedule(inter, value.as_str().map_err(err)?).map_err(err)?;
+						config.daemon.schedules.push((inter.to_string(), schedule))
+					}
+					(section, key) if section.starts_with("policy.") => {
+						let def = definitions
+							.entry(section["policy.".len()..].to_string())
+							.or_insert(PolicyDef {
+								line:     nth + 1,
+								strategy: Policy::Random,
+								oui:      None,
+								mimic:    None,
+							});
+						let value = value.as_str().map_err(err)?;
+						match key {
+							"strategy" => def.strategy = value.parse().map_err(err)?,
+							"oui" => {
+								def.oui = Some(
+									parse_hex_bytes(value)
+										.and_then(|bytes| <[u8; 3]>::try_from(bytes).ok())
+										.ok_or_else(|| {
+											err(format!("invalid vendor prefix '{}'", value))
+										})?,
+								)
+							}
+							"mimic" => {
+								def.mimic =
+									Some(oui::Category::from_str(value, true).map_err(|_| {
+										err(format!("unknown vendor category '{}'", value))
+									})?)
+							}
+							_ => {
+								return Err(err(format!("unknown key '{}' in [{}]", key, section)))
+							}
+						}
+					}
+					// Every key is an alias
+					("alias", name) => {
+						let alias = value.as_str().map_err(err)?.parse().map_err(err)?;
+						config.aliases.insert(name.to_string(), alias);
+					}
+					// Every key is an interface name
+					("schedule", inter) => {
+						let schedule = value.as_str().map_err(err)?;
+						let schedule = parse_schedule(inter, schedule).map_err(err)?;
+						config.daemon.schedules.push((inter.to_string(), schedule))
+					}
+					_ => return Err(err(format!("unknown key '{}' in [{}]", key, section))),
+				}
+				Ok(())
+			};
+			if let Err(e) = line() {
+				parsed.errors.push(e);
+			}
+		}
+
+		for (name, def) in definitions {
+			match def.build() {
+				Ok(policy) => {
+					config.policies.insert(name, policy);
+				}
+				Err(e) => parsed
+					.errors
+					.push((def.line, format!("[policy.{}]: {}", name, e))),
+			}
+		}
+		for (line, section, name) in references {
+			match config.policy(&name) {
+				Ok(policy) => match section.as_str() {
+					"general" => config.general.policy = policy,
+					section => config.interface(section).policy = Some(policy),
+				},
+				Err(e) => parsed.errors.push((line, e)),
+			}
+		}
+		parsed
+	}
+
+	/// Parse configuration text like [`Config::load`], but report every error
+	/// rather than the first, along with warnings about settings that load
+	/// but won't do what they seem to. `present` are the interfaces of this
+	/// system with their permanent addresses, for the warnings about the ones
+	/// that aren't there (none of those when it's unknown).
+	pub fn check(text: &str, present: Option<&[(String, Option<MacAddr>)]>) -> Vec<Problem> {
+		let parsed = Config::parse_all(text);
+		let config = &parsed.config;
+		let key = |section: &str, key: &str| {
+			parsed
+				.keys
+				.get(&(section.to_string(), key.to_string()))
+				.copied()
+				.unwrap_or_default()
+		};
+		let mut warnings = parsed.warnings;
+
+		for (name, inter) in &config.interfaces {
+			let section = format!("interface.{}", name);
+			if let Some(addr) = inter.address {
+				let line = key(&section, "address");
+				if addr.is_multicast() || addr.is_null() {
+					warnings.push((
+						line,
+						format!("{} isn't an address an interface can use", addr),
+					));
+				} else if config.is_blacklisted(addr) {
+					warnings.push((
+						line,
+						format!("the fixed address of {} is blacklisted", name),
+					));
+				}
+			}
+			if config.is_excluded(name) && (inter.policy.is_some() || inter.address.is_some()) {
+				warnings.push((
+					parsed.headers.get(&section).copied().unwrap_or_default(),
+					format!(
+						"{} is excluded, so its policy or address only applies when asked for",
+						name
+					),
+				));
+			}
+		}
+		if let Some(path) = &config.general.oui_db {
+			if !path.exists() {
+				warnings.push((
+					key("general", "oui_db"),
+					format!("the OUI database {} doesn't exist", path.display()),
+				));
+			}
+		}
+		if config.daemon.polkit && !config.daemon.dbus {
+			warnings.push((
+				key("daemon", "polkit"),
+				"polkit only applies to D-Bus callers, and dbus is off".to_string(),
+			));
+		}
+
+		if let Some(present) = present {
+			let exists = |name: &str| present.iter().any(|(present, _)| present == name);
+			for name in config.general.exclude.iter().filter(|name| !exists(name)) {
+				warnings.push((
+					key("general", "exclude"),
+					format!("excluded interface {} doesn't exist here", name),
+				));
+			}
+			for name in config.interfaces.keys().filter(|name| !exists(name)) {
+				let section = format!("interface.{}", name);
+				warnings.push((
+					parsed.headers.get(&section).copied().unwrap_or_default(),
+					format!("interface {} doesn't exist here", name),
+				));
+			}
+			for (inter, _) in config
+				.daemon
+				.schedules
+				.iter()
+				.filter(|(name, _)| !exists(name))
+			{
+				warnings.push((
+					key("schedule", inter),
+					format!("scheduled interface {} doesn't exist here", inter),
+				));
+			}
+			for (name, alias) in &config.aliases {
+				let target = match alias {
+					Alias::Name(target) if !exists(target) => target.clone(),
+					Alias::Permanent(addr)
+						if !present
+							.iter()
+							.any(|(_, permanent)| *permanent == Some(*addr)) =>
+					{
+						format!("the interface with permanent address {}", addr)
+					}
+					_ => continue,
+				};
+				warnings.push((
+					key("alias", name),
+					format!("alias {} is for {}, which doesn't exist here", name, target),
+				));
+			}
+		}
+
+		let mut problems: Vec<Problem> = parsed
+			.errors
+			.into_iter()
+			.map(|(line, message)| (line, true, message))
+			.chain(
+				warnings
+					.into_iter()
+					.map(|(line, message)| (line, false, message)),
+			)
+			.map(|(line, error, message)| Problem {
+				line,
+				error,
+				message,
+			})
+			.collect();
+		problems.sort_by_key(|problem| problem.line);
+		problems
+	}
+
+	/// The settings of `[interface.<name>]`, created on first use
+	fn interface(&mut self, section: &str) -> &mut Interface {
+		let name = &section["interface.".len()..];
+		self.interfaces.entry(name.to_string()).or_default()
+	}
+}
+
+fn parse_schedule(inter: &str, schedule: &str) -> Result<Schedule, String> {
+	schedule
+		.parse()
+		.map_err(|e| format!("invalid schedule '{}' for {}: {}", schedule, inter, e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Line and whether it's an error, of each problem
+	fn problems(text: &str, present: Option<&[(String, Option<MacAddr>)]>) -> Vec<(usize, bool)> {
+		Config::check(text, present)
+			.iter()
+			.map(|problem| (problem.line, problem.error))
+			.collect()
+	}
+
+	#[test]
+	fn check_accepts_a_valid_configuration() {
+		let text = r#"
+[general]
+policy = "work"
+exclude = ["docker0"]
+
+[policy.work]
+strategy = "full"
+oui = "00:1b:21"
+
+[interface.wlan0]
+schedule = "daily at 03:00"
+
+[alias]
+wifi = "wlan0"
+"#;
+		assert!(Config::check(text, None).is_empty());
+		assert!(Config::parse(text).is_ok());
+	}
+
+	#[test]
+	fn check_reports_every_error() {
+		let text = r#"
+[general]
+policy = "nonexistent"
+avoid_recent = "many"
+
+[daemon
+hotplug = maybe
+
+[interface.eth0]
+address = "02:00:00:00:00"
+"#;
+		assert_eq!(
+			problems(text, None),
+			[(3, true), (4, true), (6, true), (7, true), (10, true)]
+		);
+		assert!(Config::parse(text).is_err());
+	}
+
+	#[test]
+	fn check_warns_about_what_wont_work() {
+		let text = r#"
+[general]
+exclude = ["eth0"]
+blacklist = ["02:00:00:00:00:01"]
+exclude = ["eth0", "eth1"]
+
+[daemon]
+polkit = true
+
+[interface.eth0]
+address = "02:00:00:00:00:02"
+
+[interface.wlan0]
+address = "02:00:00:00:00:01"
+
+[interface.wlan1]
+address = "01:00:5e:00:00:01"
+"#;
+		assert_eq!(
+			problems(text, None),
+			[
+				(5, false),
+				(8, false),
+				(10, false),
+				(14, false),
+				(17, false)
+			]
+		);
+		// Warnings don't keep it from loading
+		assert!(Config::parse(text).is_ok());
+	}
+
+	#[test]
+	fn check_knows_the_interfaces_present() {
+		let text = r#"
+[general]
+exclude = ["docker0"]
+
+[interface.wlan1]
+policy = "full"
+
+[schedule]
+eth1 = "every 1h"
+
+[alias]
+wifi = "wlan0"
+usb = "permanent:00:1b:21:00:00:01"
+dock = "permanent:00:1b:21:00:00:02"
+"#;
+		let present = [
+			(
+				"wlan0".to_string(),
+				Some(MacAddr::new([0x00, 0x1b, 0x21, 0, 0, 0x01])),
+			),
+			("eth0".to_string(), None),
+		];
+		assert_eq!(
+			problems(text, Some(&present)),
+			[(3, false), (5, false), (9, false), (14, false)]
+		);
+		assert!(Config::check(text, None).is_empty());
+	}
+}
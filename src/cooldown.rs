@@ -0,0 +1,85 @@
+//! A minimum time between changes to an interface (`--cooldown`, `cooldown`
+//! in the configuration)
+//!
+//! A script in a loop or an event that keeps firing would otherwise have rac
+//! take the link down and up again and again. The time of the last change
+//! comes from the history, so the cooldown holds across rac processes and the
+//! daemon alike. Putting an interface back (its permanent address, or `rac
+//! undo`) is never held back.
+
+use std::{
+	collections::BTreeMap,
+	sync::OnceLock,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+	error::{self, RacError},
+	ethtool, history, schedule, MacAddr,
+};
+
+struct Cooldowns {
+	default:    Duration,
+	interfaces: BTreeMap<String, Duration>,
+}
+
+static COOLDOWNS: OnceLock<Cooldowns> = OnceLock::new();
+
+/// Set the cooldown of every interface, and the ones of some interfaces
+pub fn set(default: Duration, interfaces: BTreeMap<String, Duration>) {
+	COOLDOWNS
+		.set(Cooldowns {
+			default,
+			interfaces,
+		})
+		.ok();
+}
+
+/// Parse a cooldown like `5m`, or `0` for none
+pub fn parse(cooldown: &str) -> Option<Duration> {
+	match cooldown {
+		"0" => Some(Duration::ZERO),
+		cooldown => schedule::parse_interval(cooldown),
+	}
+}
+
+fn get(inter: &str) -> Duration {
+	COOLDOWNS.get().map_or(Duration::ZERO, |cooldowns| {
+		cooldowns
+			.interfaces
+			.get(inter)
+			.copied()
+			.unwrap_or(cooldowns.default)
+	})
+}
+
+/// Refuse a change to an interface that was changed less than its cooldown
+/// ago
+pub fn check(inter: &str, addr: MacAddr, kind: history::Kind) -> error::Result<()> {
+	let cooldown = get(inter);
+	if cooldown.is_zero()
+		|| kind == history::Kind::Undo
+		|| ethtool::permanent_addr(inter).is_ok_and(|permanent| permanent == Some(addr))
+	{
+		return Ok(());
+	}
+	let Some(last) = history::entries()?
+		.into_iter()
+		.rev()
+		.find(|entry| entry.inter == inter)
+	else {
+		return Ok(());
+	};
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs();
+	let since = Duration::from_secs(now.saturating_sub(last.time));
+	if since >= cooldown {
+		return Ok(());
+	}
+	Err(RacError::Cooldown {
+		inter:     inter.to_string(),
+		remaining: cooldown - since,
+	})
+}
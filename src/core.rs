@@ -0,0 +1,313 @@
+//! The MAC address type, shared by the `rac` binary and the library: parsing,
+//! formatting and the meaning of its bits
+//!
+//! This only needs `core` (no allocations either), so it builds without the
+//! `std` feature.
+
+use ::core::{fmt, net::Ipv6Addr, str::FromStr};
+use rand::{
+	distributions::{Distribution, Standard},
+	Rng, RngCore,
+};
+
+#[derive(Debug)]
+pub enum MacParseError {
+	/// Parsing of the MAC address contained an invalid digit.
+	InvalidDigit,
+	/// The MAC address did not have the correct length.
+	InvalidLength,
+}
+
+impl fmt::Display for MacParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			MacParseError::InvalidDigit => "invalid digit",
+			MacParseError::InvalidLength => "invalid length",
+		})
+	}
+}
+
+impl ::core::error::Error for MacParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr {
+	pub bytes: [u8; 6],
+}
+
+impl MacAddr {
+	pub fn new(bytes: [u8; 6]) -> MacAddr { MacAddr { bytes } }
+
+	/// Generate a valid MAC address
+	#[cfg(feature = "std")]
+	pub fn random() -> MacAddr { MacAddr::random_from(&mut rand::thread_rng()) }
+
+	/// Generate a valid MAC address from a given source of randomness, e.g. a
+	/// hardware RNG without `std`
+	pub fn random_from<R: RngCore + ?Sized>(rng: &mut R) -> MacAddr {
+		let mut addr: MacAddr = MacAddr { bytes: [0; 6] };
+		rng.fill_bytes(&mut addr.bytes);
+		addr.bytes[0] &= 0xfe; // clear multicast bit
+		addr.bytes[0] |= 0x02; // set local assignment bit (IEEE802)
+		addr
+	}
+
+	/// Modified EUI-64 (RFC 4291): FF:FE in the middle, U/L bit flipped, as
+	/// used for IPv6 interface identifiers
+	pub fn to_eui64(self) -> [u8; 8] {
+		let b = self.bytes;
+		[b[0] ^ 0x02, b[1], b[2], 0xff, 0xfe, b[3], b[4], b[5]]
+	}
+
+	/// The MAC a modified EUI-64 was derived from, `None` if it doesn't have
+	/// FF:FE in the middle
+	pub fn from_eui64(eui64: [u8; 8]) -> Option<MacAddr> {
+		let e = eui64;
+		if e[3..5] != [0xff, 0xfe] {
+			return None;
+		}
+		Some(MacAddr::new([e[0] ^ 0x02, e[1], e[2], e[5], e[6], e[7]]))
+	}
+
+	/// Whether the locally administered (U/L) bit is set
+	pub fn is_local(self) -> bool { self.bytes[0] & 0x02 != 0 }
+
+	/// Whether the group (I/G) bit is set
+	pub fn is_multicast(self) -> bool { self.bytes[0] & 0x01 != 0 }
+
+	pub fn is_broadcast(self) -> bool { self.bytes == [0xff; 6] }
+
+	pub fn is_null(self) -> bool { self.bytes == [0; 6] }
+
+	/// The address as a 48-bit integer
+	pub fn to_u64(self) -> u64 {
+		self.bytes
+			.iter()
+			.fold(0, |acc, &byte| (acc << 8) | u64::from(byte))
+	}
+
+	/// The address for the low 48 bits of an integer
+	pub fn from_u64(value: u64) -> MacAddr {
+		let mut bytes = [0u8; 6];
+		bytes.copy_from_slice(&value.to_be_bytes()[2..]);
+		MacAddr::new(bytes)
+	}
+
+	/// The address `step` positions after this one (before, if negative),
+	/// carrying across bytes. `None` if that leaves the 48-bit range.
+	pub fn checked_offset(self, step: i64) -> Option<MacAddr> {
+		let value = i128::from(self.to_u64()) + i128::from(step);
+		if (0..1 << 48).contains(&value) {
+			Some(MacAddr::from_u64(value as u64))
+		} else {
+			None
+		}
+	}
+
+	/// The fe80::/64 address SLAAC derives from this MAC
+	pub fn to_link_local_ipv6(self) -> Ipv6Addr {
+		let mut octets = [0u8; 16];
+		octets[..2].copy_from_slice(&[0xfe, 0x80]);
+		octets[8..].copy_from_slice(&self.to_eui64());
+		Ipv6Addr::from(octets)
+	}
+
+	/// The MAC SLAAC derived an IPv6 address from (link-local or not), `None`
+	/// if its interface identifier isn't a modified EUI-64, e.g. with privacy
+	/// extensions or stable-privacy addresses
+	pub fn from_ipv6(addr: Ipv6Addr) -> Option<MacAddr> {
+		let mut eui64 = [0u8; 8];
+		eui64.copy_from_slice(&addr.octets()[8..]);
+		MacAddr::from_eui64(eui64)
+	}
+}
+
+/// `rng.gen::<MacAddr>()`, like [`MacAddr::random_from`]
+impl Distribution<MacAddr> for Standard {
+	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> MacAddr { MacAddr::random_from(rng) }
+}
+
+/// Random addresses with some of their bits decided, e.g.
+/// `MacGenerator::new().local().unicast().with_oui([0x00, 0x1B, 0x21])`. It's
+/// a [`Distribution`], so `rng.sample(generator)` or `sample_iter` draw from
+/// it. The vendor prefix goes in first and the bits after it, so `.local()`
+/// with an OUI gives an address under that prefix with the U/L bit set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MacGenerator {
+	oui:       Option<[u8; 3]>,
+	local:     Option<bool>,
+	multicast: Option<bool>,
+}
+
+impl MacGenerator {
+	/// Every bit random
+	pub fn new() -> MacGenerator { MacGenerator::default() }
+
+	/// Set the locally administered (U/L) bit
+	pub fn local(mut self) -> MacGenerator {
+		self.local = Some(true);
+		self
+	}
+
+	/// Clear the locally administered (U/L) bit
+	pub fn universal(mut self) -> MacGenerator {
+		self.local = Some(false);
+		self
+	}
+
+	/// Clear the group (I/G) bit
+	pub fn unicast(mut self) -> MacGenerator {
+		self.multicast = Some(false);
+		self
+	}
+
+	/// Set the group (I/G) bit
+	pub fn multicast(mut self) -> MacGenerator {
+		self.multicast = Some(true);
+		self
+	}
+
+	/// Start every address with a vendor prefix
+	pub fn with_oui(mut self, oui: [u8; 3]) -> MacGenerator {
+		self.oui = Some(oui);
+		self
+	}
+}
+
+impl Distribution<MacAddr> for MacGenerator {
+	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> MacAddr {
+		let mut addr = MacAddr { bytes: [0; 6] };
+		rng.fill_bytes(&mut addr.bytes);
+		if let Some(oui) = self.oui {
+			addr.bytes[..3].copy_from_slice(&oui);
+		}
+		match self.local {
+			Some(true) => addr.bytes[0] |= 0x02,
+			Some(false) => addr.bytes[0] &= !0x02,
+			None => {}
+		}
+		match self.multicast {
+			Some(true) => addr.bytes[0] |= 0x01,
+			Some(false) => addr.bytes[0] &= !0x01,
+			None => {}
+		}
+		addr
+	}
+}
+
+impl fmt::Display for MacAddr {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{:<02X}:{:<02X}:{:<02X}:{:<02X}:{:<02X}:{:<02X}",
+			self.bytes[0],
+			self.bytes[1],
+			self.bytes[2],
+			self.bytes[3],
+			self.bytes[4],
+			self.bytes[5]
+		)?;
+		Ok(())
+	}
+}
+
+impl FromStr for MacAddr {
+	type Err = MacParseError;
+
+	fn from_str(input: &str) -> Result<Self, Self::Err> {
+		let mut array = [0u8; 6];
+
+		let mut nth = 0;
+		for byte in input.split([':', '-']) {
+			if nth == 6 {
+				return Err(MacParseError::InvalidLength);
+			}
+
+			array[nth] = u8::from_str_radix(byte, 16).map_err(|_| MacParseError::InvalidDigit)?;
+
+			nth += 1;
+		}
+
+		if nth != 6 {
+			return Err(MacParseError::InvalidLength);
+		}
+
+		Ok(MacAddr::new(array))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn eui64() {
+		let addr = MacAddr::new([0x00, 0x1b, 0x21, 0x3a, 0x4c, 0x5d]);
+		// The U/L bit is flipped, FF:FE goes in the middle
+		assert_eq!(
+			addr.to_eui64(),
+			[0x02, 0x1b, 0x21, 0xff, 0xfe, 0x3a, 0x4c, 0x5d]
+		);
+		assert_eq!(
+			addr.to_link_local_ipv6(),
+			"fe80::21b:21ff:fe3a:4c5d".parse::<Ipv6Addr>().unwrap()
+		);
+	}
+
+	#[test]
+	fn from_eui64() {
+		let addr = MacAddr::new([0x00, 0x1b, 0x21, 0x3a, 0x4c, 0x5d]);
+		assert_eq!(MacAddr::from_eui64(addr.to_eui64()), Some(addr));
+		// Not made from a MAC, no FF:FE in the middle
+		assert_eq!(
+			MacAddr::from_eui64([0x02, 0x1b, 0x21, 0x00, 0x00, 0x3a, 0x4c, 0x5d]),
+			None
+		);
+	}
+
+	#[test]
+	fn from_ipv6() {
+		let addr = MacAddr::new([0x00, 0x1b, 0x21, 0x3a, 0x4c, 0x5d]);
+		assert_eq!(MacAddr::from_ipv6(addr.to_link_local_ipv6()), Some(addr));
+		// Any prefix, only the interface identifier matters
+		assert_eq!(
+			MacAddr::from_ipv6("2001:db8::21b:21ff:fe3a:4c5d".parse().unwrap()),
+			Some(addr)
+		);
+		// A privacy extensions address
+		assert_eq!(
+			MacAddr::from_ipv6("2001:db8::a1b2:c3d4:e5f6:789".parse().unwrap()),
+			None
+		);
+	}
+
+	#[test]
+	fn checked_offset_carries() {
+		assert_eq!(
+			MacAddr::new([0x02, 0, 0, 0, 0, 0xff]).checked_offset(1),
+			Some(MacAddr::new([0x02, 0, 0, 0, 0x01, 0]))
+		);
+		assert_eq!(
+			MacAddr::new([0x02, 0, 0, 0x01, 0, 0]).checked_offset(-1),
+			Some(MacAddr::new([0x02, 0, 0, 0, 0xff, 0xff]))
+		);
+		assert_eq!(
+			MacAddr::new([0x02, 0, 0, 0, 0, 0x05]).checked_offset(0),
+			Some(MacAddr::new([0x02, 0, 0, 0, 0, 0x05]))
+		);
+	}
+
+	#[test]
+	fn checked_offset_stays_in_range() {
+		assert_eq!(MacAddr::new([0xff; 6]).checked_offset(1), None);
+		assert_eq!(MacAddr::new([0; 6]).checked_offset(-1), None);
+		assert_eq!(
+			MacAddr::new([0; 6]).checked_offset((1 << 48) - 1),
+			Some(MacAddr::new([0xff; 6]))
+		);
+		assert_eq!(
+			MacAddr::new([0, 0, 0, 0, 0, 1]).checked_offset(i64::MIN),
+			None
+		);
+		assert_eq!(MacAddr::new([0xff; 6]).checked_offset(i64::MAX), None);
+	}
+}
@@ -0,0 +1,1098 @@
+//! Long-running mode (`rac daemon`)
+//!
+//! Everything runs on one thread around a single `poll(2)`: netlink link
+//! events, D-Bus, the control socket, the metrics endpoint, their clients and
+//! signals (via a signalfd) are file descriptors in the same set. Nothing in
+//! the loop waits on anyone else: clients are read and written as far as they
+//! go without blocking, and calls that need polkit's answer are put aside
+//! until its reply comes in on the bus. The poll timeout is whatever is due
+//! next, a schedule or a deadline of a client or of a call waiting for polkit.
+//! Nothing shares state across threads, so there is no locking, and idling
+//! costs nothing until one of them is ready.
+//!
+//! Only changing an address is done in place, from running `ip` to waiting
+//! for the carrier to come back, so the rest waits for as long as a change
+//! takes (at most `--timeout` for each command, and 10s for the carrier).
+
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	fs,
+	io::{self, BufRead, BufReader, Read, Write},
+	mem,
+	net::{TcpListener, TcpStream},
+	os::unix::{
+		fs::PermissionsExt,
+		io::AsRawFd,
+		net::{UnixListener, UnixStream},
+	},
+	path::{Path, PathBuf},
+	time::{Duration, Instant, SystemTime},
+};
+
+use nix::{
+	poll::{poll, PollFd, PollFlags},
+	sys::{
+		signal::{self, SigSet, Signal},
+		signalfd::{SfdFlags, SignalFd},
+	},
+	unistd::Pid,
+};
+
+use crate::{
+	apply_addr,
+	config::Config,
+	dbus::{self, Arg},
+	error::{self, RacError},
+	escalate, ethtool, event, json, logfile,
+	metrics::{self, Metrics},
+	netlink, networks, report, restrictions, MacAddr,
+};
+
+/// How long a connected interface may take to tell which network it's on
+/// (for wired networks, until it has a default route)
+const IDENTIFY_WAIT: Duration = Duration::from_secs(30);
+
+/// How often interfaces waiting for it try again
+const IDENTIFY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a client of the control socket or the metrics endpoint has to
+/// send its request and take the reply
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest request a client may send
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// How many clients are served at once, more are turned away
+const MAX_CLIENTS: usize = 16;
+
+/// How long a D-Bus call may wait for polkit to answer, while someone may be
+/// looking at the authentication dialog
+const AUTHORIZATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Name, object and interface the D-Bus API is exported as
+const BUS_NAME: &str = "io.github.conelul.Rac1";
+const OBJECT_PATH: &str = "/io/github/conelul/Rac1";
+
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="io.github.conelul.Rac1">
+    <method name="GetCurrent">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="address" type="s" direction="out"/>
+    </method>
+    <method name="SetRandom">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="address" type="s" direction="out"/>
+    </method>
+    <method name="Restore">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="address" type="s" direction="out"/>
+    </method>
+    <method name="ListInterfaces">
+      <arg name="interfaces" type="a(ss)" direction="out"/>
+    </method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect">
+      <arg name="data" type="s" direction="out"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// The control socket, removed again when the daemon exits
+struct Control {
+	listener: UnixListener,
+	path:     PathBuf,
+}
+
+impl Control {
+	fn bind(path: &Path) -> io::Result<Control> {
+		// A socket left behind by a daemon that didn't exit cleanly
+		let _ = fs::remove_file(path);
+		let listener = UnixListener::bind(path)?;
+		fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+		Ok(Control {
+			listener,
+			path: path.to_path_buf(),
+		})
+	}
+}
+
+impl Drop for Control {
+	fn drop(&mut self) { let _ = fs::remove_file(&self.path); }
+}
+
+/// The PID file, removed again when the daemon exits
+struct PidFile {
+	path: PathBuf,
+}
+
+impl PidFile {
+	fn create(path: &Path) -> io::Result<PidFile> {
+		let running = fs::read_to_string(path)
+			.ok()
+			.and_then(|pid| pid.trim().parse().ok())
+			.filter(|pid| signal::kill(Pid::from_raw(*pid), None).is_ok());
+		if let Some(pid) = running {
+			return Err(RacError::Backend(format!(
+				"rac daemon is already running (pid {}, see {})",
+				pid,
+				path.display()
+			))
+			.into());
+		}
+		fs::write(path, format!("{}\n", std::process::id()))?;
+		Ok(PidFile {
+			path: path.to_path_buf(),
+		})
+	}
+}
+
+impl Drop for PidFile {
+	fn drop(&mut self) { let _ = fs::remove_file(&self.path); }
+}
+
+/// A connection to the control socket or the metrics endpoint. It's read and
+/// written as the poll loop finds it ready, so a slow client holds up nothing
+/// else.
+struct Client<S> {
+	stream:   S,
+	/// The request as far as it arrived, then what's left of the reply
+	buf:      Vec<u8>,
+	replying: bool,
+	deadline: Instant,
+}
+
+impl<S: Read + Write + AsRawFd> Client<S> {
+	fn new(stream: S) -> Client<S> {
+		Client {
+			stream,
+			buf: Vec::new(),
+			replying: false,
+			deadline: Instant::now() + CLIENT_TIMEOUT,
+		}
+	}
+
+	fn poll_fd(&self) -> PollFd {
+		let events = if self.replying {
+			PollFlags::POLLOUT
+		} else {
+			PollFlags::POLLIN
+		};
+		PollFd::new(self.stream.as_raw_fd(), events)
+	}
+
+	/// Read what arrived, returning the request once `complete` finds it
+	/// whole or the client stops sending
+	fn read(&mut self, complete: fn(&[u8]) -> bool) -> io::Result<Option<String>> {
+		let mut chunk = [0u8; 1024];
+		loop {
+			match self.stream.read(&mut chunk) {
+				Ok(0) => break,
+				Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+			if complete(&self.buf) {
+				break;
+			}
+			if self.buf.len() > MAX_REQUEST {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					"request too long",
+				));
+			}
+		}
+		Ok(Some(
+			String::from_utf8_lossy(&mem::take(&mut self.buf)).into_owned(),
+		))
+	}
+
+	/// Send what the client takes of the reply, true once it's all sent
+	fn write(&mut self) -> io::Result<bool> {
+		while !self.buf.is_empty() {
+			match self.stream.write(&self.buf) {
+				Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+				Ok(n) => drop(self.buf.drain(..n)),
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(true)
+	}
+
+	/// Go on with the request once the poll loop found the client ready,
+	/// true once it's done with
+	fn step(
+		&mut self,
+		complete: fn(&[u8]) -> bool,
+		answer: impl FnOnce(&str) -> Vec<u8>,
+	) -> io::Result<bool> {
+		if !self.replying {
+			let Some(request) = self.read(complete)? else {
+				return Ok(false);
+			};
+			// Hung up without asking anything
+			if request.is_empty() {
+				return Ok(true);
+			}
+			self.buf = answer(&request);
+			self.replying = true;
+		}
+		self.write()
+	}
+}
+
+/// Take a new client, unless there are too many already
+fn accept<S: Read + Write + AsRawFd>(
+	clients: &mut Vec<Client<S>>,
+	accepted: io::Result<S>,
+	set_nonblocking: fn(&S) -> io::Result<()>,
+) -> io::Result<()> {
+	let stream = accepted?;
+	if clients.len() >= MAX_CLIENTS {
+		return Err(io::Error::other("too many clients, turned one away"));
+	}
+	set_nonblocking(&stream)?;
+	clients.push(Client::new(stream));
+	Ok(())
+}
+
+/// Serve the clients the poll loop found ready, and drop the ones that are
+/// done, failed or took too long. Failures are reported as `event`.
+fn serve_clients<S: Read + Write + AsRawFd>(
+	clients: &mut Vec<Client<S>>,
+	ready: &[bool],
+	complete: fn(&[u8]) -> bool,
+	mut answer: impl FnMut(&str) -> Vec<u8>,
+	(event_name, text): (&str, &str),
+) {
+	let mut ready = ready.iter();
+	clients.retain_mut(|client| {
+		let result = match ready.next() {
+			Some(true) => client.step(complete, &mut answer),
+			_ => Ok(false),
+		}
+		.and_then(|done| {
+			if done || client.deadline > Instant::now() {
+				Ok(done)
+			} else {
+				Err(io::Error::new(
+					io::ErrorKind::TimedOut,
+					"the client took too long",
+				))
+			}
+		});
+		match result {
+			Ok(done) => !done,
+			Err(e) => {
+				report(
+					event(event_name).str("error", &e.to_string()),
+					format!("{}: {}", text, e),
+				);
+				false
+			}
+		}
+	});
+}
+
+/// A D-Bus call waiting for polkit to authorize it, by the serial of the
+/// question
+struct Authorizing {
+	call:     dbus::Message,
+	deadline: Instant,
+}
+
+/// What the daemon keeps track of while running
+struct State {
+	started:   Instant,
+	/// Indexes of the interfaces present
+	known:     HashSet<u32>,
+	/// Whether each interface had a carrier when last heard of, by index
+	carrier:   HashMap<u32, bool>,
+	metrics:   Metrics,
+	/// Addresses interfaces had before the daemon first changed them
+	originals: BTreeMap<String, MacAddr>,
+	/// When each scheduled interface was last rotated
+	last_run:  HashMap<String, SystemTime>,
+	/// Connected interfaces whose network isn't known yet, until when to
+	/// keep trying (`per_network`)
+	joining:   HashMap<String, Instant>,
+}
+
+impl State {
+	/// Remember the address of an interface that's about to be changed
+	fn changing(&mut self, link: &netlink::Link) {
+		if let Some(addr) = link.mac() {
+			self.originals.entry(link.name.clone()).or_insert(addr);
+		}
+	}
+}
+
+/// Run until SIGTERM, applying the configured policy to interfaces as they
+/// appear. SIGHUP reloads the configuration from `path`.
+pub fn run(path: Option<&Path>, mut config: Config) -> io::Result<()> {
+	let _pid_file = config
+		.daemon
+		.pid_file
+		.as_deref()
+		.map(PidFile::create)
+		.transpose()?;
+	// Signals are handled in the loop below instead of killing the daemon
+	let mut mask = SigSet::empty();
+	for sig in [Signal::SIGHUP, Signal::SIGTERM, Signal::SIGINT] {
+		mask.add(sig);
+	}
+	mask.thread_block()?;
+	let mut signals = SignalFd::with_flags(&mask, SfdFlags::SFD_NONBLOCK)?;
+
+	let mut socket = netlink::Socket::new(netlink::RTMGRP_LINK)?;
+	let links = socket.links()?;
+	let mut state = State {
+		started:   Instant::now(),
+		// Interfaces present at startup are left alone
+		known:     links.iter().map(|link| link.index).collect(),
+		carrier:   links
+			.iter()
+			.map(|link| (link.index, link.has_carrier()))
+			.collect(),
+		metrics:   Metrics::new(),
+		originals: BTreeMap::new(),
+		last_run:  HashMap::new(),
+		joining:   HashMap::new(),
+	};
+	let started = SystemTime::now();
+
+	let mut bus = if config.daemon.dbus {
+		let mut bus = dbus::Connection::system()?;
+		bus.request_name(BUS_NAME)?;
+		Some(bus)
+	} else {
+		None
+	};
+	let mut authorizing: HashMap<u32, Authorizing> = HashMap::new();
+	let control = config
+		.daemon
+		.control_socket
+		.as_deref()
+		.map(Control::bind)
+		.transpose()?;
+	let scraper = config.daemon.metrics.map(TcpListener::bind).transpose()?;
+	let mut controls: Vec<Client<UnixStream>> = Vec::new();
+	let mut scrapes: Vec<Client<TcpStream>> = Vec::new();
+	logfile::configure(&config.daemon)
+		.inspect_err(|e| println!("Failed to open the log file: {}", e))?;
+
+	report(
+		event("started")
+			.raw("hotplug", &config.daemon.hotplug.to_string())
+			.str("policy", config.general.policy.name())
+			.raw("dbus", &config.daemon.dbus.to_string()),
+		format!(
+			"rac daemon started (hotplug: {}, policy: {:?}, D-Bus: {})",
+			config.daemon.hotplug, config.general.policy, config.daemon.dbus
+		),
+	);
+	loop {
+		// Unused sources poll a negative fd, which never becomes ready
+		let fds = [
+			socket.as_raw_fd(),
+			bus.as_ref().map_or(-1, |bus| bus.as_raw_fd()),
+			control
+				.as_ref()
+				.map_or(-1, |control| control.listener.as_raw_fd()),
+			scraper.as_ref().map_or(-1, |scraper| scraper.as_raw_fd()),
+			signals.as_raw_fd(),
+		];
+		let mut fds: Vec<_> = fds
+			.into_iter()
+			.map(|fd| PollFd::new(fd, PollFlags::POLLIN))
+			.chain(controls.iter().map(Client::poll_fd))
+			.chain(scrapes.iter().map(Client::poll_fd))
+			.collect();
+		let now = SystemTime::now();
+		let timeout = config
+			.daemon
+			.schedules
+			.iter()
+			.map(|(inter, schedule)| {
+				let last = state.last_run.get(inter).copied().unwrap_or(started);
+				schedule.wait(last, now)
+			})
+			.chain((!state.joining.is_empty()).then_some(IDENTIFY_INTERVAL))
+			.chain(
+				controls
+					.iter()
+					.map(|client| client.deadline)
+					.chain(scrapes.iter().map(|client| client.deadline))
+					.chain(authorizing.values().map(|pending| pending.deadline))
+					.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+			)
+			.min()
+			// Round up so the schedule is due once poll returns
+			.map_or(-1, |wait| {
+				wait.as_millis().saturating_add(1).min(i32::MAX as u128) as i32
+			});
+		poll(&mut fds, timeout)?;
+		let ready_all: Vec<bool> = fds
+			.iter()
+			.map(|fd| fd.revents().is_some_and(|events| !events.is_empty()))
+			.collect();
+		let ready = |n: usize| ready_all[n];
+		let (ready_controls, ready_scrapes) = ready_all[5..].split_at(controls.len());
+
+		// Errors from here on are reported and the daemon goes on
+		let messages = if ready(0) {
+			socket.recv().unwrap_or_else(|e| {
+				report(
+					event("netlink_failed").str("error", &e.to_string()),
+					format!("Failed to read link events: {}", e),
+				);
+				Vec::new()
+			})
+		} else {
+			Vec::new()
+		};
+		for message in messages {
+			match message {
+				netlink::Message::NewLink(link) => {
+					let appeared = state.known.insert(link.index);
+					let had_carrier = state.carrier.insert(link.index, link.has_carrier());
+					if appeared && config.daemon.hotplug {
+						hotplug(&config, &mut state, &link);
+					} else if had_carrier == Some(true) && !link.has_carrier() {
+						disconnected(&config, &mut state, &link);
+					} else if had_carrier == Some(false) && link.has_carrier() {
+						connected(&config, &mut state, &link);
+					}
+				}
+				netlink::Message::DelLink(link) => {
+					state.known.remove(&link.index);
+					state.carrier.remove(&link.index);
+					state.joining.remove(&link.name);
+				}
+				_ => {}
+			}
+		}
+		if let Some(connection) = bus.as_mut().filter(|_| ready(1)) {
+			let handled = match connection.recv() {
+				Ok(message) if message.is_call() => {
+					handle_call(&config, &mut state, connection, &mut authorizing, message)
+				}
+				Ok(message) => match message
+					.reply_serial()
+					.and_then(|serial| authorizing.remove(&serial))
+				{
+					Some(pending) => {
+						let allowed = authorized(&pending.call, message.authorized());
+						finish(&config, &mut state, connection, &pending.call, allowed)
+					}
+					None => Ok(()),
+				},
+				// Whatever was left of the message is still on the socket, so
+				// nothing more can be read from it
+				Err(e) => {
+					report(
+						event("dbus_lost").str("error", &e.to_string()),
+						format!(
+							"Lost the D-Bus connection, D-Bus is off until restart: {}",
+							e
+						),
+					);
+					bus = None;
+					authorizing.clear();
+					Ok(())
+				}
+			};
+			if let Err(e) = handled {
+				report(
+					event("dbus_failed").str("error", &e.to_string()),
+					format!("Failed to answer a D-Bus call: {}", e),
+				);
+			}
+		}
+		if let Some(connection) = bus.as_mut() {
+			let now = Instant::now();
+			for (_, pending) in authorizing.extract_if(|_, pending| pending.deadline <= now) {
+				let denied = Err(RacError::PermissionDenied(
+					"polkit didn't answer in time".to_string(),
+				));
+				if let Err(e) = finish(&config, &mut state, connection, &pending.call, denied) {
+					report(
+						event("dbus_failed").str("error", &e.to_string()),
+						format!("Failed to answer a D-Bus call: {}", e),
+					);
+				}
+			}
+		}
+		serve_clients(
+			&mut controls,
+			ready_controls,
+			|request| request.contains(&b'\n'),
+			|request| answer(&config, &mut state, request),
+			("control_failed", "Control request failed"),
+		);
+		if let Some(control) = control.as_ref().filter(|_| ready(2)) {
+			let accepted = control.listener.accept().map(|(stream, _)| stream);
+			if let Err(e) = accept(&mut controls, accepted, |stream| {
+				stream.set_nonblocking(true)
+			}) {
+				report(
+					event("control_failed").str("error", &e.to_string()),
+					format!("Control request failed: {}", e),
+				);
+			}
+		}
+		serve_clients(
+			&mut scrapes,
+			ready_scrapes,
+			metrics::complete,
+			|request| state.metrics.answer(request),
+			("metrics_failed", "Metrics request failed"),
+		);
+		if let Some(scraper) = scraper.as_ref().filter(|_| ready(3)) {
+			let accepted = scraper.accept().map(|(stream, _)| stream);
+			if let Err(e) = accept(&mut scrapes, accepted, |stream| {
+				stream.set_nonblocking(true)
+			}) {
+				report(
+					event("metrics_failed").str("error", &e.to_string()),
+					format!("Metrics request failed: {}", e),
+				);
+			}
+		}
+		run_schedules(&config, &mut state, started);
+		identify_networks(&config, &mut state);
+		while let Some(info) = signals.read_signal()? {
+			match Signal::try_from(info.ssi_signo as i32) {
+				Ok(Signal::SIGHUP) => reload(path, &mut config),
+				_ => {
+					shutdown(&config, &state);
+					return Ok(());
+				}
+			}
+		}
+	}
+}
+
+/// Rotate the interfaces whose schedule is due
+fn run_schedules(config: &Config, state: &mut State, started: SystemTime) {
+	let now = SystemTime::now();
+	for (inter, schedule) in &config.daemon.schedules {
+		let last = state.last_run.get(inter).copied().unwrap_or(started);
+		if !schedule.wait(last, now).is_zero() {
+			continue;
+		}
+		state.last_run.insert(inter.clone(), now);
+		let excluded = config.is_excluded(inter);
+		report(
+			event("scheduled")
+				.str("interface", inter)
+				.raw("excluded", &excluded.to_string()),
+			format!(
+				"Scheduled rotation: {}{}",
+				inter,
+				if excluded {
+					" (excluded, skipping)"
+				} else {
+					""
+				}
+			),
+		);
+		if excluded {
+			continue;
+		}
+		if let Err(e) = rotate(config, state, inter) {
+			failed(inter, &e, format!("Failed to rotate {}: {}", inter, e));
+		}
+	}
+}
+
+/// Re-read the configuration, keeping the old one if the new one is invalid.
+/// Sockets, D-Bus and the PID file stay as they were started.
+fn reload(path: Option<&Path>, config: &mut Config) {
+	match Config::load(path) {
+		Ok(new) => {
+			*config = new;
+			if let Err(e) = logfile::configure(&config.daemon) {
+				report(
+					event("log_failed").str("error", &e.to_string()),
+					format!("Failed to open the log file: {}", e),
+				);
+			}
+			report(
+				event("reloaded")
+					.raw("hotplug", &config.daemon.hotplug.to_string())
+					.str("policy", config.general.policy.name()),
+				format!(
+					"Reloaded configuration (hotplug: {}, policy: {:?})",
+					config.daemon.hotplug, config.general.policy
+				),
+			);
+		}
+		Err(e) => report(
+			event("reload_failed").str("error", &e.to_string()),
+			format!("Failed to reload configuration, keeping the old one: {}", e),
+		),
+	}
+}
+
+/// Put the original addresses back if configured to, before exiting
+fn shutdown(config: &Config, state: &State) {
+	report(event("stopping"), "rac daemon stopping");
+	if !config.daemon.restore_on_exit {
+		return;
+	}
+	for (name, &addr) in &state.originals {
+		match find_link(name).and_then(|link| apply_addr(&link, addr, restrictions::Mode::Restore))
+		{
+			// The change is an event of its own
+			Ok(()) if crate::ndjson() => {}
+			Ok(()) => println!("Restored {} to {}", name, addr),
+			Err(e) => failed(name, &e, format!("Failed to restore {}: {}", name, e)),
+		}
+	}
+}
+
+/// Randomize a newly appeared interface, before anything brings it up
+fn hotplug(config: &Config, state: &mut State, link: &netlink::Link) {
+	// Only links with a MAC address can be randomized
+	if link.is_loopback() || link.mac().is_none() {
+		return;
+	}
+	let excluded = config.is_excluded(&link.name);
+	report(
+		event("appeared")
+			.str("interface", &link.name)
+			.raw("excluded", &excluded.to_string()),
+		format!(
+			"New interface: {}{}",
+			link.name,
+			if excluded {
+				" (excluded, skipping)"
+			} else {
+				""
+			}
+		),
+	);
+	if excluded {
+		return;
+	}
+
+	let addr = match config.address_for(&link.name, link.mac()) {
+		Ok(addr) => addr,
+		Err(e) => {
+			failed(
+				&link.name,
+				&e,
+				format!("Failed to generate an address for {}: {}", link.name, e),
+			);
+			state.metrics.record(&link.name, &Err::<(), _>(e));
+			return;
+		}
+	};
+	state.changing(link);
+	let result = apply_addr(link, addr, config.mode_for(&link.name));
+	if let Err(e) = &result {
+		failed(
+			&link.name,
+			e,
+			format!("Failed to set MAC address of {}: {}", link.name, e),
+		);
+	}
+	state.metrics.record(&link.name, &result);
+}
+
+/// Randomize an interface that lost its carrier, so it reconnects with a new
+/// address
+fn disconnected(config: &Config, state: &mut State, link: &netlink::Link) {
+	// Taking the interface down (by hand, or while changing its address) also
+	// drops the carrier
+	if !config.daemon.rotate_on_disconnect || !link.is_up() || link.mac().is_none() {
+		return;
+	}
+	let excluded = config.is_excluded(&link.name);
+	report(
+		event("disconnected")
+			.str("interface", &link.name)
+			.raw("excluded", &excluded.to_string()),
+		format!(
+			"{} disconnected{}",
+			link.name,
+			if excluded {
+				" (excluded, skipping)"
+			} else {
+				""
+			}
+		),
+	);
+	if excluded {
+		return;
+	}
+	if let Err(e) = rotate(config, state, &link.name) {
+		failed(
+			&link.name,
+			&e,
+			format!("Failed to rotate {}: {}", link.name, e),
+		);
+	}
+}
+
+/// Start finding out which network an interface that got a carrier is on,
+/// to give it the address assigned to that network
+fn connected(config: &Config, state: &mut State, link: &netlink::Link) {
+	// A fixed address always wins
+	if !config.daemon.per_network
+		|| link.mac().is_none()
+		|| config.is_excluded(&link.name)
+		|| config.fixed_address(&link.name).is_some()
+	{
+		return;
+	}
+	state
+		.joining
+		.insert(link.name.clone(), Instant::now() + IDENTIFY_WAIT);
+	identify_networks(config, state);
+}
+
+/// Look for the networks of the interfaces that are joining one, and give
+/// those found their address
+fn identify_networks(config: &Config, state: &mut State) {
+	let names: Vec<String> = state.joining.keys().cloned().collect();
+	for name in names {
+		let network = match networks::identify(&name) {
+			Ok(Some(network)) => network,
+			Ok(None) if state.joining[&name] > Instant::now() => continue,
+			Ok(None) => {
+				state.joining.remove(&name);
+				report(
+					event("network_unknown").str("interface", &name),
+					format!("{} didn't tell which network it's on, leaving it", name),
+				);
+				continue;
+			}
+			Err(e) => {
+				state.joining.remove(&name);
+				failed(
+					&name,
+					&e,
+					format!("Failed to identify the network of {}: {}", name, e),
+				);
+				continue;
+			}
+		};
+		state.joining.remove(&name);
+		if let Err(e) = join(config, state, &name, &network) {
+			failed(
+				&name,
+				&e,
+				format!("Failed to give {} its address on {}: {}", name, network, e),
+			);
+		}
+	}
+}
+
+/// Give an interface the address assigned to its network, assigning a new
+/// one on first visit
+fn join(config: &Config, state: &mut State, name: &str, network: &str) -> io::Result<()> {
+	let link = find_link(name)?;
+	let (addr, new) = {
+		let mut networks = networks::Networks::open(&config.general.state_dir)?;
+		match networks.get(name, network) {
+			Some(addr) => (addr, false),
+			None => {
+				let addr = config.generate(config.policy_for(name), Some(name), link.mac())?;
+				networks.assign(name, network, addr);
+				networks.save()?;
+				(addr, true)
+			}
+		}
+	};
+	report(
+		event("network")
+			.str("interface", name)
+			.str("network", network)
+			.str("address", &addr.to_string())
+			.raw("new", &new.to_string()),
+		format!(
+			"{} is on {}{}, using {}",
+			name,
+			network,
+			if new { " for the first time" } else { "" },
+			addr
+		),
+	);
+	if link.mac() == Some(addr) {
+		return Ok(());
+	}
+	state.changing(&link);
+	// Assigned addresses are generated with the policy, on the first visit
+	let result = apply_addr(&link, addr, config.policy_for(name).mode());
+	state.metrics.record(name, &result);
+	Ok(result?)
+}
+
+/// Report something that failed for an interface
+fn failed(inter: &str, e: &dyn std::fmt::Display, text: String) {
+	report(
+		event("failed")
+			.str("interface", inter)
+			.str("error", &e.to_string()),
+		text,
+	);
+}
+
+/// Find a link that has a MAC address by name
+fn find_link(name: &str) -> error::Result<netlink::Link> {
+	let link = netlink::Socket::new(0)?
+		.links()?
+		.into_iter()
+		.find(|link| link.name == name)
+		.ok_or_else(|| RacError::NoInterface(name.to_string()))?;
+	if link.mac().is_none() {
+		return Err(RacError::Parse(format!(
+			"{} doesn't use MAC addresses",
+			name
+		)));
+	}
+	Ok(link)
+}
+
+/// D-Bus error name for a failure
+fn error_name(e: &RacError) -> &'static str {
+	match e {
+		RacError::Parse(_) | RacError::Unusable(_) => "io.github.conelul.Rac1.Error.InvalidInput",
+		RacError::NoInterface(_) => "io.github.conelul.Rac1.Error.NoInterface",
+		RacError::PermissionDenied(_) => "io.github.conelul.Rac1.Error.PermissionDenied",
+		RacError::Backend(_) | RacError::Daemon { .. } => "io.github.conelul.Rac1.Error.Backend",
+		RacError::Verification { .. } => "io.github.conelul.Rac1.Error.Verification",
+		RacError::Busy(_) => "io.github.conelul.Rac1.Error.Busy",
+		RacError::Cooldown { .. } => "io.github.conelul.Rac1.Error.Cooldown",
+		RacError::Unsafe(_) => "io.github.conelul.Rac1.Error.Unsafe",
+	}
+}
+
+/// Answer a D-Bus method call, or with `polkit` on, ask polkit about the
+/// ones that change interfaces and leave them to `finish` once it answers
+fn handle_call(
+	config: &Config,
+	state: &mut State,
+	bus: &mut dbus::Connection,
+	authorizing: &mut HashMap<u32, Authorizing>,
+	call: dbus::Message,
+) -> io::Result<()> {
+	let member = call.member.as_deref().unwrap_or_default();
+	let expected = match (call.interface(), member) {
+		("org.freedesktop.DBus.Introspectable", "Introspect") | (BUS_NAME, "ListInterfaces") => "",
+		(BUS_NAME, "GetCurrent" | "SetRandom" | "Restore") => "s",
+		_ => {
+			return bus.reply_error(
+				&call,
+				"org.freedesktop.DBus.Error.UnknownMethod",
+				&format!("no method '{}' on '{}'", member, call.interface()),
+			)
+		}
+	};
+	if call.path() != OBJECT_PATH || call.signature != expected {
+		return bus.reply_error(
+			&call,
+			"org.freedesktop.DBus.Error.InvalidArgs",
+			&format!("{} takes '{}' at {}", member, expected, OBJECT_PATH),
+		);
+	}
+
+	if !matches!(member, "SetRandom" | "Restore") || !config.daemon.polkit {
+		return finish(config, state, bus, &call, Ok(()));
+	}
+	let sender = call.sender().unwrap_or_default();
+	match bus.check_authorization(sender, escalate::CHANGE_ACTION) {
+		Ok(serial) => {
+			authorizing.insert(
+				serial,
+				Authorizing {
+					call,
+					deadline: Instant::now() + AUTHORIZATION_TIMEOUT,
+				},
+			);
+			Ok(())
+		}
+		Err(e) => {
+			let denied = authorized(&call, Err(e));
+			finish(config, state, bus, &call, denied)
+		}
+	}
+}
+
+/// Carry out a method call once it's known whether it's `authorized`, and
+/// reply to it
+fn finish(
+	config: &Config,
+	state: &mut State,
+	bus: &mut dbus::Connection,
+	call: &dbus::Message,
+	authorized: error::Result<()>,
+) -> io::Result<()> {
+	let member = call.member.as_deref().unwrap_or_default();
+	let name = call.str_arg(0).unwrap_or_default();
+	let result = authorized.and_then(|_| match member {
+		"Introspect" => Ok(vec![Arg::Str(INTROSPECTION.to_string())]),
+		"GetCurrent" => current(name).map(|addr| vec![Arg::Str(addr)]),
+		"SetRandom" => rotate(config, state, name).map(|addr| vec![Arg::Str(addr.to_string())]),
+		"Restore" => restore(state, name).map(|addr| vec![Arg::Str(addr.to_string())]),
+		_ => interfaces().map(|pairs| vec![Arg::Pairs(pairs)]),
+	});
+	match result {
+		Ok(args) => bus.reply(call, &args),
+		Err(e) => {
+			report(
+				event("dbus_failed")
+					.str("method", member)
+					.str("error", &e.to_string()),
+				format!("D-Bus {} failed: {}", member, e),
+			);
+			bus.reply_error(call, error_name(&e), &e.to_string())
+		}
+	}
+}
+
+/// What polkit's `answer` means for the caller of `call`
+fn authorized(call: &dbus::Message, answer: io::Result<bool>) -> error::Result<()> {
+	let sender = call.sender().unwrap_or_default();
+	match answer {
+		Ok(true) => Ok(()),
+		Ok(false) => Err(RacError::PermissionDenied(format!(
+			"{} isn't authorized to change interfaces",
+			sender
+		))),
+		Err(e) => Err(RacError::PermissionDenied(format!(
+			"couldn't ask polkit: {}",
+			e
+		))),
+	}
+}
+
+fn current(name: &str) -> error::Result<String> {
+	let link = find_link(name)?;
+	Ok(link.address.map(|a| a.to_string()).unwrap_or_default())
+}
+
+/// Randomize an interface with the configured policy
+fn rotate(config: &Config, state: &mut State, name: &str) -> error::Result<MacAddr> {
+	let link = find_link(name)?;
+	state.changing(&link);
+	let result = config
+		.address_for(name, link.mac())
+		.map_err(|e| RacError::Backend(e.to_string()))
+		.and_then(|addr| apply_addr(&link, addr, config.mode_for(name)).map(|_| addr));
+	state.metrics.record(name, &result);
+	result
+}
+
+/// Go back to an interface's permanent address
+fn restore(state: &mut State, name: &str) -> error::Result<MacAddr> {
+	let link = find_link(name)?;
+	let addr = ethtool::permanent_addr(name)?
+		.ok_or_else(|| RacError::Backend(format!("{} has no permanent address", name)))?;
+	state.changing(&link);
+	apply_addr(&link, addr, restrictions::Mode::Restore)?;
+	Ok(addr)
+}
+
+/// Every interface with its address
+fn interfaces() -> error::Result<Vec<(String, String)>> {
+	let links = netlink::Socket::new(0)?.links()?;
+	Ok(links
+		.into_iter()
+		.filter(|link| !link.is_loopback())
+		.filter_map(|link| {
+			let addr = link.address?.to_string();
+			Some((link.name, addr))
+		})
+		.collect())
+}
+
+/// The reply to a control request: one JSON object per line each way
+fn answer(config: &Config, state: &mut State, line: &str) -> Vec<u8> {
+	let request = json::parse_object(line).unwrap_or_default();
+	let field = |key: &str| {
+		request
+			.iter()
+			.find(|(k, _)| k == key)
+			.map(|(_, value)| value.as_str())
+	};
+	let inter = field("interface").unwrap_or_default();
+	let reply = match field("command") {
+		Some("status") => Ok(json::Object::new()
+			.num("pid", std::process::id())
+			.num("uptime", state.started.elapsed().as_secs())
+			.str("policy", config.general.policy.name())
+			.raw("hotplug", &config.daemon.hotplug.to_string())
+			.raw("dbus", &config.daemon.dbus.to_string())
+			.num("interfaces", state.known.len())),
+		Some("rotate") => rotate(config, state, inter).map(|addr| {
+			json::Object::new()
+				.str("interface", inter)
+				.str("address", &addr.to_string())
+		}),
+		Some("restore") => restore(state, inter).map(|addr| {
+			json::Object::new()
+				.str("interface", inter)
+				.str("address", &addr.to_string())
+		}),
+		Some("current") => current(inter).map(|addr| {
+			json::Object::new()
+				.str("interface", inter)
+				.str("address", &addr)
+		}),
+		_ => Err(RacError::Parse(format!("invalid request: {}", line.trim()))),
+	};
+	let reply = match reply {
+		Ok(object) => object.raw("ok", "true").finish(),
+		Err(e) => json::Object::new()
+			.raw("ok", "false")
+			.str("error", &e.to_string())
+			.num("code", e.exit_code())
+			.finish(),
+	};
+	format!("{}\n", reply).into_bytes()
+}
+
+/// Send a request to the running daemon (`rac ctl`), returning the fields of
+/// its reply
+pub fn request(config: &Config, request: &str) -> error::Result<Vec<(String, String)>> {
+	let path = config
+		.daemon
+		.control_socket
+		.as_deref()
+		.ok_or_else(|| RacError::Backend("the control socket is turned off".to_string()))?;
+	let mut stream = UnixStream::connect(path).map_err(|e| match e.kind() {
+		io::ErrorKind::PermissionDenied => {
+			RacError::PermissionDenied(format!("can't connect to {}", path.display()))
+		}
+		_ => RacError::Backend(format!(
+			"can't connect to {} (is the daemon running?): {}",
+			path.display(),
+			e
+		)),
+	})?;
+	writeln!(stream, "{}", request)?;
+	let mut line = String::new();
+	BufReader::new(&stream).read_line(&mut line)?;
+
+	let reply = json::parse_object(&line)
+		.ok_or_else(|| RacError::Backend(format!("invalid reply: {}", line.trim())))?;
+	let field = |key: &str| reply.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+	if field("ok").as_deref() != Some("true") {
+		return Err(RacError::Daemon {
+			message: field("error").unwrap_or_default(),
+			code:    field("code")
+				.and_then(|code| code.parse().ok())
+				.unwrap_or(1),
+		});
+	}
+	Ok(reply)
+}
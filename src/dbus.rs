@@ -0,0 +1,561 @@
+//! Minimal D-Bus client, just enough to export a few methods on the system bus
+//!
+//! Only the little-endian wire format and `EXTERNAL` authentication are
+//! supported. Message arguments may be basic strings and integers, or an
+//! array of string pairs when replying; asking polkit about a caller takes a
+//! few containers of its own.
+
+use std::{
+	env,
+	io::{self, Read, Write},
+	os::unix::{
+		io::{AsRawFd, RawFd},
+		net::UnixStream,
+	},
+};
+
+use nix::unistd::getuid;
+
+/// Where the system bus listens unless `DBUS_SYSTEM_BUS_ADDRESS` says otherwise
+const SYSTEM_BUS: &str = "/run/dbus/system_bus_socket";
+
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+
+/// Flag set on calls whose sender doesn't want a reply
+const NO_REPLY_EXPECTED: u8 = 0x1;
+
+// Header field codes
+const FIELD_PATH: u8 = 1;
+const FIELD_INTERFACE: u8 = 2;
+const FIELD_MEMBER: u8 = 3;
+const FIELD_ERROR_NAME: u8 = 4;
+const FIELD_REPLY_SERIAL: u8 = 5;
+const FIELD_DESTINATION: u8 = 6;
+const FIELD_SENDER: u8 = 7;
+const FIELD_SIGNATURE: u8 = 8;
+
+fn invalid_data(e: &str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, e.to_string()) }
+
+/// A basic value, in a header field or message body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+	Str(String),
+	Path(String),
+	Signature(String),
+	U32(u32),
+}
+
+impl Value {
+	fn signature(&self) -> &'static str {
+		match self {
+			Value::Str(_) => "s",
+			Value::Path(_) => "o",
+			Value::Signature(_) => "g",
+			Value::U32(_) => "u",
+		}
+	}
+}
+
+/// An argument of a reply, or of a call to polkit
+pub enum Arg {
+	Str(String),
+	U32(u32),
+	/// `a(ss)`
+	Pairs(Vec<(String, String)>),
+	/// `a{ss}`
+	Dict(Vec<(String, String)>),
+	/// polkit's subject for a bus name, `(sa{sv})`
+	Subject(String),
+}
+
+impl Arg {
+	fn signature(&self) -> &'static str {
+		match self {
+			Arg::Str(_) => "s",
+			Arg::U32(_) => "u",
+			Arg::Pairs(_) => "a(ss)",
+			Arg::Dict(_) => "a{ss}",
+			Arg::Subject(_) => "(sa{sv})",
+		}
+	}
+}
+
+/// Serializes values with D-Bus alignment rules
+#[derive(Default)]
+struct Writer {
+	buf: Vec<u8>,
+}
+
+impl Writer {
+	fn pad(&mut self, align: usize) {
+		while !self.buf.len().is_multiple_of(align) {
+			self.buf.push(0);
+		}
+	}
+
+	fn u32(&mut self, value: u32) {
+		self.pad(4);
+		self.buf.extend_from_slice(&value.to_le_bytes());
+	}
+
+	fn str(&mut self, value: &str) {
+		self.u32(value.len() as u32);
+		self.buf.extend_from_slice(value.as_bytes());
+		self.buf.push(0);
+	}
+
+	fn signature(&mut self, value: &str) {
+		self.buf.push(value.len() as u8);
+		self.buf.extend_from_slice(value.as_bytes());
+		self.buf.push(0);
+	}
+
+	fn value(&mut self, value: &Value) {
+		match value {
+			Value::Str(s) | Value::Path(s) => self.str(s),
+			Value::Signature(s) => self.signature(s),
+			Value::U32(u) => self.u32(*u),
+		}
+	}
+
+	/// Write an array of 8-byte aligned elements
+	fn array<T>(&mut self, items: &[T], mut write: impl FnMut(&mut Writer, &T)) {
+		self.u32(0);
+		let len_at = self.buf.len() - 4;
+		// The length doesn't include the padding before the first element
+		self.pad(8);
+		let start = self.buf.len();
+		for item in items {
+			self.pad(8);
+			write(self, item);
+		}
+		let len = (self.buf.len() - start) as u32;
+		self.buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+	}
+
+	fn arg(&mut self, arg: &Arg) {
+		match arg {
+			Arg::Str(s) => self.str(s),
+			Arg::U32(u) => self.u32(*u),
+			// Dictionary entries are aligned like structs
+			Arg::Pairs(pairs) | Arg::Dict(pairs) => self.array(pairs, |w, (a, b)| {
+				w.str(a);
+				w.str(b);
+			}),
+			Arg::Subject(name) => {
+				self.pad(8);
+				self.str("system-bus-name");
+				self.array(&[("name", name)], |w, (key, value)| {
+					w.str(key);
+					w.signature("s");
+					w.str(value);
+				});
+			}
+		}
+	}
+}
+
+/// Deserializes values with D-Bus alignment rules
+struct Reader<'a> {
+	buf: &'a [u8],
+	pos: usize,
+}
+
+impl Reader<'_> {
+	fn align(&mut self, align: usize) { self.pos = self.pos.div_ceil(align) * align; }
+
+	fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+		let bytes = self
+			.buf
+			.get(self.pos..self.pos + len)
+			.ok_or_else(|| invalid_data("truncated D-Bus message"))?;
+		self.pos += len;
+		Ok(bytes)
+	}
+
+	fn u8(&mut self) -> io::Result<u8> { Ok(self.take(1)?[0]) }
+
+	fn u32(&mut self) -> io::Result<u32> {
+		self.align(4);
+		let bytes = self.take(4)?;
+		Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+	}
+
+	fn str(&mut self) -> io::Result<String> {
+		let len = self.u32()? as usize;
+		let s = String::from_utf8_lossy(self.take(len)?).into_owned();
+		self.take(1)?;
+		Ok(s)
+	}
+
+	fn signature(&mut self) -> io::Result<String> {
+		let len = self.u8()? as usize;
+		let s = String::from_utf8_lossy(self.take(len)?).into_owned();
+		self.take(1)?;
+		Ok(s)
+	}
+
+	fn value(&mut self, signature: char) -> io::Result<Value> {
+		Ok(match signature {
+			's' => Value::Str(self.str()?),
+			'o' => Value::Path(self.str()?),
+			'g' => Value::Signature(self.signature()?),
+			'u' => Value::U32(self.u32()?),
+			_ => return Err(invalid_data("unsupported D-Bus type")),
+		})
+	}
+}
+
+/// A received message
+#[derive(Debug)]
+pub struct Message {
+	kind:          u8,
+	flags:         u8,
+	serial:        u32,
+	path:          Option<String>,
+	interface:     Option<String>,
+	pub member:    Option<String>,
+	reply_serial:  Option<u32>,
+	sender:        Option<String>,
+	pub signature: String,
+	/// The arguments, empty if the signature isn't made of basic types only
+	pub args:      Vec<Value>,
+	error_name:    Option<String>,
+	/// The arguments as they came, for the ones that aren't basic
+	body:          Vec<u8>,
+}
+
+impl Message {
+	pub fn is_call(&self) -> bool { self.kind == METHOD_CALL }
+
+	pub fn path(&self) -> &str { self.path.as_deref().unwrap_or_default() }
+
+	pub fn interface(&self) -> &str { self.interface.as_deref().unwrap_or_default() }
+
+	/// The unique bus name of whoever sent the message
+	pub fn sender(&self) -> Option<&str> { self.sender.as_deref() }
+
+	/// The serial of the call this message replies to
+	pub fn reply_serial(&self) -> Option<u32> { self.reply_serial }
+
+	/// polkit's answer to `Connection::check_authorization`: whether the sender
+	/// is authorized
+	pub fn authorized(&self) -> io::Result<bool> {
+		if self.kind == ERROR {
+			return Err(io::Error::other(self.error()));
+		}
+		if !self.signature.starts_with("(bb") {
+			return Err(invalid_data("unexpected reply from polkit"));
+		}
+		// `(bba{ss})`: whether it's authorized comes first
+		let mut reader = Reader {
+			buf: &self.body,
+			pos: 0,
+		};
+		Ok(reader.u32()? != 0)
+	}
+
+	/// What an error reply says went wrong
+	fn error(&self) -> &str {
+		self.str_arg(0)
+			.or(self.error_name.as_deref())
+			.unwrap_or("unknown error")
+	}
+
+	/// The `nth` argument, if it's a string
+	pub fn str_arg(&self, nth: usize) -> Option<&str> {
+		match self.args.get(nth) {
+			Some(Value::Str(s)) => Some(s),
+			_ => None,
+		}
+	}
+}
+
+/// A connection to a message bus
+pub struct Connection {
+	stream: UnixStream,
+	serial: u32,
+}
+
+impl Connection {
+	/// Connect and authenticate to the system bus
+	pub fn system() -> io::Result<Connection> {
+		let path = match env::var("DBUS_SYSTEM_BUS_ADDRESS") {
+			Ok(address) => address
+				.split(';')
+				.find_map(|address| address.strip_prefix("unix:path="))
+				.map(|path| path.split(',').next().unwrap_or(path).to_string())
+				.ok_or_else(|| {
+					io::Error::new(
+						io::ErrorKind::Unsupported,
+						format!("unsupported bus address '{}'", address),
+					)
+				})?,
+			Err(_) => SYSTEM_BUS.to_string(),
+		};
+		let mut connection = Connection {
+			stream: UnixStream::connect(path)?,
+			serial: 0,
+		};
+		connection.authenticate()?;
+		connection.call(
+			"org.freedesktop.DBus",
+			"/org/freedesktop/DBus",
+			"org.freedesktop.DBus",
+			"Hello",
+			&[],
+		)?;
+		Ok(connection)
+	}
+
+	/// `EXTERNAL` authentication, the bus checks our uid on the socket
+	fn authenticate(&mut self) -> io::Result<()> {
+		let uid: String = getuid()
+			.to_string()
+			.bytes()
+			.map(|b| format!("{:02x}", b))
+			.collect();
+		self.stream.write_all(b"\0")?;
+		write!(self.stream, "AUTH EXTERNAL {}\r\n", uid)?;
+
+		// Read byte by byte, so nothing after the line is consumed
+		let mut line = Vec::new();
+		let mut byte = [0u8];
+		while !line.ends_with(b"\r\n") {
+			self.stream.read_exact(&mut byte)?;
+			line.push(byte[0]);
+		}
+		if !line.starts_with(b"OK ") {
+			return Err(io::Error::new(
+				io::ErrorKind::PermissionDenied,
+				"the bus rejected authentication",
+			));
+		}
+		self.stream.write_all(b"BEGIN\r\n")
+	}
+
+	fn send(
+		&mut self,
+		kind: u8,
+		flags: u8,
+		mut fields: Vec<(u8, Value)>,
+		args: &[Arg],
+	) -> io::Result<u32> {
+		let mut body = Writer::default();
+		for arg in args {
+			body.arg(arg);
+		}
+		let signature: String = args.iter().map(Arg::signature).collect();
+		if !signature.is_empty() {
+			fields.push((FIELD_SIGNATURE, Value::Signature(signature)));
+		}
+		self.serial += 1;
+
+		let mut msg = Writer::default();
+		msg.buf.extend_from_slice(&[b'l', kind, flags, 1]);
+		msg.u32(body.buf.len() as u32);
+		msg.u32(self.serial);
+		msg.array(&fields, |w, (code, value)| {
+			w.buf.push(*code);
+			w.signature(value.signature());
+			w.value(value);
+		});
+		msg.pad(8);
+		msg.buf.extend_from_slice(&body.buf);
+		self.stream.write_all(&msg.buf)?;
+		Ok(self.serial)
+	}
+
+	/// Call a method without waiting for its reply, returning the serial that
+	/// the reply answers
+	fn send_call(
+		&mut self,
+		destination: &str,
+		path: &str,
+		interface: &str,
+		member: &str,
+		args: &[Arg],
+	) -> io::Result<u32> {
+		self.send(
+			METHOD_CALL,
+			0,
+			vec![
+				(FIELD_PATH, Value::Path(path.to_string())),
+				(FIELD_INTERFACE, Value::Str(interface.to_string())),
+				(FIELD_MEMBER, Value::Str(member.to_string())),
+				(FIELD_DESTINATION, Value::Str(destination.to_string())),
+			],
+			args,
+		)
+	}
+
+	/// Call a method and wait for its reply, dropping anything else received
+	/// meanwhile (only meant for setting up the connection)
+	fn call(
+		&mut self,
+		destination: &str,
+		path: &str,
+		interface: &str,
+		member: &str,
+		args: &[Arg],
+	) -> io::Result<Message> {
+		let serial = self.send_call(destination, path, interface, member, args)?;
+		loop {
+			let reply = self.recv()?;
+			if reply.reply_serial != Some(serial) {
+				continue;
+			}
+			if reply.kind == ERROR {
+				return Err(io::Error::other(format!(
+					"{} failed: {}",
+					member,
+					reply.error()
+				)));
+			}
+			return Ok(reply);
+		}
+	}
+
+	/// Take ownership of a well-known bus name
+	pub fn request_name(&mut self, name: &str) -> io::Result<()> {
+		// DBUS_NAME_FLAG_DO_NOT_QUEUE
+		let reply = self.call(
+			"org.freedesktop.DBus",
+			"/org/freedesktop/DBus",
+			"org.freedesktop.DBus",
+			"RequestName",
+			&[Arg::Str(name.to_string()), Arg::U32(4)],
+		)?;
+		match reply.args.first() {
+			// Primary owner, or already the owner
+			Some(Value::U32(1)) | Some(Value::U32(4)) => Ok(()),
+			_ => Err(io::Error::new(
+				io::ErrorKind::AddrInUse,
+				format!("couldn't own bus name '{}'", name),
+			)),
+		}
+	}
+
+	/// Ask polkit whether the sender of a call may do `action`, letting it
+	/// show an authentication dialog first. The answer comes later, as the
+	/// reply to the returned serial (see `Message::authorized`).
+	pub fn check_authorization(&mut self, sender: &str, action: &str) -> io::Result<u32> {
+		// AllowUserInteraction
+		self.send_call(
+			"org.freedesktop.PolicyKit1",
+			"/org/freedesktop/PolicyKit1/Authority",
+			"org.freedesktop.PolicyKit1.Authority",
+			"CheckAuthorization",
+			&[
+				Arg::Subject(sender.to_string()),
+				Arg::Str(action.to_string()),
+				Arg::Dict(Vec::new()),
+				Arg::U32(1),
+				Arg::Str(String::new()),
+			],
+		)
+	}
+
+	/// Block until the next message arrives
+	pub fn recv(&mut self) -> io::Result<Message> {
+		let mut header = [0u8; 16];
+		self.stream.read_exact(&mut header)?;
+		if header[0] != b'l' {
+			return Err(invalid_data("big-endian D-Bus messages aren't supported"));
+		}
+		let word = |at: usize| {
+			u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])
+				as usize
+		};
+		let (body_len, fields_len) = (word(4), word(12));
+		let mut buf = header.to_vec();
+		buf.resize((16 + fields_len).div_ceil(8) * 8 + body_len, 0);
+		self.stream.read_exact(&mut buf[16..])?;
+
+		let mut message = Message {
+			kind:         header[1],
+			flags:        header[2],
+			serial:       word(8) as u32,
+			path:         None,
+			interface:    None,
+			member:       None,
+			reply_serial: None,
+			sender:       None,
+			signature:    String::new(),
+			args:         Vec::new(),
+			error_name:   None,
+			body:         Vec::new(),
+		};
+		let mut reader = Reader { buf: &buf, pos: 16 };
+		while reader.pos < 16 + fields_len {
+			reader.align(8);
+			let code = reader.u8()?;
+			let signature = reader.signature()?;
+			let value = reader.value(signature.chars().next().unwrap_or_default())?;
+			match (code, value) {
+				(FIELD_PATH, Value::Path(s)) => message.path = Some(s),
+				(FIELD_INTERFACE, Value::Str(s)) => message.interface = Some(s),
+				(FIELD_MEMBER, Value::Str(s)) => message.member = Some(s),
+				(FIELD_ERROR_NAME, Value::Str(s)) => message.error_name = Some(s),
+				(FIELD_REPLY_SERIAL, Value::U32(u)) => message.reply_serial = Some(u),
+				(FIELD_SENDER, Value::Str(s)) => message.sender = Some(s),
+				(FIELD_SIGNATURE, Value::Signature(s)) => message.signature = s,
+				_ => {}
+			}
+		}
+
+		reader.align(8);
+		message.body = buf[reader.pos.min(buf.len())..].to_vec();
+		let mut args = Vec::new();
+		for signature in message.signature.chars() {
+			match reader.value(signature) {
+				Ok(value) => args.push(value),
+				// Leave containers and other types to the signature check
+				Err(_) => {
+					args.clear();
+					break;
+				}
+			}
+		}
+		message.args = args;
+		Ok(message)
+	}
+
+	/// Reply to a method call
+	pub fn reply(&mut self, call: &Message, args: &[Arg]) -> io::Result<()> {
+		if call.flags & NO_REPLY_EXPECTED != 0 {
+			return Ok(());
+		}
+		let mut fields = vec![(FIELD_REPLY_SERIAL, Value::U32(call.serial))];
+		fields.extend(
+			call.sender
+				.clone()
+				.map(|sender| (FIELD_DESTINATION, Value::Str(sender))),
+		);
+		self.send(METHOD_RETURN, 0, fields, args).map(|_| ())
+	}
+
+	/// Reply to a method call with an error
+	pub fn reply_error(&mut self, call: &Message, name: &str, message: &str) -> io::Result<()> {
+		if call.flags & NO_REPLY_EXPECTED != 0 {
+			return Ok(());
+		}
+		let mut fields = vec![
+			(FIELD_REPLY_SERIAL, Value::U32(call.serial)),
+			(FIELD_ERROR_NAME, Value::Str(name.to_string())),
+		];
+		fields.extend(
+			call.sender
+				.clone()
+				.map(|sender| (FIELD_DESTINATION, Value::Str(sender))),
+		);
+		self.send(ERROR, 0, fields, &[Arg::Str(message.to_string())])
+			.map(|_| ())
+	}
+}
+
+impl AsRawFd for Connection {
+	fn as_raw_fd(&self) -> RawFd { self.stream.as_raw_fd() }
+}
@@ -0,0 +1,118 @@
+//! DHCP client identifiers (`rac set --rotate-client-id`)
+//!
+//! Most clients identify themselves with a client-id or DUID that's generated
+//! once and kept, so the DHCP server still recognizes the host under a new
+//! MAC address. Whatever is found for dhcpcd, dhclient and NetworkManager is
+//! replaced by an identifier derived from the new address (DUID-LL), or
+//! dropped so the client derives one itself. The clients pick it up the next
+//! time they (re)connect.
+
+use std::{fs, io, path::Path, process::Command};
+
+use crate::{escalate, timeout, MacAddr};
+
+/// Where dhcpcd keeps its DUID, newer versions first
+const DHCPCD_DUID: [&str; 2] = ["/var/lib/dhcpcd/duid", "/etc/dhcpcd.duid"];
+
+/// Where dhclient keeps its leases, which also hold its DUID
+const DHCLIENT_LEASES: [&str; 2] = ["/var/lib/dhcp", "/var/lib/dhclient"];
+
+/// A DUID-LL (type 3, hardware type 1 for Ethernet) for an address, in the
+/// colon-separated hex dhcpcd uses
+fn duid_ll(addr: MacAddr) -> String { format!("00:03:00:01:{}", addr).to_ascii_lowercase() }
+
+/// Replace dhcpcd's DUID, returning the file it was in
+fn dhcpcd(addr: MacAddr) -> io::Result<Option<String>> {
+	for path in DHCPCD_DUID {
+		if Path::new(path).exists() {
+			fs::write(path, format!("{}\n", duid_ll(addr)))?;
+			return Ok(Some(path.to_string()));
+		}
+	}
+	Ok(None)
+}
+
+/// Drop the `default-duid` statements from dhclient's lease files, so it
+/// generates a new DUID from the current address. Returns the files changed.
+fn dhclient() -> io::Result<Vec<String>> {
+	let mut changed = Vec::new();
+	for dir in DHCLIENT_LEASES {
+		let Ok(entries) = fs::read_dir(dir) else {
+			continue;
+		};
+		for entry in entries.flatten() {
+			let path = entry.path();
+			let is_lease = path
+				.file_name()
+				.and_then(|name| name.to_str())
+				.is_some_and(|name| name.starts_with("dhclient") && name.ends_with(".leases"));
+			if !is_lease {
+				continue;
+			}
+			let text = fs::read_to_string(&path)?;
+			if !text
+				.lines()
+				.any(|line| line.trim_start().starts_with("default-duid"))
+			{
+				continue;
+			}
+			let kept: Vec<&str> = text
+				.lines()
+				.filter(|line| !line.trim_start().starts_with("default-duid"))
+				.collect();
+			fs::write(&path, kept.join("\n") + "\n")?;
+			changed.push(path.display().to_string());
+		}
+	}
+	Ok(changed)
+}
+
+/// Make the NetworkManager connection active on an interface derive its
+/// client-id and DUID from the MAC address, returning the connection's name
+fn network_manager(inter: &str) -> io::Result<Option<String>> {
+	let output = match timeout::output(Command::new("nmcli").args([
+		"-g",
+		"GENERAL.CONNECTION",
+		"device",
+		"show",
+		inter,
+	])) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+		result => result?,
+	};
+	let connection = String::from_utf8_lossy(&output.stdout).trim().to_string();
+	if !output.status.success() || connection.is_empty() {
+		return Ok(None);
+	}
+	let status = timeout::status(
+		escalate::command("nmcli")
+			.args(["connection", "modify", &connection])
+			.args(["ipv4.dhcp-client-id", "mac", "ipv6.dhcp-duid", "ll"]),
+	)?;
+	if !status.success() {
+		return Err(io::Error::other(format!(
+			"nmcli couldn't modify the connection '{}'",
+			connection
+		)));
+	}
+	Ok(Some(connection))
+}
+
+/// Rotate the identifiers of every DHCP client found, returning what was
+/// changed
+pub fn rotate(inter: &str, addr: MacAddr) -> io::Result<Vec<String>> {
+	let mut changed = Vec::new();
+	if let Some(path) = dhcpcd(addr)? {
+		changed.push(format!("dhcpcd's DUID ({})", path));
+	}
+	for path in dhclient()? {
+		changed.push(format!("dhclient's DUID ({})", path));
+	}
+	if let Some(connection) = network_manager(inter)? {
+		changed.push(format!(
+			"the client-id of NetworkManager's '{}'",
+			connection
+		));
+	}
+	Ok(changed)
+}
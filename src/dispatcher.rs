@@ -0,0 +1,64 @@
+//! NetworkManager dispatcher hook (`rac install-dispatcher`)
+//!
+//! NetworkManager runs the scripts in `dispatcher.d/pre-up.d` before it brings
+//! a connection up, and waits for them. The hook runs `rac dispatch` on the
+//! interface, which gives it an address from the configuration, so every
+//! connection starts with a fresh one without running the daemon.
+
+use std::{
+	fs::{self, OpenOptions},
+	io::{self, Write},
+	os::unix::fs::OpenOptionsExt,
+	path::Path,
+};
+
+/// Where the hook is installed
+pub const PATH: &str = "/etc/NetworkManager/dispatcher.d/pre-up.d/90-rac";
+
+/// Quote a word for sh
+pub fn quote(word: &str) -> String { format!("'{}'", word.replace('\'', "'\\''")) }
+
+/// The hook, running `exe` with the configuration file `config`, if one was
+/// given
+fn script(exe: &Path, config: Option<&Path>) -> String {
+	let mut command = quote(&exe.to_string_lossy());
+	if let Some(config) = config {
+		command += &format!(" --config {}", quote(&config.to_string_lossy()));
+	}
+	[
+		"#!/bin/sh",
+		"# Installed by `rac install-dispatcher`: give the interface a new address",
+		"# before NetworkManager brings a connection up on it",
+		"[ \"$2\" = pre-up ] || exit 0",
+		&format!("exec {} --plain dispatch \"$1\"", command),
+		"",
+	]
+	.join("\n")
+}
+
+/// Write the hook, replacing an older one. NetworkManager only runs scripts
+/// that are owned by root and not writable by anyone else.
+pub fn install(exe: &Path, config: Option<&Path>) -> io::Result<()> {
+	let path = Path::new(PATH);
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	let tmp = path.with_extension("tmp");
+	OpenOptions::new()
+		.write(true)
+		.create(true)
+		.truncate(true)
+		.mode(0o755)
+		.open(&tmp)?
+		.write_all(script(exe, config).as_bytes())?;
+	fs::rename(&tmp, path)
+}
+
+/// Remove the hook, returning whether it was installed
+pub fn remove() -> io::Result<bool> {
+	match fs::remove_file(PATH) {
+		Ok(()) => Ok(true),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+		Err(e) => Err(e),
+	}
+}
@@ -0,0 +1,375 @@
+//! `rac doctor`: what on this system gets in the way of changing addresses
+//!
+//! Every check says what it found and, when something is off, what to do
+//! about it: the tools and kernel interfaces rac needs, the rights to change
+//! interfaces, radios switched off by rfkill, network managers that put their
+//! own address back, and drivers known to misbehave. Nothing is changed, so
+//! the output is safe to paste into a bug report.
+
+use std::{fs, io, path::Path, process::Command};
+
+use colored::Colorize;
+use nix::unistd::geteuid;
+
+use crate::{dispatcher, escalate, ethtool, json, netlink, nm, quirks, timeout};
+
+/// `CAP_NET_ADMIN`, as a bit of the capability sets in `/proc/self/status`
+const CAP_NET_ADMIN: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+	Ok,
+	Warning,
+	Failed,
+}
+
+impl Status {
+	fn name(self) -> &'static str {
+		match self {
+			Status::Ok => "ok",
+			Status::Warning => "warning",
+			Status::Failed => "failed",
+		}
+	}
+}
+
+/// The result of a check
+struct Finding {
+	check:  String,
+	status: Status,
+	detail: String,
+	/// What to do about it
+	advice: Option<String>,
+}
+
+fn finding(check: &str, status: Status, detail: impl Into<String>) -> Finding {
+	Finding {
+		check: check.to_string(),
+		status,
+		detail: detail.into(),
+		advice: None,
+	}
+}
+
+impl Finding {
+	fn advice(mut self, advice: impl Into<String>) -> Finding {
+		self.advice = Some(advice.into());
+		self
+	}
+}
+
+/// Whether a process with this name (as in `/proc/<pid>/comm`, cut at 15
+/// characters) is running
+fn running(comm: &str) -> bool {
+	fs::read_dir("/proc")
+		.map(|entries| {
+			entries.flatten().any(|entry| {
+				fs::read_to_string(entry.path().join("comm")).is_ok_and(|name| name.trim() == comm)
+			})
+		})
+		.unwrap_or(false)
+}
+
+fn iproute2() -> Finding {
+	match timeout::output(Command::new("ip").arg("-V")) {
+		// e.g. `ip utility, iproute2-6.1.0, libbpf 1.1.0`
+		Ok(output) if output.status.success() => finding(
+			"iproute2",
+			Status::Ok,
+			String::from_utf8_lossy(&output.stdout).trim(),
+		),
+		Ok(_) => finding("iproute2", Status::Failed, "`ip -V` failed")
+			.advice("reinstall iproute2 (rac runs `ip link set` for every change)"),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {
+			finding("iproute2", Status::Failed, "ip isn't installed")
+				.advice("install iproute2 (rac runs `ip link set` for every change)")
+		}
+		Err(e) => finding("iproute2", Status::Failed, e.to_string()),
+	}
+}
+
+fn netlink() -> Finding {
+	match netlink::Socket::new(0).and_then(|mut socket| socket.links()) {
+		Ok(links) => finding(
+			"netlink",
+			Status::Ok,
+			format!("{} interface(s)", links.len()),
+		),
+		Err(e) => finding("netlink", Status::Failed, e.to_string()).advice(
+			"rac reads interfaces over rtnetlink; a sandbox or seccomp profile may be blocking \
+			 AF_NETLINK sockets",
+		),
+	}
+}
+
+/// Whether this process has `CAP_NET_ADMIN` itself
+fn has_net_admin() -> bool {
+	fs::read_to_string("/proc/self/status")
+		.ok()
+		.and_then(|status| {
+			let caps = status
+				.lines()
+				.find_map(|line| line.strip_prefix("CapEff:"))?;
+			u64::from_str_radix(caps.trim(), 16).ok()
+		})
+		.is_some_and(|caps| caps & (1 << CAP_NET_ADMIN) != 0)
+}
+
+fn privileges() -> Finding {
+	const CHECK: &str = "privileges";
+	if geteuid().is_root() {
+		return finding(CHECK, Status::Ok, "running as root");
+	}
+	if has_net_admin() && escalate::get() == escalate::Method::Direct {
+		return finding(CHECK, Status::Ok, "rac has CAP_NET_ADMIN");
+	}
+	match escalate::get() {
+		escalate::Method::Sudo => match timeout::status(
+			Command::new("sudo")
+				.args(["-n", "ip", "-V"])
+				.stdout(std::process::Stdio::null())
+				.stderr(std::process::Stdio::null()),
+		) {
+			Ok(status) if status.success() => {
+				finding(CHECK, Status::Ok, "sudo runs ip without a password")
+			}
+			Ok(_) => finding(CHECK, Status::Warning, "sudo asks for a password to run ip").advice(
+				"rac fails with exit code 4 where sudo can't ask (scripts, the dispatcher hook); \
+				 allow ip without a password in sudoers, or use --escalate pkexec on a desktop",
+			),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {
+				finding(CHECK, Status::Failed, "sudo isn't installed")
+					.advice("use --escalate pkexec, or run rac as root with --escalate none")
+			}
+			Err(e) => finding(CHECK, Status::Failed, format!("can't run sudo: {}", e)),
+		},
+		escalate::Method::Pkexec if escalate::path("pkexec").is_none() => {
+			finding(CHECK, Status::Failed, "pkexec isn't installed")
+				.advice("install polkit, or use --escalate sudo")
+		}
+		escalate::Method::Pkexec if !Path::new(escalate::POLICY_PATH).exists() => finding(
+			CHECK,
+			Status::Warning,
+			"pkexec asks for every command, rac's polkit actions aren't installed",
+		)
+		.advice("run `sudo rac install-polkit`, so a change asks once"),
+		escalate::Method::Pkexec => finding(
+			CHECK,
+			Status::Ok,
+			format!("pkexec, with the actions in {}", escalate::POLICY_PATH),
+		),
+		escalate::Method::Direct => finding(
+			CHECK,
+			Status::Failed,
+			"--escalate none, but rac runs neither as root nor with CAP_NET_ADMIN",
+		)
+		.advice("run rac as root, or use --escalate sudo or pkexec"),
+	}
+}
+
+/// Radios switched off: soft-blocked ones can be turned on with `rfkill`,
+/// hard-blocked ones only with a switch or key
+fn rfkill() -> Vec<Finding> {
+	const CHECK: &str = "rfkill";
+	let Ok(entries) = fs::read_dir("/sys/class/rfkill") else {
+		return vec![finding(CHECK, Status::Ok, "no rfkill devices")];
+	};
+	let mut devices: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+	devices.sort();
+	let read = |device: &Path, file: &str| {
+		fs::read_to_string(device.join(file))
+			.map(|value| value.trim().to_string())
+			.unwrap_or_default()
+	};
+	let mut findings = Vec::new();
+	for device in &devices {
+		let (name, kind) = (read(device, "name"), read(device, "type"));
+		let index = device
+			.file_name()
+			.and_then(|name| name.to_str())
+			.and_then(|name| name.strip_prefix("rfkill"))
+			.unwrap_or_default()
+			.to_string();
+		if read(device, "hard") == "1" {
+			findings.push(
+				finding(
+					CHECK,
+					Status::Warning,
+					format!("{} ({}) is hard-blocked", name, kind),
+				)
+				.advice("turn the radio on with its switch or key; software can't"),
+			);
+		} else if read(device, "soft") == "1" {
+			findings.push(
+				finding(
+					CHECK,
+					Status::Warning,
+					format!("{} ({}) is soft-blocked", name, kind),
+				)
+				.advice(format!("`rfkill unblock {}` turns it back on", index)),
+			);
+		}
+	}
+	if findings.is_empty() {
+		findings.push(finding(
+			CHECK,
+			Status::Ok,
+			format!("{} device(s), none blocked", devices.len()),
+		));
+	}
+	findings
+}
+
+/// Network managers that set addresses of their own
+fn managers(links: &[netlink::Link]) -> Vec<Finding> {
+	const CHECK: &str = "managers";
+	let mut findings = Vec::new();
+	if running("NetworkManager") {
+		let managed: Vec<&str> = links
+			.iter()
+			.filter(|link| nm::is_managed(&link.name).unwrap_or(false))
+			.map(|link| link.name.as_str())
+			.collect();
+		let detail = match managed.is_empty() {
+			true => "NetworkManager is running, managing none of the interfaces".to_string(),
+			false => format!("NetworkManager is running, managing {}", managed.join(", ")),
+		};
+		findings.push(
+			if managed.is_empty() || Path::new(dispatcher::PATH).exists() {
+				finding(CHECK, Status::Ok, detail)
+			} else {
+				finding(CHECK, Status::Warning, detail).advice(
+					"it may put its own address back when it reconnects: use `rac set \
+					 --nm-unmanage`, or `rac install-dispatcher` to have rac give each connection \
+					 an address",
+				)
+			},
+		);
+	}
+	if running("iwd") {
+		findings.push(finding(CHECK, Status::Warning, "iwd is running").advice(
+			"it picks its own address when it reconnects: pin the address with `rac set --iwd \
+			 <SSID>`",
+		));
+	}
+	// `systemd-networkd`, cut to 15 characters
+	if running("systemd-network") {
+		findings.push(
+			finding(CHECK, Status::Warning, "systemd-networkd is running").advice(
+				"MACAddress= in its .network files (or MACAddressPolicy= in .link files) sets \
+				 addresses too; leave them unset for the interfaces rac changes",
+			),
+		);
+	}
+	if findings.is_empty() {
+		findings.push(finding(
+			CHECK,
+			Status::Ok,
+			"no NetworkManager, iwd or systemd-networkd running",
+		));
+	}
+	findings
+}
+
+/// The driver of each interface, and what it's known to get wrong
+fn interfaces(links: &[netlink::Link]) -> Vec<Finding> {
+	links
+		.iter()
+		.map(|link| {
+			let check = format!("interface {}", link.name);
+			match quirks::lookup(&link.name) {
+				Some((driver, problem)) => finding(
+					&check,
+					Status::Warning,
+					format!("driver {} may {}", driver, problem),
+				)
+				.advice(
+					"a change may fail verification or not stick; `rac selftest` shows which ways \
+					 of changing addresses work here",
+				),
+				None => {
+					let driver = ethtool::driver(&link.name).ok().flatten();
+					finding(
+						&check,
+						Status::Ok,
+						driver.map_or("no driver reported".to_string(), |driver| {
+							format!("driver {}, no known quirks", driver)
+						}),
+					)
+				}
+			}
+		})
+		.collect()
+}
+
+/// Run every check and print the findings, failing if any check did
+pub fn run(json_output: bool) -> io::Result<()> {
+	let links: Vec<netlink::Link> = netlink::Socket::new(0)
+		.and_then(|mut socket| socket.links())
+		.map(|links| {
+			links
+				.into_iter()
+				.filter(|link| !link.is_loopback())
+				.collect()
+		})
+		.unwrap_or_default();
+	let mut findings = vec![iproute2(), netlink(), privileges()];
+	findings.extend(rfkill());
+	findings.extend(managers(&links));
+	findings.extend(interfaces(&links));
+
+	let kernel = fs::read_to_string("/proc/sys/kernel/osrelease").unwrap_or_default();
+	let failed = findings
+		.iter()
+		.filter(|finding| finding.status == Status::Failed)
+		.count();
+	if json_output {
+		println!(
+			"{}",
+			json::array(findings.iter().map(|finding| {
+				json::Object::new()
+					.str("check", &finding.check)
+					.str("status", finding.status.name())
+					.str("detail", &finding.detail)
+					.opt_str("advice", finding.advice.as_deref())
+					.finish()
+			}))
+		);
+	} else {
+		println!(
+			"rac {} on Linux {} (--escalate {})",
+			env!("CARGO_PKG_VERSION"),
+			kernel.trim(),
+			escalate::get().name()
+		);
+		let width = findings
+			.iter()
+			.map(|finding| finding.check.len())
+			.max()
+			.unwrap_or_default()
+			.max(9);
+		for finding in &findings {
+			// Padded before the colors, which would count towards the width
+			let status = format!("{:<7}", finding.status.name());
+			let status = match finding.status {
+				Status::Ok => status.green().bold(),
+				Status::Warning => status.yellow().bold(),
+				Status::Failed => status.red().bold(),
+			};
+			println!(
+				"{:<width$}  {}  {}",
+				finding.check,
+				status,
+				finding.detail,
+				width = width
+			);
+			if let Some(advice) = &finding.advice {
+				println!("{:<width$}  {}", "", advice.dimmed(), width = width + 9);
+			}
+		}
+	}
+	if failed > 0 {
+		return Err(io::Error::other(format!("{} check(s) failed", failed)));
+	}
+	Ok(())
+}
@@ -0,0 +1,181 @@
+//! Changing addresses before anything else runs (`rac install-early`)
+//!
+//! Services start after the initramfs has possibly brought up networking,
+//! e.g. for a root filesystem on NFS or an encrypted one unlocked over SSH.
+//! These hooks change the address from inside the initramfs instead, as udev
+//! first sees each interface: a dracut module or an initramfs-tools hook
+//! copies rac, its configuration and a udev rule running `rac dispatch` into
+//! the initramfs. A systemd `.link` file has udev itself pick a random
+//! address, in the initramfs (where it's included) and after.
+
+use std::{fs, io, path::Path};
+
+use clap::ArgEnum;
+
+use crate::{dispatcher::quote, service::File};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Target {
+	Dracut,
+	InitramfsTools,
+	/// A `.link` file for systemd-udevd
+	SystemdLink,
+}
+
+const DRACUT_MODULE: &str = "/usr/lib/dracut/modules.d/90rac";
+const INITRAMFS_TOOLS_HOOK: &str = "/etc/initramfs-tools/hooks/rac";
+const LINK_FILE: &str = "/etc/systemd/network/00-rac.link";
+
+/// Where the copies go in the initramfs
+const RAC: &str = "/usr/bin/rac";
+const CONFIG: &str = "/etc/rac/rac.conf";
+const RESTRICTIONS: &str = crate::restrictions::PATH;
+
+/// The initramfs generator this system uses
+pub fn detect() -> Target {
+	if Path::new("/usr/lib/dracut").is_dir() {
+		Target::Dracut
+	} else if Path::new("/usr/share/initramfs-tools").is_dir() {
+		Target::InitramfsTools
+	} else {
+		Target::SystemdLink
+	}
+}
+
+/// The udev rule giving every interface an address as it appears
+fn rule(random: bool) -> String {
+	format!(
+		"ACTION==\"add\", SUBSYSTEM==\"net\", KERNEL!=\"lo\", RUN+=\"{} --plain dispatch{} $name\"",
+		RAC,
+		if random { " --random" } else { "" }
+	)
+}
+
+/// The files of a hook copying `exe` and the configuration file `config`
+/// into the initramfs, with random addresses or the configured ones. The
+/// `.link` file only does random addresses, leaving out the `exclude`d
+/// interfaces.
+pub fn files(
+	target: Target,
+	exe: &Path,
+	config: Option<&Path>,
+	random: bool,
+	exclude: &[String],
+) -> Vec<File> {
+	let exe = quote(&exe.to_string_lossy());
+	let config = config.map_or(CONFIG.to_string(), |config| {
+		config.to_string_lossy().into_owned()
+	});
+	let config = quote(&config);
+	match target {
+		Target::Dracut => vec![
+			File::new(
+				&format!("{}/module-setup.sh", DRACUT_MODULE),
+				&[
+					"#!/bin/bash",
+					"# Installed by `rac install-early`",
+					"check() { require_binaries ip || return 1; return 0; }",
+					"depends() { return 0; }",
+					"install() {",
+					&format!("\tinst_binary {} {}", exe, RAC),
+					"\tinst_multiple ip",
+					&format!("\t[ -f {} ] && inst_simple {} {}", config, config, CONFIG),
+					&format!("\t[ -f {0} ] && inst_simple {0}", RESTRICTIONS),
+					"\tinst_simple \"$moddir/90-rac.rules\" /etc/udev/rules.d/90-rac.rules",
+					"}",
+				],
+				true,
+			),
+			File::new(
+				&format!("{}/90-rac.rules", DRACUT_MODULE),
+				&[&rule(random)],
+				false,
+			),
+		],
+		Target::InitramfsTools => vec![File::new(
+			INITRAMFS_TOOLS_HOOK,
+			&[
+				"#!/bin/sh",
+				"# Installed by `rac install-early`",
+				"[ \"$1\" = prereqs ] && exit 0",
+				". /usr/share/initramfs-tools/hook-functions",
+				&format!("copy_exec {} {}", exe, RAC),
+				"copy_exec \"$(command -v ip)\"",
+				&format!(
+					"[ -f {} ] && copy_file config {} {}",
+					config, config, CONFIG
+				),
+				&format!("[ -f {0} ] && copy_file config {0}", RESTRICTIONS),
+				"mkdir -p \"$DESTDIR/lib/udev/rules.d\"",
+				&format!(
+					"echo {} > \"$DESTDIR/lib/udev/rules.d/90-rac.rules\"",
+					quote(&rule(random))
+				),
+			],
+			true,
+		)],
+		Target::SystemdLink => {
+			let mut lines = vec![
+				"# Installed by `rac install-early`: random addresses for physical interfaces"
+					.to_string(),
+				"# as soon as udev sees them".to_string(),
+				"[Match]".to_string(),
+				"Type=ether wlan".to_string(),
+				// Only physical devices have a path
+				"Path=?*".to_string(),
+			];
+			if !exclude.is_empty() {
+				lines.push(format!("OriginalName=!{}", exclude.join(" ")));
+			}
+			lines.extend(
+				[
+					"",
+					"[Link]",
+					// A matching file replaces 99-default.link, naming included
+					"NamePolicy=keep kernel database onboard slot path",
+					"AlternativeNamesPolicy=database onboard slot path",
+					"MACAddressPolicy=random",
+				]
+				.map(String::from),
+			);
+			let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+			vec![File::new(LINK_FILE, &lines, false)]
+		}
+	}
+}
+
+/// How to make the installed (or removed) hook take effect
+pub fn hint(target: Target) -> &'static str {
+	match target {
+		Target::Dracut => "Run `dracut -f` to rebuild the initramfs",
+		Target::InitramfsTools => "Run `update-initramfs -u` to rebuild the initramfs",
+		Target::SystemdLink => {
+			"It applies from the next boot, rebuild the initramfs too if it includes udev (`dracut \
+			 -f` or `update-initramfs -u`)"
+		}
+	}
+}
+
+/// Remove a hook, returning what was removed
+pub fn remove(target: Target) -> io::Result<Vec<String>> {
+	let paths = match target {
+		Target::Dracut => vec![
+			format!("{}/module-setup.sh", DRACUT_MODULE),
+			format!("{}/90-rac.rules", DRACUT_MODULE),
+		],
+		Target::InitramfsTools => vec![INITRAMFS_TOOLS_HOOK.to_string()],
+		Target::SystemdLink => vec![LINK_FILE.to_string()],
+	};
+	let mut removed = Vec::new();
+	for path in paths {
+		match fs::remove_file(&path) {
+			Ok(()) => removed.push(path),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(e),
+		}
+	}
+	if target == Target::Dracut {
+		let _ = fs::remove_dir(DRACUT_MODULE);
+	}
+	Ok(removed)
+}
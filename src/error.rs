@@ -0,0 +1,137 @@
+//! Errors of rac's operations
+//!
+//! Functions that parse input or change interfaces return a [`RacError`], so
+//! callers can tell failures apart. It converts into an [`io::Error`] (keeping
+//! the `RacError` as its inner error) for code that deals in `io::Result`.
+
+use std::{error, fmt, io, time::Duration};
+
+use crate::{HwAddr, MacAddr};
+
+pub type Result<T> = std::result::Result<T, RacError>;
+
+#[derive(Debug)]
+pub enum RacError {
+	/// An address, prefix or other argument is invalid
+	Parse(String),
+	/// A valid address that can't be used for an interface (multicast, null,
+	/// ...)
+	Unusable(String),
+	/// There is no interface with this name
+	NoInterface(String),
+	/// Changing the interface needs privileges we don't have
+	PermissionDenied(String),
+	/// The tool or kernel interface making the change failed
+	Backend(String),
+	/// The change went through, but the interface reports another address
+	Verification {
+		inter:    String,
+		expected: MacAddr,
+		actual:   Option<HwAddr>,
+	},
+	/// Another rac process is changing the interface
+	Busy(String),
+	/// The interface was changed too recently (`--cooldown`)
+	Cooldown {
+		inter:     String,
+		remaining: Duration,
+	},
+	/// The change would cut off the SSH session rac runs in (`--force`)
+	Unsafe(String),
+	/// The daemon failed a request (`rac ctl`), with its exit code
+	Daemon { message: String, code: i32 },
+}
+
+impl RacError {
+	/// The process exit code for this failure (see "Exit codes" in the README)
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			RacError::Parse(_) => 2,
+			RacError::NoInterface(_) => 3,
+			RacError::PermissionDenied(_) => 4,
+			RacError::Verification { .. } => 5,
+			RacError::Backend(_) => 6,
+			RacError::Unusable(_) => 7,
+			RacError::Busy(_) => 8,
+			RacError::Cooldown { .. } => 9,
+			RacError::Unsafe(_) => 10,
+			RacError::Daemon { code, .. } => *code,
+		}
+	}
+}
+
+/// The process exit code for any error, 1 unless it comes from a [`RacError`]
+pub fn exit_code(e: &io::Error) -> i32 {
+	match e
+		.get_ref()
+		.and_then(|inner| inner.downcast_ref::<RacError>())
+	{
+		Some(e) => e.exit_code(),
+		None => match e.kind() {
+			io::ErrorKind::InvalidInput => 2,
+			io::ErrorKind::PermissionDenied => 4,
+			_ => 1,
+		},
+	}
+}
+
+impl fmt::Display for RacError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			RacError::Parse(e) | RacError::Unusable(e) | RacError::Unsafe(e) => f.write_str(e),
+			RacError::NoInterface(inter) => write!(f, "interface doesn't exist: '{}'", inter),
+			RacError::PermissionDenied(e) => write!(f, "permission denied: {}", e),
+			RacError::Backend(e) | RacError::Daemon { message: e, .. } => f.write_str(e),
+			RacError::Busy(inter) => write!(
+				f,
+				"{} is being changed by another rac process (pass --wait to wait for it)",
+				inter
+			),
+			RacError::Cooldown { inter, remaining } => write!(
+				f,
+				"{} was changed too recently, the cooldown allows the next change in {}s (see \
+				 --cooldown)",
+				inter,
+				remaining.as_secs().max(1)
+			),
+			RacError::Verification {
+				inter,
+				expected,
+				actual: Some(actual),
+			} => write!(
+				f,
+				"{} has address {} instead of {}",
+				inter, actual, expected
+			),
+			RacError::Verification {
+				inter, expected, ..
+			} => write!(f, "{} has no address instead of {}", inter, expected),
+		}
+	}
+}
+
+impl error::Error for RacError {}
+
+impl From<io::Error> for RacError {
+	fn from(e: io::Error) -> RacError {
+		match e.kind() {
+			io::ErrorKind::PermissionDenied => RacError::PermissionDenied(e.to_string()),
+			_ => RacError::Backend(e.to_string()),
+		}
+	}
+}
+
+impl From<RacError> for io::Error {
+	fn from(e: RacError) -> io::Error {
+		let kind = match e {
+			RacError::Parse(_) | RacError::Unusable(_) => io::ErrorKind::InvalidInput,
+			RacError::NoInterface(_) => io::ErrorKind::NotFound,
+			RacError::PermissionDenied(_) | RacError::Unsafe(_) => io::ErrorKind::PermissionDenied,
+			RacError::Busy(_) | RacError::Cooldown { .. } => io::ErrorKind::WouldBlock,
+			RacError::Backend(_) | RacError::Verification { .. } | RacError::Daemon { .. } => {
+				io::ErrorKind::Other
+			}
+		};
+		io::Error::new(kind, e)
+	}
+}
@@ -0,0 +1,197 @@
+//! How rac gets the rights to change interfaces (`--escalate`, `escalate` in
+//! the configuration)
+//!
+//! rac runs as the user and only runs the commands that need root (`ip`,
+//! `sysctl`, `hostname`, `nmcli`) with more rights: through sudo by default,
+//! or through pkexec, which asks with polkit's authentication dialog on a
+//! desktop. The actions `rac install-polkit` installs let polkit remember the
+//! answer for a few minutes, so a change asks once rather than for every
+//! command. With `none`, rac runs them as it is, for when it already runs as
+//! root or with `CAP_NET_ADMIN`.
+
+use std::{
+	env,
+	fs::{self, OpenOptions},
+	io::{self, Write},
+	os::unix::fs::OpenOptionsExt,
+	path::{Path, PathBuf},
+	process::Command,
+	sync::OnceLock,
+};
+
+use clap::ArgEnum;
+
+/// Where polkit looks for the actions
+pub const POLICY_PATH: &str = "/usr/share/polkit-1/actions/io.github.conelul.rac.policy";
+
+/// The action D-Bus callers need to change an interface
+pub const CHANGE_ACTION: &str = "io.github.conelul.rac.change";
+
+/// The commands run with more rights, each getting an action of its own
+const PROGRAMS: &[&str] = &["ip", "sysctl", "hostname", "nmcli"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Method {
+	/// `sudo <command>`, without a password where rac checks first
+	Sudo,
+	/// `pkexec <command>`, asking through polkit
+	Pkexec,
+	/// The command as it is
+	#[clap(name = "none")]
+	Direct,
+}
+
+impl Method {
+	pub fn name(&self) -> &'static str {
+		self.to_possible_value()
+			.map(|value| value.get_name())
+			.unwrap_or_default()
+	}
+}
+
+static METHOD: OnceLock<Method> = OnceLock::new();
+
+pub fn set(method: Method) { METHOD.set(method).ok(); }
+
+pub fn get() -> Method { METHOD.get().copied().unwrap_or(Method::Sudo) }
+
+/// A command running `program` with the rights to change interfaces
+pub fn command(program: &str) -> Command {
+	match get() {
+		Method::Sudo => {
+			let mut command = Command::new("sudo");
+			command.arg(program);
+			command
+		}
+		// By the path its action is for, see `policy`
+		Method::Pkexec => {
+			let mut command = Command::new("pkexec");
+			command.arg(path(program).unwrap_or_else(|| program.into()));
+			command
+		}
+		Method::Direct => Command::new(program),
+	}
+}
+
+/// Say which program is missing when sudo or pkexec isn't installed
+pub fn missing(e: io::Error) -> io::Error {
+	let program = match get() {
+		Method::Sudo => "sudo",
+		Method::Pkexec => "pkexec",
+		Method::Direct => return e,
+	};
+	match e.kind() {
+		io::ErrorKind::NotFound => io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("{} isn't installed (see --escalate)", program),
+		),
+		_ => e,
+	}
+}
+
+/// Whether a command failed because sudo or pkexec refused to run it (or the
+/// dialog was dismissed), rather than on its own
+pub fn refused(stderr: &str) -> bool {
+	// pkexec: `Error executing command as another user: Not authorized`
+	stderr.starts_with("sudo:") || stderr.starts_with("Error executing command as another user")
+}
+
+/// The full path of a program, from PATH, with symlinks resolved so that the
+/// actions match however PATH finds it (`/bin` is often a link to `/usr/bin`)
+pub fn path(program: &str) -> Option<PathBuf> {
+	env::var_os("PATH").and_then(|path| {
+		env::split_paths(&path)
+			.map(|dir| dir.join(program))
+			.find(|path| path.is_file())
+			.and_then(|path| fs::canonicalize(path).ok())
+	})
+}
+
+fn action(id: &str, description: &str, message: &str, exec: Option<&Path>) -> String {
+	let annotation = exec.map_or(String::new(), |exec| {
+		format!(
+			concat!(
+				"    <annotate key=\"org.freedesktop.policykit.exec.path\">{}</annotate>\n",
+				"    <annotate key=\"org.freedesktop.policykit.exec.allow_gui\">true</annotate>\n"
+			),
+			exec.display()
+		)
+	});
+	format!(
+		concat!(
+			"  <action id=\"{}\">\n",
+			"    <description>{}</description>\n",
+			"    <message>Authentication is required to {}</message>\n",
+			"    <defaults>\n",
+			"      <allow_any>auth_admin</allow_any>\n",
+			"      <allow_inactive>auth_admin</allow_inactive>\n",
+			"      <allow_active>auth_admin_keep</allow_active>\n",
+			"    </defaults>\n",
+			"{}",
+			"  </action>\n"
+		),
+		id, description, message, annotation
+	)
+}
+
+/// The actions: one for D-Bus callers, and one for each of the commands
+/// installed here, by their path (pkexec picks the action by the path of the
+/// program it runs)
+fn policy() -> String {
+	let mut actions = action(
+		CHANGE_ACTION,
+		"Change the MAC address of a network interface",
+		"change the MAC address of a network interface",
+		None,
+	);
+	for program in PROGRAMS {
+		if let Some(path) = path(program) {
+			actions += &action(
+				&format!("io.github.conelul.rac.exec.{}", program),
+				&format!("Run {} for rac", program),
+				&format!("let rac run {}", program),
+				Some(&path),
+			);
+		}
+	}
+	format!(
+		concat!(
+			"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+			"<!DOCTYPE policyconfig PUBLIC \"-//freedesktop//DTD PolicyKit Policy Configuration \
+			 1.0//EN\"\n",
+			" \"http://www.freedesktop.org/standards/PolicyKit/1/policyconfig.dtd\">\n",
+			"<!-- Installed by `rac install-polkit` -->\n",
+			"<policyconfig>\n",
+			"  <vendor>rac</vendor>\n",
+			"{}",
+			"</policyconfig>\n"
+		),
+		actions
+	)
+}
+
+/// Write the actions, replacing older ones
+pub fn install() -> io::Result<()> {
+	let path = Path::new(POLICY_PATH);
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	let tmp = path.with_extension("tmp");
+	OpenOptions::new()
+		.write(true)
+		.create(true)
+		.truncate(true)
+		.mode(0o644)
+		.open(&tmp)?
+		.write_all(policy().as_bytes())?;
+	fs::rename(&tmp, path)
+}
+
+/// Remove the actions, returning whether they were installed
+pub fn remove() -> io::Result<bool> {
+	match fs::remove_file(POLICY_PATH) {
+		Ok(()) => Ok(true),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+		Err(e) => Err(e),
+	}
+}
@@ -0,0 +1,180 @@
+//! ethtool ioctls (`SIOCETHTOOL`), and the older `SIOCSIFHWADDR` for
+//! `rac selftest`
+
+use std::{io, os::unix::io::RawFd};
+
+use nix::{
+	errno::Errno,
+	libc,
+	sys::socket::{socket, AddressFamily, SockFlag, SockType},
+	unistd::close,
+};
+
+use crate::MacAddr;
+
+const SIOCETHTOOL: libc::c_ulong = 0x8946;
+const SIOCSIFHWADDR: libc::c_ulong = 0x8924;
+const ETHTOOL_GDRVINFO: u32 = 0x03;
+const ETHTOOL_GPERMADDR: u32 = 0x20;
+const MAX_ADDR_LEN: usize = 32;
+
+/// `struct ifreq`, with the `ifr_data` member of the union
+#[repr(C)]
+struct IfReq {
+	name: [libc::c_char; libc::IFNAMSIZ],
+	data: *mut libc::c_void,
+	_pad: [u8; 24],
+}
+
+/// `struct ifreq`, with the `ifr_hwaddr` member of the union
+#[repr(C)]
+struct IfReqHwAddr {
+	name:   [libc::c_char; libc::IFNAMSIZ],
+	family: libc::sa_family_t,
+	data:   [u8; 14],
+	_pad:   [u8; 8],
+}
+
+/// `struct ethtool_perm_addr`
+#[repr(C)]
+struct PermAddr {
+	cmd:  u32,
+	size: u32,
+	data: [u8; MAX_ADDR_LEN],
+}
+
+/// `struct ethtool_drvinfo`
+#[repr(C)]
+struct DrvInfo {
+	cmd:        u32,
+	driver:     [u8; 32],
+	version:    [u8; 32],
+	fw_version: [u8; 32],
+	bus_info:   [u8; 32],
+	/// `erom_version`, reserved, and counts of other ethtool data
+	_rest:      [u8; 32 + 12 + 5 * 4],
+}
+
+/// What an interface's driver reports about itself
+#[derive(Debug)]
+pub struct DriverInfo {
+	pub driver:     String,
+	pub version:    String,
+	pub fw_version: String,
+	pub bus_info:   String,
+}
+
+/// Socket used only to issue ioctls on
+struct Socket(RawFd);
+
+impl Socket {
+	fn new() -> io::Result<Socket> {
+		Ok(Socket(socket(
+			AddressFamily::Inet,
+			SockType::Datagram,
+			SockFlag::SOCK_CLOEXEC,
+			None,
+		)?))
+	}
+
+	/// Run an ethtool command, `cmd` points to the command's struct
+	fn ethtool(&self, inter: &str, cmd: *mut libc::c_void) -> io::Result<()> {
+		let mut req = IfReq {
+			name: if_name(inter)?,
+			data: cmd,
+			_pad: [0; 24],
+		};
+		// SAFETY: `req` is a valid ifreq whose data points to a live ethtool struct
+		Errno::result(unsafe { libc::ioctl(self.0, SIOCETHTOOL as _, &mut req) })?;
+		Ok(())
+	}
+}
+
+/// An interface name as the NUL-padded `ifr_name` of an ifreq
+fn if_name(inter: &str) -> io::Result<[libc::c_char; libc::IFNAMSIZ]> {
+	if inter.len() >= libc::IFNAMSIZ {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"interface name too long",
+		));
+	}
+	let mut name = [0; libc::IFNAMSIZ];
+	for (dst, &src) in name.iter_mut().zip(inter.as_bytes()) {
+		*dst = src as libc::c_char;
+	}
+	Ok(name)
+}
+
+impl Drop for Socket {
+	fn drop(&mut self) { let _ = close(self.0); }
+}
+
+/// Get the permanent (burned-in) address of an interface, if it has one
+pub fn permanent_addr(inter: &str) -> io::Result<Option<MacAddr>> {
+	let mut perm = PermAddr {
+		cmd:  ETHTOOL_GPERMADDR,
+		size: MAX_ADDR_LEN as u32,
+		data: [0; MAX_ADDR_LEN],
+	};
+	match Socket::new()?.ethtool(inter, &mut perm as *mut PermAddr as *mut _) {
+		Ok(()) => {}
+		// Virtual devices usually don't implement ethtool at all
+		Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => return Ok(None),
+		Err(e) => return Err(e),
+	}
+	if perm.size != 6 || perm.data[..6].iter().all(|&b| b == 0) {
+		return Ok(None);
+	}
+	let mut bytes = [0u8; 6];
+	bytes.copy_from_slice(&perm.data[..6]);
+	Ok(Some(MacAddr::new(bytes)))
+}
+
+/// Set the address of an interface with `SIOCSIFHWADDR`
+pub fn set_hwaddr(inter: &str, addr: MacAddr) -> io::Result<()> {
+	let mut req = IfReqHwAddr {
+		name:   if_name(inter)?,
+		family: libc::ARPHRD_ETHER,
+		data:   [0; 14],
+		_pad:   [0; 8],
+	};
+	req.data[..6].copy_from_slice(&addr.bytes);
+	let socket = Socket::new()?;
+	// SAFETY: `req` is a valid ifreq with the address in ifr_hwaddr
+	Errno::result(unsafe { libc::ioctl(socket.0, SIOCSIFHWADDR as _, &mut req) })?;
+	Ok(())
+}
+
+/// A NUL-terminated string field, empty if unset
+fn c_string(field: &[u8]) -> String {
+	let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+	String::from_utf8_lossy(&field[..len]).into_owned()
+}
+
+/// Get the name of an interface's driver, if it reports one
+pub fn driver(inter: &str) -> io::Result<Option<String>> {
+	Ok(driver_info(inter)?.map(|info| info.driver))
+}
+
+/// Get what an interface's driver reports about itself (`ETHTOOL_GDRVINFO`)
+pub fn driver_info(inter: &str) -> io::Result<Option<DriverInfo>> {
+	// SAFETY: the struct is plain integers and byte arrays, for which all
+	// zeroes is valid
+	let mut info: DrvInfo = unsafe { std::mem::zeroed() };
+	info.cmd = ETHTOOL_GDRVINFO;
+	match Socket::new()?.ethtool(inter, &mut info as *mut DrvInfo as *mut _) {
+		Ok(()) => {}
+		Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => return Ok(None),
+		Err(e) => return Err(e),
+	}
+	let driver = c_string(&info.driver);
+	if driver.is_empty() {
+		return Ok(None);
+	}
+	Ok(Some(DriverInfo {
+		driver,
+		version: c_string(&info.version),
+		fw_version: c_string(&info.fw_version),
+		bus_info: c_string(&info.bus_info),
+	}))
+}
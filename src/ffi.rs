@@ -0,0 +1,158 @@
+//! C interface (`--features ffi`), declared in `include/rac.h`
+//!
+//! Addresses are passed as 6-byte arrays. Every function returns `RAC_OK`, or
+//! one of the negative `RAC_E*` codes on failure.
+
+use std::{
+	ffi::CStr,
+	os::raw::{c_char, c_int},
+	ptr, slice,
+	str::FromStr,
+};
+
+use crate::{
+	change::{self, Change},
+	error::RacError,
+	escalate, history, restrictions, MacAddr,
+};
+
+pub const RAC_OK: c_int = 0;
+/// An argument is null or isn't valid UTF-8
+pub const RAC_EINVAL: c_int = -1;
+/// The text isn't a MAC address
+pub const RAC_EPARSE: c_int = -2;
+/// The buffer is too small for the result
+pub const RAC_ERANGE: c_int = -3;
+/// `ip` couldn't change the address (or couldn't be run)
+pub const RAC_EBACKEND: c_int = -4;
+/// Not allowed: no CAP_NET_ADMIN, or `/etc/rac/restrictions.conf` refuses it
+pub const RAC_EPERM: c_int = -5;
+/// There is no interface with this name
+pub const RAC_ENODEV: c_int = -6;
+/// Another process is changing the interface, or it was changed too recently
+pub const RAC_EBUSY: c_int = -7;
+/// The change went through, but the interface reports another address
+pub const RAC_EVERIFY: c_int = -8;
+
+/// Bytes `rac_format` writes: `XX:XX:XX:XX:XX:XX` and the NUL
+pub const RAC_FORMAT_LEN: usize = 18;
+
+unsafe fn str_arg<'a>(text: *const c_char) -> Option<&'a str> {
+	if text.is_null() {
+		return None;
+	}
+	CStr::from_ptr(text).to_str().ok()
+}
+
+unsafe fn write_addr(addr: MacAddr, out: *mut u8) {
+	ptr::copy_nonoverlapping(addr.bytes.as_ptr(), out, addr.bytes.len());
+}
+
+/// Parse a MAC address (`:` or `-` separated) into `addr`
+///
+/// # Safety
+///
+/// `text` must be a NUL-terminated string and `addr` must point to 6
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rac_parse(text: *const c_char, addr: *mut u8) -> c_int {
+	let Some(text) = str_arg(text) else {
+		return RAC_EINVAL;
+	};
+	if addr.is_null() {
+		return RAC_EINVAL;
+	}
+	match MacAddr::from_str(text) {
+		Ok(parsed) => {
+			write_addr(parsed, addr);
+			RAC_OK
+		}
+		Err(_) => RAC_EPARSE,
+	}
+}
+
+/// Format an address the way rac prints it, NUL-terminated
+///
+/// # Safety
+///
+/// `addr` must point to 6 bytes and `buf` to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rac_format(addr: *const u8, buf: *mut c_char, len: usize) -> c_int {
+	if addr.is_null() || buf.is_null() {
+		return RAC_EINVAL;
+	}
+	if len < RAC_FORMAT_LEN {
+		return RAC_ERANGE;
+	}
+	let mut bytes = [0; 6];
+	bytes.copy_from_slice(slice::from_raw_parts(addr, 6));
+	let text = MacAddr::new(bytes).to_string();
+	let out = slice::from_raw_parts_mut(buf as *mut u8, len);
+	out[..text.len()].copy_from_slice(text.as_bytes());
+	out[text.len()] = 0;
+	RAC_OK
+}
+
+/// Generate a random unicast, locally administered address into `addr`
+///
+/// # Safety
+///
+/// `addr` must point to 6 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rac_generate_random(addr: *mut u8) -> c_int {
+	if addr.is_null() {
+		return RAC_EINVAL;
+	}
+	write_addr(MacAddr::random(), addr);
+	RAC_OK
+}
+
+fn error_code(e: &RacError) -> c_int {
+	match e {
+		RacError::Parse(_) | RacError::Unusable(_) | RacError::Unsafe(_) => RAC_EINVAL,
+		RacError::NoInterface(_) => RAC_ENODEV,
+		RacError::PermissionDenied(_) => RAC_EPERM,
+		RacError::Busy(_) | RacError::Cooldown { .. } => RAC_EBUSY,
+		RacError::Verification { .. } => RAC_EVERIFY,
+		RacError::Backend(_) | RacError::Daemon { .. } => RAC_EBACKEND,
+	}
+}
+
+/// Set the address of an interface: down, change and back up, like
+/// `rac set` (see [`change::apply`]), within the restrictions and recorded in
+/// the history. Unlike rac, this runs `ip` directly rather than through sudo,
+/// so the caller needs CAP_NET_ADMIN.
+///
+/// # Safety
+///
+/// `interface` must be a NUL-terminated string and `addr` must point to 6
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rac_set_addr(interface: *const c_char, addr: *const u8) -> c_int {
+	let Some(inter) = str_arg(interface) else {
+		return RAC_EINVAL;
+	};
+	if addr.is_null() {
+		return RAC_EINVAL;
+	}
+	let mut bytes = [0; 6];
+	bytes.copy_from_slice(slice::from_raw_parts(addr, 6));
+	let addr = MacAddr::new(bytes);
+	escalate::set(escalate::Method::Direct);
+	// Unreadable restrictions refuse every change, as they do for rac
+	if restrictions::load().is_err() {
+		return RAC_EPERM;
+	}
+	match change::apply(
+		inter,
+		addr,
+		Change::Bounce,
+		history::Kind::Set,
+		restrictions::Mode::given(inter, addr),
+		None,
+		&mut |_| {},
+	) {
+		Ok(_) => RAC_OK,
+		Err(e) => error_code(&e),
+	}
+}
@@ -0,0 +1,137 @@
+//! The history of address changes made by rac, for `rac undo`
+//!
+//! Every change is appended to `<state dir>/history` as one line with the
+//! time, interface, kind of change and the address before and after it:
+//!
+//! ```text
+//! 1760000000 wlan0 set 3C:22:FB:12:34:56 02:AB:CD:EF:01:23
+//! 1760000042 wlan0 undo 02:AB:CD:EF:01:23 3C:22:FB:12:34:56
+//! ```
+
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{self, BufRead, BufReader, Write},
+	os::unix::io::AsRawFd,
+	path::PathBuf,
+	str::FromStr,
+	sync::OnceLock,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use nix::fcntl::{flock, FlockArg};
+
+use crate::MacAddr;
+
+/// Default directory for persistent state (pools, ...), the configuration's
+/// `state_dir`
+pub const DEFAULT_STATE_DIR: &str = "/var/lib/rac";
+
+/// Past this size the oldest half of the history is dropped
+const MAX_SIZE: u64 = 64 * 1024;
+
+/// Where the history is kept, from the configuration's state directory
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_dir(state_dir: PathBuf) { PATH.set(state_dir.join("history")).ok(); }
+
+/// What made a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+	/// Anything but `rac undo`
+	Set,
+	/// `rac undo`, reverting an earlier change
+	Undo,
+}
+
+/// A change to the address of an interface
+#[derive(Debug, Clone)]
+pub struct Entry {
+	/// When it was made, in seconds since the epoch
+	pub time:  u64,
+	pub inter: String,
+	pub kind:  Kind,
+	pub old:   MacAddr,
+	pub new:   MacAddr,
+}
+
+/// The history file
+pub fn path() -> PathBuf {
+	PATH.get()
+		.cloned()
+		.unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR).join("history"))
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+	let [time, inter, kind, old, new] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+		return None;
+	};
+	Some(Entry {
+		time:  time.parse().ok()?,
+		inter: inter.to_string(),
+		kind:  match kind {
+			"set" => Kind::Set,
+			"undo" => Kind::Undo,
+			_ => return None,
+		},
+		old:   MacAddr::from_str(old).ok()?,
+		new:   MacAddr::from_str(new).ok()?,
+	})
+}
+
+/// Append a change to the history
+pub fn record(inter: &str, kind: Kind, old: MacAddr, new: MacAddr) -> io::Result<()> {
+	let path = path();
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	let mut file = OpenOptions::new().append(true).create(true).open(&path)?;
+	flock(file.as_raw_fd(), FlockArg::LockExclusive).map_err(io::Error::from)?;
+	let time = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs();
+	let kind = match kind {
+		Kind::Set => "set",
+		Kind::Undo => "undo",
+	};
+	writeln!(file, "{} {} {} {} {}", time, inter, kind, old, new)?;
+
+	if file.metadata()?.len() > MAX_SIZE {
+		let content = fs::read_to_string(&path)?;
+		let lines: Vec<&str> = content.lines().collect();
+		let kept = lines[lines.len() / 2..].join("\n") + "\n";
+		// Replace the file whole, so readers never see half of it
+		let tmp = path.with_extension("tmp");
+		fs::write(&tmp, kept)?;
+		fs::rename(&tmp, &path)?;
+	}
+	Ok(())
+}
+
+/// All recorded changes, oldest first
+pub fn entries() -> io::Result<Vec<Entry>> {
+	let file = match File::open(path()) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+		result => result?,
+	};
+	let mut entries = Vec::new();
+	for line in BufReader::new(file).lines() {
+		// Skip lines that don't parse rather than losing the whole history
+		entries.extend(parse_line(&line?));
+	}
+	Ok(entries)
+}
+
+/// The change `rac undo` should revert on an interface: the latest one that
+/// hasn't been undone yet, so repeated undos walk further back
+pub fn last_change(entries: &[Entry], inter: &str) -> Option<Entry> {
+	let mut undone = 0;
+	for entry in entries.iter().rev().filter(|entry| entry.inter == inter) {
+		match entry.kind {
+			Kind::Undo => undone += 1,
+			Kind::Set if undone > 0 => undone -= 1,
+			Kind::Set => return Some(entry.clone()),
+		}
+	}
+	None
+}
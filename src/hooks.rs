@@ -0,0 +1,115 @@
+//! Hooks run after every change (`--post-hook`, `hooks_dir` in the
+//! configuration)
+//!
+//! After an address changes, every executable file in the hooks directory
+//! (`/etc/rac/hooks.d` by default) runs in the order of its name, like
+//! run-parts: a name starting with `.` or ending with `~` is skipped, so
+//! packages can drop in `50-firewall` and editors leave no stray backups
+//! running. The command of `--post-hook` runs after them, with `sh -c`. Both
+//! get the change in their environment:
+//!
+//! - `RAC_INTERFACE`: the interface
+//! - `RAC_OLD_MAC`: its address before the change
+//! - `RAC_MAC`: its address now
+//! - `RAC_KIND`: `set`, or `undo` for `rac undo`
+//!
+//! Their output goes to stderr, so it can't get mixed up with `--plain` or
+//! `--json`. A hook that fails is reported, but doesn't fail the change.
+
+use std::{
+	fs, io,
+	os::unix::fs::PermissionsExt,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+	sync::OnceLock,
+};
+
+use crate::{history, timeout, MacAddr};
+
+/// Where hooks are looked for, unless configured otherwise
+pub const DEFAULT_DIR: &str = "/etc/rac/hooks.d";
+
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+static POST_HOOK: OnceLock<String> = OnceLock::new();
+
+pub fn set_dir(dir: PathBuf) { DIR.set(dir).ok(); }
+
+pub fn set_post_hook(command: String) { POST_HOOK.set(command).ok(); }
+
+/// The executable files in the hooks directory, in the order they run
+fn scripts(dir: &Path) -> io::Result<Vec<PathBuf>> {
+	let entries = match fs::read_dir(dir) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+		entries => entries?,
+	};
+	let mut scripts = Vec::new();
+	for entry in entries {
+		let entry = entry?;
+		let name = entry.file_name();
+		let name = name.to_string_lossy();
+		if name.starts_with('.') || name.ends_with('~') {
+			continue;
+		}
+		// Following symlinks, so hooks can be linked in from elsewhere
+		let Ok(metadata) = fs::metadata(entry.path()) else {
+			continue;
+		};
+		if metadata.is_file() && metadata.permissions().mode() & 0o111 != 0 {
+			scripts.push(entry.path());
+		}
+	}
+	scripts.sort();
+	Ok(scripts)
+}
+
+/// Run one hook, returning why it failed
+fn run_one(
+	mut command: Command,
+	inter: &str,
+	kind: history::Kind,
+	old: MacAddr,
+	new: MacAddr,
+) -> Result<(), String> {
+	command
+		.env("RAC_INTERFACE", inter)
+		.env("RAC_OLD_MAC", old.to_string())
+		.env("RAC_MAC", new.to_string())
+		.env(
+			"RAC_KIND",
+			match kind {
+				history::Kind::Set => "set",
+				history::Kind::Undo => "undo",
+			},
+		)
+		.stdin(Stdio::null())
+		.stdout(io::stderr());
+	match timeout::status(&mut command) {
+		Ok(status) if status.success() => Ok(()),
+		Ok(status) => Err(format!("exited with {}", status)),
+		Err(e) => Err(e.to_string()),
+	}
+}
+
+/// Run the hooks for a change, returning the ones that failed and why
+pub fn run(inter: &str, kind: history::Kind, old: MacAddr, new: MacAddr) -> Vec<(String, String)> {
+	let dir = DIR.get().map_or(Path::new(DEFAULT_DIR), PathBuf::as_path);
+	let mut failed = Vec::new();
+	match scripts(dir) {
+		Ok(scripts) => {
+			for script in scripts {
+				if let Err(e) = run_one(Command::new(&script), inter, kind, old, new) {
+					failed.push((script.display().to_string(), e));
+				}
+			}
+		}
+		Err(e) => failed.push((dir.display().to_string(), e.to_string())),
+	}
+	if let Some(post_hook) = POST_HOOK.get() {
+		let mut command = Command::new("sh");
+		command.args(["-c", post_hook]);
+		if let Err(e) = run_one(command, inter, kind, old, new) {
+			failed.push(("--post-hook".to_string(), e));
+		}
+	}
+	failed
+}
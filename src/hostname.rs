@@ -0,0 +1,84 @@
+//! Random transient hostnames (`rac set --randomize-hostname`)
+//!
+//! DHCP clients send the hostname along with their requests, which links a
+//! fresh MAC address straight back to the machine. The original hostname is
+//! saved in `<state dir>/hostname` the first time it's replaced, so
+//! `rac reset` can put it back however many times it was randomized since.
+
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	error::{self, RacError},
+	escalate, timeout,
+};
+
+/// Characters Windows uses in its default hostnames
+const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+fn saved_path(state_dir: &Path) -> PathBuf { state_dir.join("hostname") }
+
+/// The kernel (transient) hostname
+fn current() -> io::Result<String> {
+	let mut buf = [0u8; 256];
+	let name = nix::unistd::gethostname(&mut buf)?;
+	Ok(name.to_string_lossy().into_owned())
+}
+
+/// Run `sudo hostname <name>`, which changes the transient hostname only
+fn set(name: &str) -> error::Result<()> {
+	let output = timeout::output(escalate::command("hostname").arg(name))?;
+	if !output.status.success() {
+		let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+		if escalate::refused(&stderr) || stderr.contains("permitted") {
+			return Err(RacError::PermissionDenied(stderr));
+		}
+		return Err(RacError::Backend(stderr));
+	}
+	Ok(())
+}
+
+/// A name like the ones Windows picks on install (`DESKTOP-1A2B3C4`), which
+/// is about the most common hostname on any network
+fn generate() -> String {
+	let suffix: String = (0..7)
+		.map(|_| CHARSET[rand::random::<usize>() % CHARSET.len()] as char)
+		.collect();
+	format!("DESKTOP-{}", suffix)
+}
+
+/// Replace the hostname with a random one, returning the old and new names
+pub fn randomize(state_dir: &Path) -> io::Result<(String, String)> {
+	let old = current()?;
+	let saved = saved_path(state_dir);
+	// Only the first randomization saves the name, later ones would save a
+	// random one
+	if !saved.exists() {
+		fs::create_dir_all(state_dir)?;
+		fs::write(&saved, format!("{}\n", old))?;
+	}
+	let new = generate();
+	set(&new)?;
+	Ok((old, new))
+}
+
+/// Put back the hostname saved before it was first randomized, returning it
+/// (or `None` if it was never randomized)
+pub fn restore(state_dir: &Path) -> io::Result<Option<String>> {
+	let saved = saved_path(state_dir);
+	let name = match fs::read_to_string(&saved) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+		result => result?.trim().to_string(),
+	};
+	if name.is_empty() {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("{} is empty", saved.display()),
+		));
+	}
+	set(&name)?;
+	fs::remove_file(&saved)?;
+	Ok(Some(name))
+}
@@ -0,0 +1,138 @@
+//! Waiting for an interface to go quiet before bouncing it (`rac set
+//! --when-idle`)
+//!
+//! An interface counts as idle when no established TCP connection uses one of
+//! its addresses (from `/proc/net/tcp` and `/proc/net/tcp6`) and it moves less
+//! than [`IDLE_RATE`] bytes a second, which leaves room for ARP, mDNS and the
+//! like.
+
+use std::{
+	fs, io,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr},
+	thread,
+	time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use nix::{ifaddrs::getifaddrs, sys::socket::SockAddr};
+
+use crate::note;
+
+/// Traffic below this many bytes a second doesn't count as activity
+const IDLE_RATE: u64 = 2048;
+
+/// How long traffic is measured over, each time the interface is checked
+const SAMPLE: Duration = Duration::from_secs(1);
+
+/// `TCP_ESTABLISHED`, as `/proc/net/tcp` shows it
+const ESTABLISHED: &str = "01";
+
+/// The IPv4 and IPv6 addresses on an interface
+fn addresses(inter: &str) -> io::Result<Vec<IpAddr>> {
+	Ok(getifaddrs()?
+		.filter(|ifaddr| ifaddr.interface_name == inter)
+		.filter_map(|ifaddr| match ifaddr.address? {
+			SockAddr::Inet(addr) => Some(addr.to_std().ip()),
+			_ => None,
+		})
+		.collect())
+}
+
+/// An address as `/proc/net/tcp*` prints it: 32-bit words in hex, each in
+/// the host's byte order
+fn parse_proc_addr(hex: &str) -> Option<IpAddr> {
+	let word = |i: usize| -> Option<[u8; 4]> {
+		let word = u32::from_str_radix(hex.get(i * 8..i * 8 + 8)?, 16).ok()?;
+		Some(word.to_ne_bytes())
+	};
+	match hex.len() {
+		8 => Some(Ipv4Addr::from(word(0)?).into()),
+		32 => {
+			let mut bytes = [0; 16];
+			for (i, chunk) in bytes.chunks_mut(4).enumerate() {
+				chunk.copy_from_slice(&word(i)?);
+			}
+			let addr = Ipv6Addr::from(bytes);
+			// IPv4 connections on a dual-stack socket
+			Some(addr.to_ipv4_mapped().map_or(addr.into(), IpAddr::V4))
+		}
+		_ => None,
+	}
+}
+
+/// How many established TCP connections use one of the addresses
+fn sessions(addresses: &[IpAddr]) -> io::Result<usize> {
+	let mut count = 0;
+	for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+		let table = match fs::read_to_string(path) {
+			// No IPv6 in this kernel
+			Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+			result => result?,
+		};
+		count += table
+			.lines()
+			.skip(1)
+			.filter_map(|line| {
+				let fields: Vec<&str> = line.split_whitespace().collect();
+				let local = fields.get(1)?.split(':').next()?;
+				(fields.get(3) == Some(&ESTABLISHED)).then(|| parse_proc_addr(local))?
+			})
+			.filter(|addr| addresses.contains(addr))
+			.count();
+	}
+	Ok(count)
+}
+
+/// The bytes received and sent on an interface so far
+fn bytes(inter: &str) -> io::Result<u64> {
+	let mut total = 0;
+	for counter in ["rx_bytes", "tx_bytes"] {
+		let path = format!("/sys/class/net/{}/statistics/{}", inter, counter);
+		total += fs::read_to_string(&path)?
+			.trim()
+			.parse::<u64>()
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+	}
+	Ok(total)
+}
+
+/// Wait until the interface is idle, failing once `timeout` passes without it
+/// going quiet
+pub fn wait(inter: &str, timeout: Duration) -> io::Result<()> {
+	let start = Instant::now();
+	let mut waiting = false;
+	loop {
+		let before = bytes(inter)?;
+		thread::sleep(SAMPLE);
+		let rate = bytes(inter)?.saturating_sub(before) / SAMPLE.as_secs();
+		let sessions = sessions(&addresses(inter)?)?;
+		if sessions == 0 && rate < IDLE_RATE {
+			if waiting {
+				note(format!("{} is idle now", inter));
+			}
+			return Ok(());
+		}
+		if start.elapsed() >= timeout {
+			println!(
+				"{} still has {} TCP session(s) and {} B/s of traffic",
+				inter.red().bold(),
+				sessions,
+				rate
+			);
+			return Err(io::Error::new(
+				io::ErrorKind::TimedOut,
+				format!("{} didn't go idle within {}s", inter, timeout.as_secs()),
+			));
+		}
+		if !waiting {
+			note(
+				format!(
+					"Waiting for {} to go idle ({} TCP session(s), {} B/s of traffic)",
+					inter, sessions, rate
+				)
+				.yellow(),
+			);
+			waiting = true;
+		}
+	}
+}
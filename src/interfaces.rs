@@ -0,0 +1,331 @@
+//! A snapshot of the system's interfaces, taken with a single netlink dump
+//!
+//! Lookups by name or kind go through one snapshot instead of asking the
+//! kernel again each time. Anything that changes an interface takes a new one
+//! afterwards, a snapshot is never updated.
+
+use std::{
+	io,
+	os::unix::io::AsRawFd,
+	time::{Duration, Instant},
+};
+
+use nix::poll::{poll, PollFd, PollFlags};
+
+use crate::{
+	error::RacError, ethtool, is_physical, is_wireless, netlink, HwAddr, MacAddr, Prefer,
+	SelectArgs,
+};
+
+/// What kind of device an interface is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+	Wireless,
+	/// Backed by a physical device, but not Wi-Fi
+	Wired,
+	/// Bridges, veth, tun/tap, ...
+	Virtual,
+}
+
+impl Kind {
+	pub fn of(name: &str) -> Kind {
+		if is_wireless(name) {
+			Kind::Wireless
+		} else if is_physical(name) {
+			Kind::Wired
+		} else {
+			Kind::Virtual
+		}
+	}
+}
+
+pub struct Interfaces {
+	links: Vec<netlink::Link>,
+}
+
+impl Interfaces {
+	/// Dump every interface on the system
+	pub fn load() -> io::Result<Interfaces> {
+		Ok(Interfaces {
+			links: netlink::Socket::new(0)?.links()?,
+		})
+	}
+
+	/// Wait up to `timeout` for an interface to exist (e.g. a USB adapter
+	/// being plugged in), returning whether it does
+	pub fn wait_for(name: &str, timeout: Duration) -> io::Result<bool> {
+		// Listening before looking, so it can't appear in between
+		let socket = netlink::Socket::new(netlink::RTMGRP_LINK)?;
+		if Interfaces::load()?.contains(name) {
+			return Ok(true);
+		}
+		let deadline = Instant::now() + timeout;
+		loop {
+			let left = deadline.saturating_duration_since(Instant::now());
+			let mut fds = [PollFd::new(socket.as_raw_fd(), PollFlags::POLLIN)];
+			if left.is_zero() || poll(&mut fds, left.as_millis().min(i32::MAX as u128) as i32)? == 0
+			{
+				return Ok(false);
+			}
+			// Created, or renamed to it by udev
+			if socket.recv()?.iter().any(
+				|message| matches!(message, netlink::Message::NewLink(link) if link.name == name),
+			) {
+				return Ok(true);
+			}
+		}
+	}
+
+	pub fn get(&self, name: &str) -> Option<&netlink::Link> {
+		self.links.iter().find(|link| link.name == name)
+	}
+
+	pub fn contains(&self, name: &str) -> bool { self.get(name).is_some() }
+
+	/// The interface's hardware address, if it exists and has one
+	pub fn address(&self, name: &str) -> Option<&HwAddr> { self.get(name)?.address.as_ref() }
+
+	/// The name of the interface that currently has an address. Bridges and
+	/// VLANs share their port's or parent's address, so a single physical
+	/// interface among several matches is taken to be the one meant.
+	pub fn by_mac(&self, addr: MacAddr) -> Result<String, RacError> {
+		let matches: Vec<&str> = self
+			.links
+			.iter()
+			.filter(|link| link.mac() == Some(addr))
+			.map(|link| link.name.as_str())
+			.collect();
+		let physical: Vec<&str> = matches
+			.iter()
+			.copied()
+			.filter(|name| is_physical(name))
+			.collect();
+		match (&matches[..], &physical[..]) {
+			([], _) => Err(RacError::NoInterface(addr.to_string())),
+			([name], _) | (_, [name]) => Ok(name.to_string()),
+			_ => Err(RacError::Parse(format!(
+				"{} is the address of {}, pass one with -i",
+				addr,
+				matches.join(", ")
+			))),
+		}
+	}
+
+	pub fn by_index(&self, index: u32) -> Option<&netlink::Link> {
+		self.links.iter().find(|link| link.index == index)
+	}
+
+	/// The name of the interface whose permanent address this is
+	pub fn by_permanent(&self, addr: MacAddr) -> Option<String> {
+		self.links
+			.iter()
+			.find(|link| ethtool::permanent_addr(&link.name).ok().flatten() == Some(addr))
+			.map(|link| link.name.clone())
+	}
+
+	/// Take one interface out of the snapshot
+	pub fn take(self, name: &str) -> Option<netlink::Link> {
+		self.links.into_iter().find(|link| link.name == name)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &netlink::Link> { self.links.iter() }
+
+	/// Pick an interface (and its address) when none is given
+	pub fn pick(&self, select: SelectArgs) -> Option<(String, HwAddr)> {
+		self.pick_by(select, Kind::of)
+	}
+
+	/// `pick`, with `kind_of` telling what each interface is
+	fn pick_by(&self, select: SelectArgs, kind_of: fn(&str) -> Kind) -> Option<(String, HwAddr)> {
+		let SelectArgs {
+			prefer,
+			include_virtual,
+		} = select;
+		let rank = |link: &&netlink::Link| {
+			let kind = kind_of(&link.name);
+			let kinds = match prefer {
+				Prefer::Wireless => [Kind::Wireless, Kind::Wired],
+				Prefer::Wired => [Kind::Wired, Kind::Wireless],
+				Prefer::Any => return (false, 0),
+			};
+			// Interfaces that are up first, then the preferred kind, the other
+			// one, and virtual ones last
+			(
+				!link.is_up(),
+				kinds.iter().position(|&k| k == kind).unwrap_or(2),
+			)
+		};
+		self.links
+			.iter()
+			.filter(|link| link.address.as_ref().is_some_and(|addr| !addr.is_zero()))
+			// docker0, virbr0, veth pairs, VPN tunnels, ... are rarely what's
+			// meant
+			.filter(|link| {
+				include_virtual || (link.is_up() && kind_of(&link.name) != Kind::Virtual)
+			})
+			// The first of equally ranked ones, in the kernel's order
+			.min_by_key(rank)
+			.and_then(|link| Some((link.name.clone(), link.address.clone()?)))
+	}
+}
+
+impl IntoIterator for Interfaces {
+	type IntoIter = std::vec::IntoIter<netlink::Link>;
+	type Item = netlink::Link;
+
+	fn into_iter(self) -> Self::IntoIter { self.links.into_iter() }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn link(index: u32, name: &str, up: bool, addr: [u8; 6]) -> netlink::Link {
+		netlink::Link {
+			index,
+			name: name.to_string(),
+			flags: if up { nix::libc::IFF_UP as u32 } else { 0 },
+			address: Some(HwAddr::Mac(MacAddr::new(addr))),
+		}
+	}
+
+	/// Names say what they are, none of them exist
+	fn kind_of(name: &str) -> Kind {
+		match &name[..4] {
+			"wifi" => Kind::Wireless,
+			"wire" => Kind::Wired,
+			_ => Kind::Virtual,
+		}
+	}
+
+	fn pick(links: Vec<netlink::Link>, prefer: Prefer, include_virtual: bool) -> Option<String> {
+		let select = SelectArgs {
+			prefer,
+			include_virtual,
+		};
+		Interfaces { links }
+			.pick_by(select, kind_of)
+			.map(|(name, _)| name)
+	}
+
+	/// A bridge and a VLAN on it share an address
+	const SHARED: [u8; 6] = [2, 0, 0, 0, 0, 9];
+
+	fn interfaces() -> Interfaces {
+		Interfaces {
+			links: vec![
+				link(1, "wired0", true, [2, 0, 0, 0, 0, 1]),
+				link(7, "br0", true, SHARED),
+				link(8, "vlan0", true, SHARED),
+			],
+		}
+	}
+
+	#[test]
+	fn pick_prefers_the_kind_asked_for() {
+		let links = || {
+			vec![
+				link(1, "virt0", true, [2, 0, 0, 0, 0, 1]),
+				link(2, "wired0", true, [2, 0, 0, 0, 0, 2]),
+				link(3, "wifi0", true, [2, 0, 0, 0, 0, 3]),
+			]
+		};
+		assert_eq!(
+			pick(links(), Prefer::Wireless, false).as_deref(),
+			Some("wifi0")
+		);
+		assert_eq!(
+			pick(links(), Prefer::Wired, false).as_deref(),
+			Some("wired0")
+		);
+		assert_eq!(pick(links(), Prefer::Any, false).as_deref(), Some("wired0"));
+		assert_eq!(pick(links(), Prefer::Any, true).as_deref(), Some("virt0"));
+	}
+
+	#[test]
+	fn pick_ranks_up_before_kind() {
+		let links = || {
+			vec![
+				link(1, "wifi0", false, [2, 0, 0, 0, 0, 1]),
+				link(2, "virt0", true, [2, 0, 0, 0, 0, 2]),
+				link(3, "wired0", true, [2, 0, 0, 0, 0, 3]),
+			]
+		};
+		// Down interfaces are only candidates with --include-virtual
+		assert_eq!(
+			pick(links(), Prefer::Wireless, false).as_deref(),
+			Some("wired0")
+		);
+		assert_eq!(
+			pick(links(), Prefer::Wireless, true).as_deref(),
+			Some("wired0")
+		);
+		assert_eq!(
+			pick(links()[..2].to_vec(), Prefer::Wireless, true).as_deref(),
+			Some("virt0")
+		);
+	}
+
+	#[test]
+	fn pick_skips_interfaces_without_an_address() {
+		let mut links = vec![
+			link(1, "wifi0", true, [0; 6]),
+			link(2, "wifi1", true, [2, 0, 0, 0, 0, 2]),
+		];
+		assert_eq!(
+			pick(links.clone(), Prefer::Wireless, false).as_deref(),
+			Some("wifi1")
+		);
+		links[1].address = None;
+		assert_eq!(pick(links, Prefer::Wireless, false), None);
+	}
+
+	#[test]
+	fn pick_takes_the_first_of_equals() {
+		let links = vec![
+			link(1, "wired1", true, [2, 0, 0, 0, 0, 1]),
+			link(2, "wired0", true, [2, 0, 0, 0, 0, 2]),
+		];
+		assert_eq!(pick(links, Prefer::Wired, false).as_deref(), Some("wired1"));
+	}
+
+	#[test]
+	fn lookup_by_mac() {
+		let interfaces = interfaces();
+		assert_eq!(
+			interfaces
+				.by_mac(MacAddr::new([2, 0, 0, 0, 0, 1]))
+				.ok()
+				.as_deref(),
+			Some("wired0")
+		);
+		assert!(matches!(
+			interfaces.by_mac(MacAddr::new([2, 0, 0, 0, 0, 2])),
+			Err(RacError::NoInterface(_))
+		));
+		// Neither is physical, so there's no telling which one is meant
+		assert!(matches!(
+			interfaces.by_mac(MacAddr::new(SHARED)),
+			Err(RacError::Parse(_))
+		));
+	}
+
+	#[test]
+	fn lookup_by_index() {
+		let interfaces = interfaces();
+		assert_eq!(
+			interfaces.by_index(7).map(|link| link.name.as_str()),
+			Some("br0")
+		);
+		assert!(interfaces.by_index(2).is_none());
+	}
+
+	#[test]
+	fn lookup_by_permanent() {
+		// Made-up interfaces have no permanent address to find
+		assert_eq!(
+			interfaces().by_permanent(MacAddr::new([2, 0, 0, 0, 0, 1])),
+			None
+		);
+	}
+}
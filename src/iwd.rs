@@ -0,0 +1,108 @@
+//! iwd integration
+//!
+//! iwd keeps per-network settings in `/var/lib/iwd/<network>.<type>` and
+//! would otherwise pick its own address on the next (re)connect, undoing the
+//! change. Pinning the address there with `AddressOverride` makes it stick.
+
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+use crate::MacAddr;
+
+/// Where iwd stores known networks
+const STORAGE_DIR: &str = "/var/lib/iwd";
+
+/// Security types iwd uses as file extensions
+const EXTENSIONS: [&str; 3] = ["psk", "open", "8021x"];
+
+/// Check whether iwd is running
+pub fn is_running() -> bool {
+	fs::read_dir("/proc")
+		.map(|entries| {
+			entries.flatten().any(|entry| {
+				fs::read_to_string(entry.path().join("comm"))
+					.map(|comm| comm.trim() == "iwd")
+					.unwrap_or(false)
+			})
+		})
+		.unwrap_or(false)
+}
+
+/// Encode an SSID the way iwd names its settings files: as-is if it only
+/// contains alphanumerics, ' ', '-' and '_', otherwise '=' followed by hex
+fn encode_ssid(ssid: &str) -> String {
+	if ssid
+		.chars()
+		.all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_')
+	{
+		ssid.to_string()
+	} else {
+		let hex: String = ssid.bytes().map(|b| format!("{:02x}", b)).collect();
+		format!("={}", hex)
+	}
+}
+
+/// Find the settings file of a known network
+pub fn network_file(ssid: &str) -> io::Result<PathBuf> {
+	let name = encode_ssid(ssid);
+	EXTENSIONS
+		.iter()
+		.map(|ext| PathBuf::from(STORAGE_DIR).join(format!("{}.{}", name, ext)))
+		.find(|path| path.exists())
+		.ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				format!("iwd doesn't know a network called '{}'", ssid),
+			)
+		})
+}
+
+/// Set `key=value` in `[section]` of an INI-style file, adding either if
+/// missing
+fn set_key(text: &str, section: &str, key: &str, value: &str) -> String {
+	let header = format!("[{}]", section);
+	let entry = format!("{}={}", key, value);
+	let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+
+	match lines.iter().position(|line| line.trim() == header) {
+		Some(start) => {
+			let end = lines[start + 1..]
+				.iter()
+				.position(|line| line.trim_start().starts_with('['))
+				.map_or(lines.len(), |i| start + 1 + i);
+			let existing = lines[start + 1..end]
+				.iter()
+				.position(|line| line.split_once('=').is_some_and(|(k, _)| k.trim() == key));
+			match existing {
+				Some(i) => lines[start + 1 + i] = entry,
+				None => {
+					// Keep the entry next to the rest of the section, not after blank lines
+					let mut at = end;
+					while at > start + 1 && lines[at - 1].trim().is_empty() {
+						at -= 1;
+					}
+					lines.insert(at, entry);
+				}
+			}
+		}
+		None => {
+			if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+				lines.push(String::new());
+			}
+			lines.push(header);
+			lines.push(entry);
+		}
+	}
+	lines.join("\n") + "\n"
+}
+
+/// Pin an address in a network's settings file (see [`network_file`]), so iwd
+/// uses it on every connection
+pub fn pin_address(path: &Path, addr: MacAddr) -> io::Result<()> {
+	let text = fs::read_to_string(path)?;
+	let text = set_key(&text, "Settings", "AlwaysRandomizeAddress", "false");
+	let text = set_key(&text, "Settings", "AddressOverride", &addr.to_string());
+	fs::write(path, text)
+}
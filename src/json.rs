@@ -0,0 +1,150 @@
+//! Tiny JSON writer for machine-readable output, and a reader for the flat
+//! objects of the daemon's control protocol
+
+use std::fmt::Write;
+
+/// Escape a string and wrap it in quotes
+pub fn string(value: &str) -> String {
+	let mut out = String::with_capacity(value.len() + 2);
+	out.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if (c as u32) < 0x20 => {
+				let _ = write!(out, "\\u{:04x}", c as u32);
+			}
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+	out
+}
+
+/// Builder for a single (flat) JSON object
+#[derive(Default)]
+pub struct Object {
+	buf: String,
+}
+
+impl Object {
+	pub fn new() -> Object { Object::default() }
+
+	/// Add a field whose value is already valid JSON
+	pub fn raw(mut self, key: &str, value: &str) -> Object {
+		self.buf.push(if self.buf.is_empty() { '{' } else { ',' });
+		self.buf.push_str(&string(key));
+		self.buf.push(':');
+		self.buf.push_str(value);
+		self
+	}
+
+	pub fn str(self, key: &str, value: &str) -> Object { self.raw(key, &string(value)) }
+
+	/// Add a string field, or `null` if there is no value
+	pub fn opt_str(self, key: &str, value: Option<&str>) -> Object {
+		match value {
+			Some(value) => self.str(key, value),
+			None => self.raw(key, "null"),
+		}
+	}
+
+	pub fn num<N: std::fmt::Display>(self, key: &str, value: N) -> Object {
+		self.raw(key, &value.to_string())
+	}
+
+	pub fn finish(mut self) -> String {
+		if self.buf.is_empty() {
+			self.buf.push('{');
+		}
+		self.buf.push('}');
+		self.buf
+	}
+}
+
+/// Join already-serialized values into a JSON array
+pub fn array<I: IntoIterator<Item = String>>(items: I) -> String {
+	format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// Parse a flat JSON object whose values are strings, numbers or booleans,
+/// into its fields (values of other types aren't supported)
+pub fn parse_object(text: &str) -> Option<Vec<(String, String)>> {
+	let mut chars = text.trim().chars().peekable();
+	let mut fields = Vec::new();
+	let skip_ws = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+		while chars.peek().is_some_and(|c| c.is_whitespace()) {
+			chars.next();
+		}
+	};
+
+	if chars.next()? != '{' {
+		return None;
+	}
+	skip_ws(&mut chars);
+	if chars.peek() == Some(&'}') {
+		chars.next();
+		return chars.next().is_none().then_some(fields);
+	}
+	loop {
+		skip_ws(&mut chars);
+		let key = parse_string(&mut chars)?;
+		skip_ws(&mut chars);
+		if chars.next()? != ':' {
+			return None;
+		}
+		skip_ws(&mut chars);
+		let value = if chars.peek() == Some(&'"') {
+			parse_string(&mut chars)?
+		} else {
+			let mut raw = String::new();
+			while chars
+				.peek()
+				.is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'))
+			{
+				raw.push(chars.next()?);
+			}
+			if raw != "true" && raw != "false" && raw.parse::<f64>().is_err() {
+				return None;
+			}
+			raw
+		};
+		fields.push((key, value));
+		skip_ws(&mut chars);
+		match chars.next()? {
+			',' => continue,
+			'}' => break,
+			_ => return None,
+		}
+	}
+	chars.next().is_none().then_some(fields)
+}
+
+/// Parse a quoted string, undoing escapes
+fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+	if chars.next()? != '"' {
+		return None;
+	}
+	let mut out = String::new();
+	loop {
+		match chars.next()? {
+			'"' => return Some(out),
+			'\\' => match chars.next()? {
+				'n' => out.push('\n'),
+				'r' => out.push('\r'),
+				't' => out.push('\t'),
+				'b' => out.push('\u{8}'),
+				'f' => out.push('\u{c}'),
+				'u' => {
+					let hex: String = (0..4).map(|_| chars.next()).collect::<Option<_>>()?;
+					out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+				}
+				c => out.push(c),
+			},
+			c => out.push(c),
+		}
+	}
+}
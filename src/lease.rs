@@ -0,0 +1,114 @@
+//! DHCP leases held under the current address (`rac set --check-lease`)
+//!
+//! DHCP servers hand out addresses by MAC, and reservations (static leases)
+//! are tied to one, so a new MAC address means a new IP address. The leases
+//! are read from dhclient's files and NetworkManager's (its internal client
+//! and dhclient), taking the most recent one for the interface.
+
+use std::{
+	fs,
+	net::Ipv4Addr,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+/// Where dhclient keeps its leases, depending on the distribution
+const DHCLIENT_DIRS: &[&str] = &["/var/lib/dhcp", "/var/lib/dhclient"];
+const NETWORK_MANAGER_DIR: &str = "/var/lib/NetworkManager";
+
+/// The lease an interface holds
+pub struct Lease {
+	pub address: Ipv4Addr,
+	/// The file it came from
+	pub source:  PathBuf,
+}
+
+/// The address of the last lease in a dhclient lease file, for `inter` (or any
+/// interface, for files that are per-interface anyway)
+fn parse_dhclient(text: &str, inter: Option<&str>) -> Option<Ipv4Addr> {
+	let mut found = None;
+	for block in text.split("lease {").skip(1) {
+		let block = block.split('}').next().unwrap_or_default();
+		let value = |key: &str| {
+			block.lines().find_map(|line| {
+				line.trim()
+					.strip_prefix(key)
+					.map(|value| value.trim().trim_end_matches(';').trim_matches('"'))
+			})
+		};
+		if inter.is_some_and(|inter| value("interface ") != Some(inter)) {
+			continue;
+		}
+		if let Some(address) = value("fixed-address ").and_then(|value| value.parse().ok()) {
+			found = Some(address);
+		}
+	}
+	found
+}
+
+/// `ADDRESS=` in a lease file of NetworkManager's internal client
+fn parse_internal(text: &str) -> Option<Ipv4Addr> {
+	text.lines()
+		.find_map(|line| line.strip_prefix("ADDRESS="))
+		.and_then(|address| address.trim().parse().ok())
+}
+
+fn files(dir: &str) -> Vec<PathBuf> {
+	fs::read_dir(dir)
+		.map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+		.unwrap_or_default()
+}
+
+fn modified(path: &Path) -> SystemTime {
+	fs::metadata(path)
+		.and_then(|metadata| metadata.modified())
+		.unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// The most recent lease an interface holds, if any lease file mentions it
+pub fn current(inter: &str) -> Option<Lease> {
+	let mut leases = Vec::new();
+	for path in DHCLIENT_DIRS.iter().flat_map(|dir| files(dir)) {
+		if path.extension().is_some_and(|ext| ext == "leases") {
+			if let Some(address) = fs::read_to_string(&path)
+				.ok()
+				.and_then(|text| parse_dhclient(&text, Some(inter)))
+			{
+				leases.push(Lease {
+					address,
+					source: path,
+				});
+			}
+		}
+	}
+	// internal-<connection>-<interface>.lease or
+	// dhclient-<connection>-<interface>.lease
+	let suffix = format!("-{}.lease", inter);
+	for path in files(NETWORK_MANAGER_DIR) {
+		let name = path
+			.file_name()
+			.map_or(String::new(), |name| name.to_string_lossy().into_owned());
+		if !name.ends_with(&suffix) {
+			continue;
+		}
+		let Ok(text) = fs::read_to_string(&path) else {
+			continue;
+		};
+		let address = if name.starts_with("internal-") {
+			parse_internal(&text)
+		} else if name.starts_with("dhclient-") {
+			parse_dhclient(&text, None)
+		} else {
+			None
+		};
+		if let Some(address) = address {
+			leases.push(Lease {
+				address,
+				source: path,
+			});
+		}
+	}
+	leases
+		.into_iter()
+		.max_by_key(|lease| modified(&lease.source))
+}
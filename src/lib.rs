@@ -0,0 +1,40 @@
+//! The parts of rac other programs can use: the MAC address type, changing
+//! the address of an interface the way rac does ([`change`]) and, with the
+//! `ffi` feature, a C interface to them (`librac.so`, see `include/rac.h`).
+//! Without the default `std` feature this is a no_std library with only the
+//! MAC address type, e.g. for firmware that handles MAC addresses the same
+//! way.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod change;
+#[cfg(feature = "std")]
+pub mod cooldown;
+pub mod core;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod escalate;
+#[cfg(feature = "std")]
+pub mod ethtool;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod lock;
+#[cfg(feature = "std")]
+pub mod netlink;
+#[cfg(feature = "std")]
+pub mod restrictions;
+#[cfg(feature = "std")]
+pub mod schedule;
+#[cfg(feature = "std")]
+pub mod timeout;
+#[cfg(feature = "std")]
+pub mod value;
+
+pub use crate::core::{MacAddr, MacGenerator, MacParseError};
+#[cfg(feature = "std")]
+pub use crate::netlink::HwAddr;
@@ -0,0 +1,120 @@
+//! Addresses for libvirt and QEMU virtual machines (`rac vm`)
+//!
+//! A domain's definition has a `<mac>` in each of its `<interface>` elements,
+//! in the order of its NICs:
+//!
+//! ```text
+//! <interface type='network'>
+//!   <mac address='52:54:00:12:34:56'/>
+//!   <source network='default'/>
+//! </interface>
+//! ```
+//!
+//! The address is replaced in the persistent definition (`virsh dumpxml
+//! --inactive`, then `virsh define`), so a running domain keeps its address
+//! until it's started again. `virsh` connects to its default URI, like it
+//! does from a shell (`LIBVIRT_DEFAULT_URI` picks another).
+
+use std::{env, fs, io, process::Command};
+
+use crate::{error::RacError, timeout, MacAddr};
+
+/// Run `virsh` and return its output
+fn virsh(args: &[&str]) -> Result<String, RacError> {
+	let output = timeout::output(Command::new("virsh").args(args)).map_err(|e| match e.kind() {
+		io::ErrorKind::NotFound => RacError::Backend("virsh isn't installed".to_string()),
+		_ => e.into(),
+	})?;
+	if !output.status.success() {
+		return Err(RacError::Backend(
+			String::from_utf8_lossy(&output.stderr).trim().to_string(),
+		));
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The persistent definition of a domain
+pub fn definition(domain: &str) -> Result<String, RacError> {
+	virsh(&["dumpxml", "--inactive", domain])
+}
+
+/// Whether a domain is running now
+pub fn is_running(domain: &str) -> bool {
+	virsh(&["domstate", domain]).is_ok_and(|state| state.trim() == "running")
+}
+
+/// Define a domain anew from its edited definition
+pub fn define(xml: &str) -> Result<(), RacError> {
+	let path = env::temp_dir().join(format!("rac-vm-{}.xml", std::process::id()));
+	fs::write(&path, xml)?;
+	let result = virsh(&["define", &path.to_string_lossy()]).map(|_| ());
+	fs::remove_file(&path).ok();
+	result
+}
+
+/// Give the `nic`-th interface of a definition an address, returning the
+/// edited definition and the address it had
+pub fn set_mac(xml: &str, nic: usize, addr: MacAddr) -> Result<(String, Option<MacAddr>), String> {
+	// `<interface>` elements only appear under `<devices>`
+	let start = xml
+		.match_indices("<interface")
+		.map(|(at, _)| at)
+		.filter(|at| {
+			matches!(
+				xml.as_bytes().get(at + "<interface".len()),
+				Some(b' ' | b'>')
+			)
+		})
+		.nth(nic)
+		.ok_or_else(|| format!("the domain has no NIC {}", nic))?;
+	let end = xml[start..]
+		.find("</interface>")
+		.map(|end| start + end)
+		.ok_or("unterminated <interface> element")?;
+	let element = &xml[start..end];
+	let new = format!("address='{}'", addr.to_string().to_lowercase());
+
+	let Some(mac) = element.find("<mac ") else {
+		// None yet, libvirt would have made one up: add it after the tag
+		let tag_end = element.find('>').ok_or("invalid <interface> element")? + 1;
+		let at = start + tag_end;
+		return Ok((
+			format!("{}\n      <mac {}/>{}", &xml[..at], new, &xml[at..]),
+			None,
+		));
+	};
+	let attr = element[mac..]
+		.find("address=")
+		.map(|attr| mac + attr)
+		.ok_or("a <mac> element without an address")?;
+	let quote = element[attr + "address=".len()..]
+		.chars()
+		.next()
+		.filter(|quote| *quote == '\'' || *quote == '"')
+		.ok_or("a <mac> element without an address")?;
+	let value_start = attr + "address=".len() + 1;
+	let value_end = element[value_start..]
+		.find(quote)
+		.map(|end| value_start + end)
+		.ok_or("unterminated address attribute")?;
+	let old = element[value_start..value_end].parse().ok();
+	Ok((
+		format!(
+			"{}{}{}",
+			&xml[..start + attr],
+			new,
+			&xml[start + value_end + 1..]
+		),
+		old,
+	))
+}
+
+/// The QEMU argument for a NIC with an address, on the netdev `net<nic>`
+pub fn qemu_arg(model: &str, nic: usize, addr: MacAddr) -> String {
+	format!(
+		"-device {},netdev=net{},mac={}",
+		model,
+		nic,
+		addr.to_string().to_lowercase()
+	)
+}
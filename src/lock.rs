@@ -0,0 +1,69 @@
+//! Per-interface locks, so two rac processes don't interleave the steps of
+//! changing the same interface
+//!
+//! These are advisory `flock`s on a file per interface in `/run/lock` (or the
+//! temporary directory where that's missing), released when the [`Lock`] is
+//! dropped or the process exits.
+
+use std::{
+	fs::{File, OpenOptions},
+	io,
+	os::unix::io::AsRawFd,
+	path::PathBuf,
+	sync::OnceLock,
+};
+
+use nix::{
+	errno::Errno,
+	fcntl::{flock, FlockArg},
+};
+
+use crate::error::{self, RacError};
+
+const LOCK_DIR: &str = "/run/lock";
+
+/// Whether to wait for another process's lock instead of failing (`--wait`)
+static WAIT: OnceLock<bool> = OnceLock::new();
+
+pub fn set_wait(wait: bool) { WAIT.set(wait).ok(); }
+
+/// A held lock on an interface
+pub struct Lock {
+	_file: File,
+}
+
+fn path(inter: &str) -> PathBuf {
+	let dir = PathBuf::from(LOCK_DIR);
+	let dir = if dir.is_dir() {
+		dir
+	} else {
+		std::env::temp_dir()
+	};
+	dir.join(format!("rac-{}.lock", inter))
+}
+
+/// Lock an interface, failing right away if another process holds the lock
+/// (or waiting for it, with `--wait`)
+pub fn interface(inter: &str) -> error::Result<Lock> {
+	let path = path(inter);
+	// Another user's lock file can still be locked through a read-only handle
+	let file = match OpenOptions::new().append(true).create(true).open(&path) {
+		Err(e) if e.kind() == io::ErrorKind::PermissionDenied => File::open(&path),
+		result => result,
+	}
+	.map_err(|e| RacError::Backend(format!("can't open {}: {}", path.display(), e)))?;
+
+	let arg = match WAIT.get() {
+		Some(true) => FlockArg::LockExclusive,
+		_ => FlockArg::LockExclusiveNonblock,
+	};
+	match flock(file.as_raw_fd(), arg) {
+		Ok(()) => Ok(Lock { _file: file }),
+		Err(Errno::EWOULDBLOCK) => Err(RacError::Busy(inter.to_string())),
+		Err(e) => Err(RacError::Backend(format!(
+			"can't lock {}: {}",
+			path.display(),
+			e
+		))),
+	}
+}
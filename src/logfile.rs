@@ -0,0 +1,188 @@
+//! The daemon's log file (`log_file` in the configuration)
+//!
+//! Every event the daemon reports is also appended to the file, as a line of
+//! text after the local time or as the event's JSON object (`log_format`),
+//! whatever stdout is connected to. Once the file grows past `log_max_size`
+//! or gets older than `log_rotate`, it's renamed to `<file>.1` (the one
+//! before to `<file>.2`, ...) and a new one started; `log_keep` old files are
+//! kept.
+
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{self, Write},
+	path::PathBuf,
+	sync::Mutex,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::ArgEnum;
+use nix::libc;
+
+use crate::config;
+
+/// Default number of rotated files kept
+pub const DEFAULT_KEEP: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Format {
+	/// The text the daemon prints, after the local time
+	Text,
+	/// The events of `--output ndjson`
+	Json,
+}
+
+/// The open log file, if the daemon has one
+struct Log {
+	path:     PathBuf,
+	format:   Format,
+	max_size: Option<u64>,
+	rotate:   Option<Duration>,
+	keep:     usize,
+	file:     File,
+	size:     u64,
+	/// When the current file was started
+	started:  SystemTime,
+}
+
+static LOG: Mutex<Option<Log>> = Mutex::new(None);
+
+/// Parse a size like `10M` (K, M and G are powers of 1024)
+pub fn parse_size(size: &str) -> Option<u64> {
+	let unit = size
+		.find(|c: char| !c.is_ascii_digit())
+		.unwrap_or(size.len());
+	let count: u64 = size[..unit].parse().ok().filter(|count| *count > 0)?;
+	let scale = match &size[unit..] {
+		"" => 1,
+		"K" => 1 << 10,
+		"M" => 1 << 20,
+		"G" => 1 << 30,
+		_ => return None,
+	};
+	count.checked_mul(scale)
+}
+
+fn open(path: &PathBuf) -> io::Result<(File, u64, SystemTime)> {
+	let file = OpenOptions::new().append(true).create(true).open(path)?;
+	let metadata = file.metadata()?;
+	// An existing file counts from when it was created, where the filesystem
+	// knows
+	let started = metadata.created().unwrap_or_else(|_| SystemTime::now());
+	Ok((file, metadata.len(), started))
+}
+
+/// Open the log file the daemon section asks for, or close it if none. Called
+/// again when the configuration is reloaded.
+pub fn configure(daemon: &config::Daemon) -> io::Result<()> {
+	let log = match &daemon.log_file {
+		Some(path) => {
+			let (file, size, started) = open(path)?;
+			Some(Log {
+				path: path.clone(),
+				format: daemon.log_format,
+				max_size: daemon.log_max_size,
+				rotate: daemon.log_rotate,
+				keep: daemon.log_keep,
+				file,
+				size,
+				started,
+			})
+		}
+		None => None,
+	};
+	*LOG.lock().unwrap_or_else(|e| e.into_inner()) = log;
+	Ok(())
+}
+
+/// The local time, `YYYY-MM-DD HH:MM:SS`
+fn timestamp() -> String {
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |since| since.as_secs()) as libc::time_t;
+	// SAFETY: both pointers are valid for the duration of the call, and an
+	// all-zero `tm` is a valid value
+	let tm = unsafe {
+		let mut tm = std::mem::zeroed::<libc::tm>();
+		libc::localtime_r(&now, &mut tm);
+		tm
+	};
+	format!(
+		"{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+		tm.tm_year + 1900,
+		tm.tm_mon + 1,
+		tm.tm_mday,
+		tm.tm_hour,
+		tm.tm_min,
+		tm.tm_sec
+	)
+}
+
+/// Text without its colors
+fn strip_colors(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	let mut chars = text.chars();
+	while let Some(c) = chars.next() {
+		if c == '\x1b' {
+			// Everything up to the final letter of the escape sequence
+			chars.by_ref().find(|c| c.is_ascii_alphabetic());
+		} else {
+			out.push(c);
+		}
+	}
+	out
+}
+
+impl Log {
+	fn due(&self, len: u64) -> bool {
+		self.max_size
+			.is_some_and(|max| self.size > 0 && self.size + len > max)
+			|| self.rotate.is_some_and(|rotate| {
+				self.started
+					.elapsed()
+					.is_ok_and(|elapsed| elapsed >= rotate)
+			})
+	}
+
+	/// Shift the old files up by one, dropping the oldest, and start a new one
+	fn rotate(&mut self) -> io::Result<()> {
+		let numbered = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+		if self.keep == 0 {
+			fs::remove_file(&self.path)?;
+		} else {
+			for n in (1..self.keep).rev() {
+				match fs::rename(numbered(n), numbered(n + 1)) {
+					Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+					_ => {}
+				}
+			}
+			fs::rename(&self.path, numbered(1))?;
+		}
+		(self.file, self.size, _) = open(&self.path)?;
+		self.started = SystemTime::now();
+		Ok(())
+	}
+
+	fn write(&mut self, event: &str, text: &str) -> io::Result<()> {
+		let line = match self.format {
+			Format::Text => format!("{} {}\n", timestamp(), strip_colors(text)),
+			Format::Json => format!("{}\n", event),
+		};
+		if self.due(line.len() as u64) {
+			self.rotate()?;
+		}
+		self.file.write_all(line.as_bytes())?;
+		self.size += line.len() as u64;
+		Ok(())
+	}
+}
+
+/// Append an event to the log file, if there is one
+pub fn record(event: &str, text: &str) {
+	if let Some(log) = LOG.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+		// A full disk shouldn't take the daemon down, the event is still
+		// printed
+		if let Err(e) = log.write(event, text) {
+			eprintln!("Failed to write to {}: {}", log.path.display(), e);
+		}
+	}
+}
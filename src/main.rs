@@ -3,194 +3,4361 @@ extern crate colored;
 extern crate nix;
 extern crate rand;
 
-use std::{io, process::Command, str::FromStr};
+mod backup;
+mod clipboard;
+mod config;
+mod daemon;
+mod dbus;
+mod dhcp;
+mod dispatcher;
+mod doctor;
+mod early;
+mod hooks;
+mod hostname;
+mod idle;
+mod interfaces;
+mod iwd;
+mod json;
+mod lease;
+mod libvirt;
+mod logfile;
+mod metrics;
+mod neigh;
+mod networks;
+mod nm;
+mod oui;
+mod pool;
+mod probe;
+mod quirks;
+mod selftest;
+mod service;
+mod show;
+mod ssh;
+mod stable;
+#[cfg(feature = "self-update")]
+mod update;
+mod wpa;
 
-use clap::{Parser, Subcommand};
+use std::{
+	collections::HashMap,
+	io::{self, IsTerminal, Read, Write},
+	os::unix::fs::{OpenOptionsExt, PermissionsExt},
+	path::PathBuf,
+	process::{self, Command, Stdio},
+	str::FromStr,
+	sync::OnceLock,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::{ArgEnum, Args as ClapArgs, Parser, Subcommand};
 use colored::Colorize;
-use nix::{ifaddrs::getifaddrs, sys::socket::SockAddr};
-use rand::random;
+use rac::{
+	change::{self, ip, ip_link, Change, Changed},
+	cooldown, error, escalate, ethtool, history, lock, netlink, restrictions, schedule, timeout,
+	value,
+};
+pub use rac::{HwAddr, MacAddr};
+
+use crate::{error::RacError, interfaces::Interfaces};
 
 /// A simple  MAC address utility
 #[derive(Parser, Debug)]
 #[clap(author, version, about, arg_required_else_help(true), long_about = None)]
 struct Args {
-	/// Set MAC address
 	#[clap(subcommand)]
-	set: Option<SubCmds>,
+	command: Option<SubCmds>,
 
 	/// Generate a random MAC address
 	#[clap(short, long)]
 	random: bool,
 
-	/// Print current MAC address
-	#[clap(short, long)]
-	current: bool,
+	/// Print current MAC address
+	#[clap(short, long)]
+	current: bool,
+
+	/// Vendor prefix for the random address (e.g. 00:1B:21)
+	#[clap(long, value_name = "PREFIX", requires = "random")]
+	oui: Option<String>,
+
+	/// Use the prefix of a popular vendor, so the address blends in
+	#[clap(
+		long,
+		arg_enum,
+		value_name = "CATEGORY",
+		requires = "random",
+		conflicts_with = "oui"
+	)]
+	mimic: Option<oui::Category>,
+
+	/// Use a prefix of this vendor, by name (e.g. "Samsung Electronics")
+	#[clap(
+		long,
+		value_name = "NAME",
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic"]
+	)]
+	vendor: Option<String>,
+
+	/// Template for the random address, with `x` for random nibbles (e.g.
+	/// 02:DE:AD:xx:xx:xx)
+	#[clap(
+		long,
+		value_name = "TEMPLATE",
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor"],
+		parse(try_from_str = parse_template)
+	)]
+	template: Option<config::Policy>,
+
+	/// Make the random address look universally administered (U/L bit clear)
+	/// instead of locally administered, e.g. for lab test addresses
+	#[clap(
+		long,
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor", "template"]
+	)]
+	unicast_global: bool,
+
+	/// Make the random address a locally administered multicast one (I/G bit
+	/// set)
+	#[clap(
+		long,
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor", "template", "unicast-global"]
+	)]
+	multicast_local: bool,
+
+	/// Make the random address a universally administered multicast one
+	#[clap(
+		long,
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor", "template", "unicast-global", "multicast-local"]
+	)]
+	multicast_global: bool,
+
+	/// Generate the random address in a SLAP quadrant: aai, eli (under the
+	/// Company ID given with --oui) or sai
+	#[clap(
+		long,
+		arg_enum,
+		value_name = "QUADRANT",
+		requires = "random",
+		conflicts_with_all = &["mimic", "vendor", "template", "unicast-global", "multicast-local", "multicast-global"]
+	)]
+	slap: Option<Slap>,
+
+	/// Also copy the random address to the clipboard
+	#[clap(long, requires = "random")]
+	copy: bool,
+
+	/// Print only the resulting values, one per line, without colors or prose
+	#[clap(long, global = true)]
+	plain: bool,
+
+	/// Print the resulting values as shell variables (RAC_MAC=...), for eval
+	#[clap(long, global = true, conflicts_with = "plain")]
+	export: bool,
+
+	/// Print events and results in this format (ndjson for monitor, daemon and
+	/// batch operations, the others for list)
+	#[clap(long, global = true, arg_enum, conflicts_with_all = &["plain", "export"])]
+	output: Option<Format>,
+
+	/// Configuration file to use [default: /etc/rac/rac.conf]
+	#[clap(long, global = true)]
+	config: Option<PathBuf>,
+
+	#[clap(flatten)]
+	select: SelectArgs,
+
+	/// Wait for other rac processes changing the same interface, instead of
+	/// failing
+	#[clap(long, global = true)]
+	wait: bool,
+
+	/// How long an external command (ip, sudo, nmcli, ...) may take before
+	/// it's killed, e.g. 10s [default: 30s, or `timeout` in the configuration]
+	#[clap(long, global = true, value_name = "DURATION", parse(try_from_str = parse_duration))]
+	timeout: Option<Duration>,
+
+	/// How to run the commands that need root: sudo, pkexec (polkit's
+	/// authentication dialog) or none, when rac has the rights itself
+	/// [default: sudo, or `escalate` in the configuration]
+	#[clap(long, global = true, arg_enum, value_name = "METHOD")]
+	escalate: Option<escalate::Method>,
+
+	/// Refuse to change an interface again sooner than this after the last
+	/// change, e.g. 5m, or 0 for no cooldown [default: `cooldown` in the
+	/// configuration, none without it]
+	#[clap(long, global = true, value_name = "DURATION", parse(try_from_str = parse_cooldown))]
+	cooldown: Option<Duration>,
+
+	/// Command run with `sh -c` after every change, after the hooks directory
+	/// (RAC_INTERFACE, RAC_OLD_MAC, RAC_MAC and RAC_KIND describe the change)
+	#[clap(long, global = true, value_name = "COMMAND")]
+	post_hook: Option<String>,
+
+	/// Run on another host over SSH (list, status, diff, set, undo and reset),
+	/// with the rac installed there
+	#[clap(long, global = true, value_name = "[USER@]HOST")]
+	host: Option<String>,
+}
+
+/// How to pick an interface when none is given
+#[derive(Debug, Clone, Copy, ClapArgs)]
+struct SelectArgs {
+	/// Kind of interface to pick when none is given
+	#[clap(long, global = true, arg_enum, default_value = "wireless")]
+	prefer: Prefer,
+
+	/// Also consider bridges, veth, tun/tap and interfaces that are down
+	#[clap(long, global = true)]
+	include_virtual: bool,
+}
+
+/// Which interfaces automatic selection prefers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Prefer {
+	/// Wi-Fi interfaces that are up, then wired ones
+	Wireless,
+	/// Wired interfaces that are up, then Wi-Fi ones
+	Wired,
+	/// The first interface with an address
+	Any,
+}
+
+/// IPv6 privacy extensions (RFC 4941), `use_tempaddr` of an interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Privacy {
+	/// Use temporary addresses, and prefer them for outgoing connections
+	On,
+	/// Only use the address derived from the MAC address
+	Off,
+}
+
+/// The quadrants of the locally administered unicast space in IEEE 802c's
+/// Structured Local Address Plan (SLAP), told apart by the second hex digit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Slap {
+	/// Administratively Assigned Identifier (x2:...), the one for addresses
+	/// assigned locally
+	Aai,
+	/// Extended Local Identifier (xA:...), whose first 3 bytes are a Company
+	/// ID, given with --oui
+	Eli,
+	/// Standard Assigned Identifier (x6:...), for addresses assigned by a
+	/// protocol of an IEEE 802 standard
+	Sai,
+}
+
+/// A setting that's either on or off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Toggle {
+	On,
+	Off,
+}
+
+/// Formats for streams of events and results, and for `rac list` (`--output`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Format {
+	/// One JSON object per line
+	Ndjson,
+	/// Aligned columns, without colors (list)
+	Table,
+	/// Comma-separated values under a header line (list)
+	Csv,
+	/// A sequence of mappings (list)
+	Yaml,
+}
+
+#[derive(Debug, Subcommand)]
+enum SubCmds {
+	/// Set MAC address
+	Set(SetArgs),
+	/// Put back the address an interface had before rac last changed it
+	Undo {
+		/// Interface to undo the change of (default: the last one changed)
+		#[clap(short, long)]
+		interface: Option<String>,
+	},
+	/// Put back the permanent (hardware) address of interfaces that were
+	/// spoofed
+	Reset {
+		/// Interface to reset
+		#[clap(short, long, required_unless_present_any = &["all", "hostname"])]
+		interface: Option<String>,
+
+		/// Reset every interface whose address differs from its permanent one,
+		/// and the hostname
+		#[clap(short, long, conflicts_with = "interface")]
+		all: bool,
+
+		/// Put back the hostname from before `--randomize-hostname`
+		#[clap(long)]
+		hostname: bool,
+	},
+	/// Create a macvlan (or macvtap) interface with its own address, leaving
+	/// the parent untouched
+	Clone(CloneArgs),
+	/// Run in the background, randomizing interfaces as they appear
+	Daemon,
+	/// Randomize every interface once (e.g. at boot) and exit
+	RandomizeAll {
+		/// Only change physical interfaces (skip virtual ones)
+		#[clap(short, long)]
+		physical: bool,
+
+		/// Change the other interfaces when one fails, instead of undoing
+		/// everything
+		#[clap(long)]
+		keep_going: bool,
+
+		/// Use this policy for every interface instead of the configured ones
+		#[clap(long, value_name = "POLICY")]
+		policy: Option<String>,
+	},
+	/// List interfaces and their MAC addresses
+	List {
+		/// Print JSON instead of a table
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// Show the EUI-64 and IPv6 link-local address a MAC address maps to
+	Derive {
+		/// MAC address to derive from ('-' to read it from stdin)
+		#[clap(parse(try_from_str = parse_addr_arg))]
+		address: MacAddr,
+	},
+	/// Compute the address following a given one
+	Next {
+		/// MAC address to start from ('-' to read it from stdin)
+		#[clap(parse(try_from_str = parse_addr_arg))]
+		address: MacAddr,
+
+		/// How far to move (may be negative)
+		#[clap(short, long, default_value_t = 1, allow_hyphen_values = true)]
+		step: i64,
+	},
+	/// Generate a contiguous block of addresses
+	Range {
+		/// First address of the block ('-' to read it from stdin)
+		#[clap(short, long, parse(try_from_str = parse_addr_arg))]
+		start: MacAddr,
+
+		/// Number of addresses [default: number of interfaces given to --apply]
+		#[clap(short, long, required_unless_present = "apply")]
+		count: Option<u64>,
+
+		/// Assign the addresses, in order, to these interfaces ('-' to read
+		/// them from stdin)
+		#[clap(short, long, use_value_delimiter = true, value_name = "INTERFACES")]
+		apply: Vec<String>,
+
+		/// Change the other interfaces when one fails, instead of undoing
+		/// everything
+		#[clap(long, requires = "apply")]
+		keep_going: bool,
+	},
+	/// Keep an address per network, so each one sees the same address on
+	/// every visit and none of the others
+	Network {
+		#[clap(subcommand)]
+		command: NetworkCmds,
+	},
+	/// Allocate addresses from managed pools
+	Pool {
+		#[clap(subcommand)]
+		command: PoolCmds,
+	},
+	/// Give a NIC of a libvirt domain a new address, or print the QEMU
+	/// argument for one
+	Vm {
+		/// libvirt domain to change, from its next start
+		#[clap(long, value_name = "DOMAIN", required_unless_present = "qemu")]
+		domain: Option<String>,
+
+		/// Which of the domain's NICs, counting from 0 (with --qemu, the
+		/// netdev is net<NIC>)
+		#[clap(long, default_value = "0")]
+		nic: usize,
+
+		/// Generate a random address
+		#[clap(short, long, required_unless_present_any = &["address", "pool"])]
+		random: bool,
+
+		/// Use a random address with this prefix (e.g. 52:54:00, the one
+		/// QEMU and libvirt use)
+		#[clap(long, value_name = "PREFIX", requires = "random")]
+		oui: Option<String>,
+
+		/// Use this address ('-' to read it from stdin)
+		#[clap(short, long, conflicts_with_all = &["random", "pool"], parse(try_from_str = parse_addr_arg))]
+		address: Option<MacAddr>,
+
+		/// Allocate the address from a managed pool (`rac pool`), labeled with
+		/// the domain and NIC
+		#[clap(long, value_name = "POOL", conflicts_with = "random")]
+		pool: Option<String>,
+
+		/// Print the `-device` argument for QEMU, with this device model,
+		/// instead of changing a domain
+		#[clap(
+			long,
+			value_name = "MODEL",
+			min_values = 0,
+			default_missing_value = "virtio-net-pci",
+			conflicts_with = "domain"
+		)]
+		qemu: Option<String>,
+	},
+	/// Validate an address and classify it
+	Check {
+		/// Address to check
+		address: String,
+	},
+	/// Explain what every part of an address means
+	Explain {
+		/// Address to explain ('-' to read it from stdin)
+		#[clap(parse(try_from_str = parse_addr_arg))]
+		address: MacAddr,
+	},
+	/// Show an address in large letters, or as a QR code
+	Show {
+		/// Address to show ('-' to read it from stdin)
+		#[clap(parse(try_from_str = parse_addr_arg))]
+		address: MacAddr,
+
+		/// Draw a QR code instead, e.g. to scan it with a phone
+		#[clap(long)]
+		qr: bool,
+	},
+	/// Search vendor prefixes by vendor name
+	Vendors {
+		/// Part of the vendor's name
+		query: String,
+	},
+	/// Print the vendor names starting with a word, for completing
+	/// '--vendor' in a shell
+	#[clap(hide = true)]
+	CompleteVendor {
+		/// What's typed so far
+		#[clap(default_value = "")]
+		start: String,
+	},
+	/// Show current and permanent MAC address of every interface
+	Status {
+		/// Print JSON instead of a table
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// Show only the interfaces whose address differs from the permanent one
+	Diff {
+		/// Print JSON instead of a table
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// List the hosts seen on the local networks, with their IP and MAC
+	/// addresses and vendors (the kernel's neighbor table)
+	Neigh {
+		/// Only the hosts seen on this interface
+		#[clap(short, long)]
+		interface: Option<String>,
+
+		/// Get every host on the local subnets to show itself first, over ARP
+		/// and IPv6
+		#[clap(short, long)]
+		sweep: bool,
+
+		/// Print JSON instead of a table
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// Check, without changing anything, that every interface has the
+	/// address the configuration and restrictions call for, failing if one
+	/// doesn't
+	Audit {
+		/// Print JSON instead of a table
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// Dump rac's state (history, pools, network assignments, the secret of
+	/// stable addresses, the saved hostname) and the configuration file into
+	/// one JSON document, for backups or another machine
+	Export {
+		/// File to write it to instead of stdout, readable only by its owner
+		file: Option<PathBuf>,
+	},
+	/// Restore state and configuration from a document of `rac export`
+	Import {
+		/// File to read it from ('-' or none for stdin)
+		file: Option<PathBuf>,
+
+		/// Replace the files that already exist
+		#[clap(long)]
+		force: bool,
+	},
+	/// Work with the configuration file
+	Config {
+		#[clap(subcommand)]
+		command: ConfigCmds,
+	},
+	/// Watch for MAC address changes as they happen
+	Monitor {
+		/// Print one JSON object per change instead of text
+		#[clap(short, long)]
+		json: bool,
+
+		/// Only interfaces matching one of these patterns, with `*` and `?`
+		/// (e.g. 'wl*')
+		#[clap(short, long, use_value_delimiter = true, value_name = "PATTERNS")]
+		interface: Vec<String>,
+
+		/// What to report [default: addr-change]
+		#[clap(
+			short,
+			long,
+			arg_enum,
+			use_value_delimiter = true,
+			value_name = "EVENTS"
+		)]
+		event: Vec<MonitorEvent>,
+	},
+	/// Show a table of interfaces that refreshes in place, highlighting the
+	/// addresses that changed since the last refresh
+	Watch {
+		/// How often to refresh (e.g. 2s, 1m)
+		#[clap(
+			short = 'n',
+			long,
+			value_name = "DURATION",
+			default_value = "2s",
+			parse(try_from_str = parse_duration)
+		)]
+		interval: Duration,
+	},
+	/// Show the driver of an interface, which decides whether its address can
+	/// be changed
+	Driver {
+		/// Interface to show the driver of
+		interface: String,
+
+		/// Print JSON instead of text
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// Turn random addresses for Wi-Fi scans (before associating) on or off,
+	/// through wpa_supplicant
+	ScanRandom {
+		/// Wi-Fi interface to change
+		interface: String,
+
+		/// Whether scans use a random address
+		#[clap(arg_enum)]
+		state: Toggle,
+	},
+	/// Check what on this system gets in the way of changing addresses
+	/// (tools, privileges, rfkill, network managers, drivers), with advice
+	Doctor {
+		/// Print the findings as JSON
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// Check which ways of changing an address work on this system, on a
+	/// throwaway veth pair in its own network namespace
+	Selftest {
+		/// Run the checks, from inside the namespace
+		#[clap(long, hide = true)]
+		in_netns: bool,
+	},
+	/// Install a NetworkManager dispatcher hook, giving an interface an
+	/// address from the configuration before each connection comes up on it
+	InstallDispatcher {
+		/// Remove the hook instead
+		#[clap(long)]
+		remove: bool,
+	},
+	/// Install the polkit actions, for `--escalate pkexec` and `polkit` in
+	/// the daemon
+	InstallPolkit {
+		/// Remove the actions instead
+		#[clap(long)]
+		remove: bool,
+	},
+	/// Give an interface an address from the configuration, for the
+	/// dispatcher hook
+	#[clap(hide = true)]
+	Dispatch {
+		/// Interface NetworkManager is bringing up
+		interface: String,
+
+		/// Use a random address instead of the configured policy
+		#[clap(long)]
+		random: bool,
+	},
+	/// Install a hook changing addresses from the initramfs, before any
+	/// networking in it or the system starts
+	InstallEarly {
+		/// Use random addresses instead of the configured ones
+		#[clap(long)]
+		random: bool,
+
+		/// The initramfs generator to hook into [default: the one installed]
+		#[clap(long, arg_enum)]
+		target: Option<early::Target>,
+
+		/// Remove the hook instead
+		#[clap(long, conflicts_with = "random")]
+		remove: bool,
+	},
+	/// Write a service for an init system, running the daemon or randomizing
+	/// interfaces at boot
+	Service {
+		/// Init system to write the service for
+		#[clap(arg_enum)]
+		init: service::Init,
+
+		/// What the service runs
+		#[clap(long, arg_enum, default_value = "daemon")]
+		mode: service::Mode,
+
+		/// Write the files into this directory instead of printing them
+		#[clap(long, value_name = "DIR")]
+		dir: Option<PathBuf>,
+	},
+	/// Replace this binary with the latest release, once its checksum matches
+	#[cfg(feature = "self-update")]
+	SelfUpdate {
+		/// Only tell whether there's a newer release
+		#[clap(long)]
+		check: bool,
+
+		/// Where releases are published
+		#[clap(long, value_name = "URL", default_value = update::RELEASES)]
+		url: String,
+	},
+	/// Control the running daemon through its control socket
+	Ctl {
+		#[clap(subcommand)]
+		command: CtlCmds,
+	},
+}
+
+/// What `rac monitor` reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum MonitorEvent {
+	/// An interface's address changed
+	AddrChange,
+	/// An interface is up and has a carrier
+	LinkUp,
+	/// An interface went down or lost its carrier
+	LinkDown,
+	/// An interface appeared
+	Added,
+	/// An interface went away
+	Removed,
+}
+
+impl MonitorEvent {
+	fn name(self) -> &'static str {
+		match self {
+			MonitorEvent::AddrChange => "changed",
+			MonitorEvent::LinkUp => "link_up",
+			MonitorEvent::LinkDown => "link_down",
+			MonitorEvent::Added => "added",
+			MonitorEvent::Removed => "removed",
+		}
+	}
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCmds {
+	/// Report every error in the configuration, and warnings about settings
+	/// that won't do what they seem to, failing on errors
+	Check {
+		/// Print JSON instead of messages
+		#[clap(short, long)]
+		json: bool,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+enum CtlCmds {
+	/// Show what the daemon is doing
+	Status,
+	/// Randomize an interface with the daemon's policy
+	Rotate {
+		/// Interface to randomize
+		interface: String,
+	},
+	/// Restore an interface's permanent address
+	Restore {
+		/// Interface to restore
+		interface: String,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+enum PoolCmds {
+	/// Create a pool handing out addresses under a prefix
+	Create {
+		/// Name of the pool
+		name: String,
+
+		/// Prefix of every address in the pool, 1 to 5 bytes (e.g. 02:AB:CD)
+		#[clap(short, long)]
+		prefix: String,
+	},
+	/// Allocate an unused address from a pool
+	Allocate {
+		/// Name of the pool
+		name: String,
+
+		/// What the address is for (e.g. a VM name)
+		#[clap(short, long)]
+		label: Option<String>,
+	},
+	/// Return an address to a pool
+	Release {
+		/// Name of the pool
+		name: String,
+
+		/// Address to release ('-' to read it from stdin)
+		#[clap(parse(try_from_str = parse_addr_arg))]
+		address: MacAddr,
+	},
+	/// List pools, or the addresses allocated from one
+	List {
+		/// Name of the pool
+		name: Option<String>,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+enum NetworkCmds {
+	/// List the address assigned to each network
+	List {
+		/// Print JSON instead of a table
+		#[clap(short, long)]
+		json: bool,
+	},
+	/// Assign an address to the network an interface is on, the one it has
+	/// now by default
+	Assign {
+		/// Interface whose network it is
+		interface: String,
+
+		/// Address to assign ('-' to read it from stdin)
+		#[clap(short, long, parse(try_from_str = parse_addr_arg))]
+		address: Option<MacAddr>,
+
+		/// Network to assign it for (ssid:<SSID> or gateway:<MAC>) instead of
+		/// the current one
+		#[clap(long)]
+		network: Option<String>,
+	},
+	/// Forget the address assigned to a network, so the next visit gets a new
+	/// one
+	Forget {
+		/// The network, as listed (ssid:<SSID> or gateway:<MAC>)
+		network: String,
+
+		/// Only for this interface
+		#[clap(short, long)]
+		interface: Option<String>,
+	},
+}
+
+#[derive(Debug, ClapArgs)]
+struct SetArgs {
+	/// New MAC address to use ('-' to read it from stdin)
+	#[clap(short, long, parse(try_from_str = parse_addr_arg))]
+	address: Option<MacAddr>,
+
+	/// Interface to use (name)
+	#[clap(short, long)]
+	interface: Option<String>,
+
+	/// Use the interface that currently has this address, e.g. when its name
+	/// changes across reboots
+	#[clap(long, value_name = "MAC", conflicts_with = "interface")]
+	by_mac: Option<MacAddr>,
+
+	/// Use the interface with this index, as routing sockets report it
+	#[clap(long, value_name = "INDEX", conflicts_with_all = &["interface", "by-mac"])]
+	ifindex: Option<u32>,
+
+	/// Use a random MAC address
+	#[clap(short, long)]
+	random: bool,
+
+	/// Vendor prefix for the random address (e.g. 00:1B:21)
+	#[clap(long, value_name = "PREFIX", requires = "random")]
+	oui: Option<String>,
+
+	/// Use the prefix of a popular vendor, so the address blends in
+	#[clap(
+		long,
+		arg_enum,
+		value_name = "CATEGORY",
+		requires = "random",
+		conflicts_with = "oui"
+	)]
+	mimic: Option<oui::Category>,
+
+	/// Use a prefix of this vendor, by name (e.g. "Samsung Electronics")
+	#[clap(
+		long,
+		value_name = "NAME",
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic"]
+	)]
+	vendor: Option<String>,
+
+	/// Template for the random address, with `x` for random nibbles (e.g.
+	/// 02:DE:AD:xx:xx:xx)
+	#[clap(
+		long,
+		value_name = "TEMPLATE",
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor"],
+		parse(try_from_str = parse_template)
+	)]
+	template: Option<config::Policy>,
+
+	/// Randomize only the bits set in this mask, keeping the others from the
+	/// current address (e.g. 00:00:00:ff:ff:ff)
+	#[clap(
+		long,
+		value_name = "MASK",
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor", "template"],
+		parse(try_from_str = parse_mask)
+	)]
+	mask: Option<config::Policy>,
+
+	/// Make the random address look universally administered (U/L bit clear)
+	/// instead of locally administered. Multicast addresses can't be set.
+	#[clap(
+		long,
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor", "template", "mask"]
+	)]
+	unicast_global: bool,
+
+	/// Generate the random address in a SLAP quadrant: aai, eli (under the
+	/// Company ID given with --oui) or sai
+	#[clap(
+		long,
+		arg_enum,
+		value_name = "QUADRANT",
+		requires = "random",
+		conflicts_with_all = &["mimic", "vendor", "template", "mask", "unicast-global"]
+	)]
+	slap: Option<Slap>,
+
+	/// Generate the address with a policy: full, keep-oui, vendor-mimic,
+	/// stable-per-network, stable-per-day or one named in the configuration
+	#[clap(
+		long,
+		value_name = "POLICY",
+		conflicts_with_all = &["address", "oui", "mimic", "vendor", "template", "mask"]
+	)]
+	policy: Option<String>,
+
+	/// Also pin the address in iwd's settings for this network (SSID), so it
+	/// survives reconnects
+	#[clap(long, value_name = "SSID")]
+	iwd: Option<String>,
+
+	/// Also tell wpa_supplicant how to handle the address on this interface
+	#[clap(long, arg_enum, value_name = "MODE")]
+	wpa_supplicant: Option<wpa::Mode>,
+
+	/// Only apply '--wpa-supplicant' to this configured network (id)
+	#[clap(long, value_name = "ID", requires = "wpa-supplicant")]
+	wpa_network: Option<u32>,
+
+	/// If the interface is a bridge port, bond slave or VLAN, set the address
+	/// on the bridge, bond or parent device instead
+	#[clap(long)]
+	master: bool,
+
+	/// Only check the arguments, interface and privileges, without changing
+	/// anything
+	#[clap(long)]
+	check: bool,
+
+	/// Print the result (with how long the link was down) as JSON
+	#[clap(short, long)]
+	json: bool,
+
+	/// Leave the interface down after setting the address, for whatever
+	/// manages it (e.g. NetworkManager) to bring up later
+	#[clap(long)]
+	no_up: bool,
+
+	/// Never take the interface down: change the address while it's up, or
+	/// fail if the driver doesn't allow that (e.g. when connected over it)
+	#[clap(long, conflicts_with = "no-up")]
+	no_down: bool,
+
+	/// Take the interface down even if this SSH session goes over it, which
+	/// otherwise changes it while up (or fails, with --no-up and --rename)
+	#[clap(long, conflicts_with = "no-down")]
+	force: bool,
+
+	/// Also rename the interface while it's down for the change
+	#[clap(long, value_name = "NAME", conflicts_with = "no-down")]
+	rename: Option<String>,
+
+	/// Wait for the interface to carry no TCP sessions or traffic before
+	/// taking it down
+	#[clap(long, conflicts_with = "no-down")]
+	when_idle: bool,
+
+	/// How long '--when-idle' waits before giving up (e.g. 30s, 10m)
+	#[clap(
+		long,
+		value_name = "DURATION",
+		default_value = "10m",
+		requires = "when-idle",
+		parse(try_from_str = parse_duration)
+	)]
+	idle_timeout: Duration,
+
+	/// Before the change, look for another host using the address on the
+	/// interface's segment, and generate another one if there is
+	#[clap(long)]
+	probe: bool,
+
+	/// Warn if the interface holds a DHCP lease, whose IP address (and any
+	/// reservation) the new MAC address won't get
+	#[clap(long)]
+	check_lease: bool,
+
+	/// Take the interface out of NetworkManager's hands, which would put its
+	/// address back on the next reconnect: during the change, or permanently
+	/// (until `rac reset`)
+	#[clap(
+		long,
+		arg_enum,
+		value_name = "HOW",
+		min_values = 0,
+		default_missing_value = "during"
+	)]
+	nm_unmanage: Option<nm::Unmanage>,
+
+	/// Wait for the interface to appear (e.g. a USB adapter being plugged
+	/// in) for up to this long, then change it right away [default: 30s]
+	#[clap(
+		long,
+		value_name = "TIMEOUT",
+		requires = "interface",
+		min_values = 0,
+		default_missing_value = "30s",
+		parse(try_from_str = parse_duration)
+	)]
+	wait_for_interface: Option<Duration>,
+
+	/// Also set a random transient hostname, which DHCP would otherwise send
+	/// along with the new address (`rac reset --hostname` puts it back)
+	#[clap(long)]
+	randomize_hostname: bool,
+
+	/// Also replace the DHCP client-id/DUID of dhcpcd, dhclient and
+	/// NetworkManager, which would identify the host under any address
+	#[clap(long)]
+	rotate_client_id: bool,
+
+	/// Turn IPv6 privacy extensions (temporary addresses) on or off for the
+	/// interface, taking effect as it comes back up
+	#[clap(long, arg_enum, value_name = "STATE")]
+	ipv6_privacy: Option<Privacy>,
+}
+
+#[derive(Debug, ClapArgs)]
+struct CloneArgs {
+	/// Interface to create the new one on (name)
+	#[clap(short, long)]
+	parent: String,
+
+	/// Name of the new interface [default: first free macvlanN/macvtapN]
+	#[clap(short, long)]
+	name: Option<String>,
+
+	/// MAC address of the new interface ('-' to read it from stdin)
+	#[clap(short, long, parse(try_from_str = parse_addr_arg))]
+	address: Option<MacAddr>,
+
+	/// Use a random MAC address
+	#[clap(
+		short,
+		long,
+		conflicts_with = "address",
+		required_unless_present = "address"
+	)]
+	random: bool,
+
+	/// Vendor prefix for the random address (e.g. 00:1B:21)
+	#[clap(long, value_name = "PREFIX", requires = "random")]
+	oui: Option<String>,
+
+	/// Use the prefix of a popular vendor, so the address blends in
+	#[clap(
+		long,
+		arg_enum,
+		value_name = "CATEGORY",
+		requires = "random",
+		conflicts_with = "oui"
+	)]
+	mimic: Option<oui::Category>,
+
+	/// Use a prefix of this vendor, by name (e.g. "Samsung Electronics")
+	#[clap(
+		long,
+		value_name = "NAME",
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic"]
+	)]
+	vendor: Option<String>,
+
+	/// Template for the random address, with `x` for random nibbles (e.g.
+	/// 02:DE:AD:xx:xx:xx)
+	#[clap(
+		long,
+		value_name = "TEMPLATE",
+		requires = "random",
+		conflicts_with_all = &["oui", "mimic", "vendor"],
+		parse(try_from_str = parse_template)
+	)]
+	template: Option<config::Policy>,
+
+	/// Create a macvtap interface (for VMs) instead of a macvlan one
+	#[clap(long)]
+	macvtap: bool,
+}
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+	Human,
+	/// `--plain`: bare values, one per line
+	Plain,
+	/// `--export`: `RAC_*=value` shell assignments
+	Export,
+}
+
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+fn output() -> Output { *OUTPUT.get().unwrap_or(&Output::Human) }
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+/// Whether events are printed as NDJSON (`--output ndjson`)
+fn ndjson() -> bool { FORMAT.get() == Some(&Format::Ndjson) }
+
+/// Start an NDJSON event, with when it happened and what it is
+fn event(name: &str) -> json::Object {
+	let time = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |since| since.as_secs());
+	json::Object::new().num("time", time).str("event", name)
+}
+
+/// Print an event, as NDJSON or as a line of text
+fn report(event: json::Object, text: impl std::fmt::Display) {
+	let (event, text) = (event.finish(), text.to_string());
+	logfile::record(&event, &text);
+	if ndjson() {
+		println!("{}", event);
+	} else {
+		println!("{}", text);
+	}
+}
+
+/// Print informational output, which `--plain` and `--export` leave out
+fn note(msg: impl std::fmt::Display) {
+	if output() == Output::Human {
+		println!("{}", msg);
+	}
+}
+
+/// Print `RAC_<NAME>='value'` for each variable, quoted for the shell
+fn export(vars: &[(&str, String)]) {
+	for (name, value) in vars {
+		println!("RAC_{}='{}'", name, value.replace('\'', "'\\''"));
+	}
+}
+
+/// Report an address that was set, next to the one it replaced: how it's
+/// administered and, if known, where it came from (e.g. its policy)
+fn report_set(inter: &str, addr: MacAddr, changed: &Changed, origin: Option<&str>) {
+	let event = event("changed")
+		.str("interface", inter)
+		.opt_str("old", changed.old.map(|old| old.to_string()).as_deref())
+		.str("address", &addr.to_string())
+		.opt_str("origin", origin);
+	let event = match changed.downtime {
+		Some(downtime) => event.raw("downtime", &secs(downtime)),
+		None => event.raw("downtime", "null"),
+	};
+	let administered = if addr.is_local() {
+		"locally administered"
+	} else {
+		"universally administered"
+	};
+	let text = format!(
+		"{}: {} → {} ({}{}){}",
+		inter,
+		changed
+			.old
+			.map_or("?".to_string(), |old| old.to_string())
+			.dimmed(),
+		addr.to_string().green().bold(),
+		administered,
+		origin.map_or(String::new(), |origin| format!(", {}", origin)),
+		changed
+			.downtime
+			.map_or(String::new(), |downtime| match downtime.as_millis() {
+				ms @ 0..=999 => format!(", link was down for {}ms", ms),
+				_ => format!(", link was down for {:.1}s", downtime.as_secs_f64()),
+			})
+	);
+	if ndjson() || output() == Output::Human {
+		report(event, text);
+		return;
+	}
+	// The log file still gets the whole event
+	logfile::record(&event.finish(), &text);
+	if output() == Output::Plain {
+		println!("{}", addr);
+		return;
+	}
+	let mut vars = vec![("INTERFACE", inter.to_string()), ("MAC", addr.to_string())];
+	vars.extend(changed.old.map(|old| ("OLD_MAC", old.to_string())));
+	vars.extend(
+		changed
+			.downtime
+			.map(|downtime| ("DOWNTIME", secs(downtime))),
+	);
+	export(&vars)
+}
+
+/// Parse a MAC address argument, reading a line from stdin if it's `-`
+fn parse_addr_arg(input: &str) -> Result<MacAddr, String> {
+	if input != "-" {
+		return MacAddr::from_str(input).map_err(|e| e.to_string());
+	}
+	// clap parses every value twice (validation and conversion), so only read once
+	static LINE: OnceLock<io::Result<String>> = OnceLock::new();
+	let line = LINE
+		.get_or_init(|| {
+			let mut line = String::new();
+			io::stdin()
+				.read_line(&mut line)
+				.map(|_| line.trim().to_string())
+		})
+		.as_ref()
+		.map_err(|e| format!("failed to read stdin: {}", e))?;
+	MacAddr::from_str(line).map_err(|e| format!("{} (read '{}' from stdin)", e, line))
+}
+
+/// Read whitespace-separated words (e.g. interface names) from stdin
+fn read_stdin_words() -> io::Result<Vec<String>> {
+	let mut text = String::new();
+	io::stdin().read_to_string(&mut text)?;
+	Ok(text.split_whitespace().map(str::to_string).collect())
+}
+
+/// The interface an argument names: itself, or what it's an alias for
+fn resolve_alias(config: &config::Config, inter: &mut String) -> io::Result<()> {
+	match config.aliases.get(inter.as_str()) {
+		None => {}
+		Some(config::Alias::Name(name)) => *inter = name.clone(),
+		Some(config::Alias::Permanent(addr)) => {
+			*inter = Interfaces::load()?.by_permanent(*addr).ok_or_else(|| {
+				RacError::NoInterface(format!("{} (permanent address {})", inter, addr))
+			})?
+		}
+	}
+	Ok(())
+}
+
+/// Resolve the aliases among the interfaces a command is given
+fn resolve_aliases(config: &config::Config, command: &mut SubCmds) -> io::Result<()> {
+	let inters: Vec<&mut String> = match command {
+		SubCmds::Set(SetArgs { interface, .. })
+		| SubCmds::Undo { interface }
+		| SubCmds::Reset { interface, .. } => interface.iter_mut().collect(),
+		SubCmds::Clone(CloneArgs { parent, .. }) => vec![parent],
+		SubCmds::Range { apply, .. } => apply.iter_mut().collect(),
+		SubCmds::Driver { interface, .. } | SubCmds::ScanRandom { interface, .. } => {
+			vec![interface]
+		}
+		SubCmds::Ctl {
+			command: CtlCmds::Rotate { interface } | CtlCmds::Restore { interface },
+		} => vec![interface],
+		SubCmds::Network {
+			command: NetworkCmds::Assign { interface, .. },
+		} => vec![interface],
+		SubCmds::Network {
+			command: NetworkCmds::Forget { interface, .. },
+		} => interface.iter_mut().collect(),
+		_ => Vec::new(),
+	};
+	for inter in inters {
+		resolve_alias(config, inter)?;
+	}
+	Ok(())
+}
+
+/// Check that `ip` can be run through sudo without a password prompt. pkexec
+/// can't be checked without asking, and `--escalate none` isn't checked at
+/// all.
+fn check_privileges() -> error::Result<()> {
+	if escalate::get() != escalate::Method::Sudo {
+		return Ok(());
+	}
+	let status = timeout::status(
+		Command::new("sudo")
+			.args(["-n", "ip", "-V"])
+			.stdout(Stdio::null())
+			.stderr(Stdio::null()),
+	)
+	.map_err(|e| RacError::PermissionDenied(format!("can't run sudo: {}", e)))?;
+	if !status.success() {
+		return Err(RacError::PermissionDenied(
+			"can't run ip through sudo without a password".to_string(),
+		));
+	}
+	Ok(())
+}
+
+/// Create a macvlan/macvtap interface on top of another one
+fn clone(config: &config::Config, args: CloneArgs) -> io::Result<()> {
+	let interfaces = Interfaces::load()?;
+	if !interfaces.contains(&args.parent) {
+		return Err(RacError::NoInterface(args.parent).into());
+	}
+	let addr = match args.address {
+		Some(addr) => addr,
+		None => config.generate(
+			random_policy(
+				config,
+				args.oui.as_deref(),
+				args.mimic,
+				args.vendor.as_deref(),
+				args.template,
+			)?,
+			Some(&args.parent),
+			None,
+		)?,
+	};
+	let kind = if args.macvtap { "macvtap" } else { "macvlan" };
+	let name = match args.name {
+		Some(name) => name,
+		None => {
+			let mut n = 0;
+			while interfaces.contains(&format!("{}{}", kind, n)) {
+				n += 1;
+			}
+			format!("{}{}", kind, n)
+		}
+	};
+
+	// Bridge mode lets the new interface talk to other macvlans on the parent
+	ip(&[
+		"link",
+		"add",
+		"link",
+		&args.parent,
+		"name",
+		&name,
+		"address",
+		&addr.to_string(),
+		"type",
+		kind,
+		"mode",
+		"bridge",
+	])
+	.inspect_err(|e| println!("Failed to create {}: {}", name, e))?;
+	ip_link(&name, &["up"])?;
+	match output() {
+		Output::Human => println!(
+			"Created {} {} on {} with MAC address {}",
+			kind,
+			name.bold(),
+			args.parent,
+			addr.to_string().green().bold()
+		),
+		Output::Plain => println!("{}\n{}", name, addr),
+		Output::Export => export(&[("INTERFACE", name), ("MAC", addr.to_string())]),
+	}
+	Ok(())
+}
+
+/// Set `use_tempaddr` of an interface with `sudo sysctl`
+fn set_ipv6_privacy(inter: &str, privacy: Privacy) -> error::Result<()> {
+	let value = match privacy {
+		Privacy::On => 2,
+		Privacy::Off => 0,
+	};
+	// The slash form keeps dots in interface names (VLANs) from splitting the key
+	let setting = format!("net/ipv6/conf/{}/use_tempaddr={}", inter, value);
+	let output = timeout::output(escalate::command("sysctl").args(["-q", "-w", &setting]))?;
+	if !output.status.success() {
+		let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+		if escalate::refused(&stderr) || stderr.contains("denied") {
+			return Err(RacError::PermissionDenied(stderr));
+		}
+		return Err(RacError::Backend(stderr));
+	}
+	Ok(())
+}
+
+/// `rac scan-random`: nl80211 has no switch for this, scans use a random
+/// address when whoever requests them asks for one, which is wpa_supplicant
+fn scan_random(inter: &str, state: Toggle) -> io::Result<()> {
+	let control = wpa::Control::open(inter).inspect_err(|e| println!("{}", e.to_string().red()))?;
+	control
+		.set_scan_random(state == Toggle::On)
+		.inspect_err(|e| {
+			println!(
+				"Failed to change scan randomization ({}), the driver may not support it",
+				e
+			)
+		})?;
+	let state = if state == Toggle::On { "on" } else { "off" };
+	match control.save() {
+		Ok(()) => note(format!(
+			"Random scan addresses are now {} for {}",
+			state.bold(),
+			inter
+		)),
+		Err(_) => note(
+			format!(
+				"Random scan addresses are now {} for {}, until wpa_supplicant restarts (is \
+				 update_config=1 set?)",
+				state.bold(),
+				inter
+			)
+			.yellow(),
+		),
+	}
+	Ok(())
+}
+
+/// `rac install-dispatcher`
+fn install_dispatcher(config: Option<&std::path::Path>, remove: bool) -> io::Result<()> {
+	if remove {
+		let removed =
+			dispatcher::remove().inspect_err(|e| println!("Failed to remove the hook: {}", e))?;
+		match removed {
+			true => note(format!("Removed {}", dispatcher::PATH)),
+			false => note(format!("No hook is installed at {}", dispatcher::PATH).yellow()),
+		}
+		return Ok(());
+	}
+	// The hook doesn't run from the current directory
+	let config = config.map(std::fs::canonicalize).transpose()?;
+	dispatcher::install(&std::env::current_exe()?, config.as_deref())
+		.inspect_err(|e| println!("Failed to write {}: {}", dispatcher::PATH.red().bold(), e))?;
+	note(format!(
+		"Installed {}, NetworkManager now runs it before every connection comes up",
+		dispatcher::PATH.bold()
+	));
+	Ok(())
+}
+
+/// `rac install-polkit`
+fn install_polkit(remove: bool) -> io::Result<()> {
+	if remove {
+		let removed =
+			escalate::remove().inspect_err(|e| println!("Failed to remove the actions: {}", e))?;
+		match removed {
+			true => note(format!("Removed {}", escalate::POLICY_PATH)),
+			false => {
+				note(format!("No actions are installed at {}", escalate::POLICY_PATH).yellow())
+			}
+		}
+		return Ok(());
+	}
+	escalate::install().inspect_err(|e| {
+		println!(
+			"Failed to write {}: {}",
+			escalate::POLICY_PATH.red().bold(),
+			e
+		)
+	})?;
+	note(format!(
+		"Installed {}, `--escalate pkexec` now asks once per change",
+		escalate::POLICY_PATH.bold()
+	));
+	Ok(())
+}
+
+/// `rac service`
+fn write_service(
+	config: Option<&std::path::Path>,
+	init: service::Init,
+	mode: service::Mode,
+	dir: Option<&std::path::Path>,
+) -> io::Result<()> {
+	let config = config.map(std::fs::canonicalize).transpose()?;
+	let files = service::files(init, mode, &std::env::current_exe()?, config.as_deref());
+	let Some(dir) = dir else {
+		for (i, file) in files.iter().enumerate() {
+			// Like head(1) with several files
+			if files.len() > 1 {
+				println!("{}==> {} <==", if i > 0 { "\n" } else { "" }, file.path);
+			}
+			print!("{}", file.text);
+		}
+		return Ok(());
+	};
+	write_files(dir, &files)?;
+	note(service::hint(init, mode));
+	Ok(())
+}
+
+/// Write generated files under a directory
+fn write_files(dir: &std::path::Path, files: &[service::File]) -> io::Result<()> {
+	for file in files {
+		let path = dir.join(file.path.trim_start_matches('/'));
+		let written = (|| -> io::Result<()> {
+			if let Some(parent) = path.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+			std::fs::write(&path, &file.text)?;
+			if file.executable {
+				std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+			}
+			Ok(())
+		})();
+		written.inspect_err(|e| println!("Failed to write {}: {}", path.display(), e))?;
+		note(format!("Wrote {}", path.display()));
+	}
+	Ok(())
+}
+
+/// `rac install-early`
+fn install_early(
+	config: &config::Config,
+	config_path: Option<&std::path::Path>,
+	random: bool,
+	target: Option<early::Target>,
+	remove: bool,
+) -> io::Result<()> {
+	let target = target.unwrap_or_else(early::detect);
+	if remove {
+		let removed =
+			early::remove(target).inspect_err(|e| println!("Failed to remove the hook: {}", e))?;
+		if removed.is_empty() {
+			note("No early hook is installed".yellow());
+			return Ok(());
+		}
+		for path in removed {
+			note(format!("Removed {}", path));
+		}
+		note(early::hint(target));
+		return Ok(());
+	}
+	if target == early::Target::SystemdLink && !random {
+		println!(
+			"{}",
+			"A .link file can only make udev pick random addresses, pass --random".red()
+		);
+		return Err(RacError::Parse("systemd-link needs --random".to_string()).into());
+	}
+	let config_path = config_path.map(std::fs::canonicalize).transpose()?;
+	let files = early::files(
+		target,
+		&std::env::current_exe()?,
+		config_path.as_deref(),
+		random,
+		&config.general.exclude,
+	);
+	write_files(std::path::Path::new("/"), &files)?;
+	note(early::hint(target));
+	Ok(())
+}
+
+/// `rac self-update`
+#[cfg(feature = "self-update")]
+fn self_update(check: bool, url: &str) -> io::Result<()> {
+	let checksum = match update::check(url)
+		.inspect_err(|e| println!("Failed to look up the latest release: {}", e))?
+	{
+		update::Status::UpToDate => {
+			note("rac is up to date".green());
+			return Ok(());
+		}
+		update::Status::Available(checksum) => checksum,
+	};
+	if check {
+		note("A new release is available, `rac self-update` installs it".yellow());
+		return Ok(());
+	}
+	let exe =
+		update::install(url, &checksum).inspect_err(|e| println!("Failed to update rac: {}", e))?;
+	note(format!(
+		"Updated {}",
+		exe.display().to_string().green().bold()
+	));
+	Ok(())
+}
+
+/// `rac dispatch`, run by the hook before a connection comes up on an
+/// interface
+fn dispatch(config: &config::Config, inter: &str, random: bool) -> io::Result<()> {
+	if config.is_excluded(inter) {
+		note(format!("Skipping excluded interface: {}", inter.yellow()));
+		return Ok(());
+	}
+	let link = link_of(inter)?.ok_or_else(|| RacError::NoInterface(inter.to_string()))?;
+	// NetworkManager runs hooks for VPNs, WireGuard, ... too
+	if link.is_loopback() || link.mac().is_none() {
+		return Ok(());
+	}
+	let (addr, mode) = match random {
+		true => (
+			config.generate(config::Policy::Random, Some(inter), link.mac()),
+			config::Policy::Random.mode(),
+		),
+		false => (
+			config.address_for(inter, link.mac()),
+			config.mode_for(inter),
+		),
+	};
+	let addr =
+		addr.inspect_err(|e| println!("Failed to generate an address for {}: {}", inter, e))?;
+	apply_addr(&link, addr, mode)?;
+	Ok(())
+}
+
+/// Set MAC address, given an interface name and a MAC address
+fn set_addr(inter: &str, addr: MacAddr, mode: restrictions::Mode) -> error::Result<()> {
+	let changed = change_addr(inter, addr, Change::Bounce, history::Kind::Set, mode, None)?;
+	report_set(inter, addr, &changed, None);
+	Ok(())
+}
+
+/// Steps of an address change, shown on a terminal as they happen
+struct Progress<'a> {
+	inter:   &'a str,
+	enabled: bool,
+	frame:   usize,
+}
+
+impl<'a> Progress<'a> {
+	const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+	/// Only shown with human output on a terminal, on stderr
+	fn new(inter: &'a str) -> Progress<'a> {
+		Progress {
+			inter,
+			enabled: output() == Output::Human && io::stderr().is_terminal(),
+			frame: 0,
+		}
+	}
+
+	/// Show the current step, advancing the spinner if it's the same as before
+	fn step(&mut self, step: &str) {
+		if !self.enabled {
+			return;
+		}
+		let frame = Self::FRAMES[self.frame % Self::FRAMES.len()];
+		eprint!("\r\x1b[K{} {}: {}…", frame, self.inter, step);
+		io::stderr().flush().ok();
+		self.frame += 1;
+	}
+}
+
+impl Drop for Progress<'_> {
+	fn drop(&mut self) {
+		if self.enabled {
+			eprint!("\r\x1b[K");
+		}
+	}
+}
+
+/// Change the address of an interface (see [`change::apply`]), keeping up
+/// the one this SSH session goes over, and run the hooks
+fn change_addr(
+	inter: &str,
+	addr: MacAddr,
+	how: Change,
+	kind: history::Kind,
+	mode: restrictions::Mode,
+	rename: Option<&str>,
+) -> error::Result<Changed> {
+	// Taking down the interface this SSH session goes over would cut it off
+	let session = ssh::guarding(inter)
+		.filter(|_| link_of(inter).is_ok_and(|link| link.is_some_and(|link| link.is_up())));
+	let how = match (session, how) {
+		(Some(session), Change::Bounce) if rename.is_none() => {
+			note(
+				format!(
+					"{} carries this SSH session (from {}), changing it without taking it down \
+					 (--force to take it down anyway)",
+					inter, session.client
+				)
+				.yellow(),
+			);
+			Change::Live
+		}
+		(Some(session), Change::Bounce | Change::LeaveDown) => {
+			return Err(RacError::Unsafe(format!(
+				"{} carries this SSH session (from {}), taking it down would cut it off; pass \
+				 --force to do it anyway",
+				inter, session.client
+			)))
+		}
+		_ => how,
+	};
+	let mut progress = Progress::new(inter);
+	let changed = change::apply(inter, addr, how, kind, mode, rename, &mut |step| {
+		progress.step(step)
+	})
+	.map_err(|e| match e {
+		RacError::Backend(e) if session.is_some() => RacError::Backend(format!(
+			"{}, and taking it down would cut off this SSH session; `rac set --force` does it \
+			 anyway",
+			e
+		)),
+		RacError::Backend(e) if how == Change::Live => RacError::Backend(format!(
+			"{}, drop --no-down to take it down for the change",
+			e
+		)),
+		e => e,
+	})?;
+	if let Some(e) = &changed.unrecorded {
+		note(format!("Couldn't record the change for `rac undo`: {}", e).yellow());
+	}
+	if let Some(old) = changed.old.filter(|old| *old != addr) {
+		for (hook, e) in hooks::run(rename.unwrap_or(inter), kind, old, addr) {
+			eprintln!("{}", format!("Hook {} failed: {}", hook, e).yellow());
+		}
+	}
+	Ok(changed)
+}
+
+/// Make sure no other host on the interface's segment uses an address,
+/// generating another one with the policy (if there is one) until none does
+fn probe_addr(
+	config: &config::Config,
+	inter: &str,
+	policy: Option<config::Policy>,
+	mut addr: MacAddr,
+	current: Option<MacAddr>,
+) -> io::Result<MacAddr> {
+	if !link_of(inter)?.is_some_and(|link| link.has_carrier()) {
+		note(format!("Not probing, {} isn't connected to anything", inter).yellow());
+		return Ok(addr);
+	}
+	for _ in 0..probe::ATTEMPTS {
+		if !probe::in_use(inter, addr).inspect_err(|e| println!("Failed to probe: {}", e))? {
+			return Ok(addr);
+		}
+		let Some(policy) = policy else {
+			break;
+		};
+		note(
+			format!(
+				"{} is in use on {}'s segment, generating another",
+				addr, inter
+			)
+			.yellow(),
+		);
+		addr = config.generate(policy, Some(inter), current)?;
+	}
+	println!(
+		"{} is in use by another host on {}'s segment",
+		addr.to_string().red().bold(),
+		inter
+	);
+	Err(RacError::Unusable(format!("{} is in use on {}'s segment", addr, inter)).into())
+}
+
+/// Set the address of a link, leaving it down if nothing has brought it up
+/// yet
+fn apply_addr(link: &netlink::Link, addr: MacAddr, mode: restrictions::Mode) -> error::Result<()> {
+	if link.is_up() {
+		set_addr(&link.name, addr, mode)
+	} else {
+		set_addr_down(&link.name, addr, mode)
+	}
+}
+
+/// Put back an address rac changed, keeping the link up or down as it is
+fn undo_addr(link: &netlink::Link, addr: MacAddr) -> error::Result<()> {
+	let how = if link.is_up() {
+		Change::Bounce
+	} else {
+		Change::LeaveDown
+	};
+	let changed = change_addr(
+		&link.name,
+		addr,
+		how,
+		history::Kind::Undo,
+		restrictions::Mode::Restore,
+		None,
+	)?;
+	report_set(&link.name, addr, &changed, Some("undo"));
+	Ok(())
+}
+
+/// Change the addresses of several interfaces, all or nothing: everything is
+/// checked before the first change, and a failure undoes the changes made so
+/// far. With `keep_going`, failures are only counted. Returns the number of
+/// interfaces that failed.
+fn apply_all(
+	changes: &[(String, MacAddr, restrictions::Mode)],
+	keep_going: bool,
+) -> io::Result<usize> {
+	let mut links = Vec::with_capacity(changes.len());
+	for (inter, ..) in changes {
+		let link = link_of(inter)?.ok_or_else(|| RacError::NoInterface(inter.clone()))?;
+		if link.mac().is_none() {
+			return Err(RacError::Parse(format!("{} doesn't use MAC addresses", inter)).into());
+		}
+		links.push(link);
+	}
+	check_privileges()?;
+
+	let mut done: Vec<&netlink::Link> = Vec::new();
+	let mut failed = 0;
+	for (link, &(_, addr, mode)) in links.iter().zip(changes) {
+		let Err(e) = apply_addr(link, addr, mode) else {
+			done.push(link);
+			continue;
+		};
+		report(
+			event("failed")
+				.str("interface", &link.name)
+				.str("error", &e.to_string()),
+			format!(
+				"Failed to set MAC address ({}): {}",
+				link.name.red().bold(),
+				e
+			),
+		);
+		failed += 1;
+		if keep_going {
+			continue;
+		}
+		// Put back what was already changed, last first
+		for link in done.iter().rev() {
+			let original = link.mac().expect("checked above");
+			match undo_addr(link, original) {
+				// The rollback is already an event of its own
+				Ok(()) if ndjson() => {}
+				Ok(()) => println!("Rolled back {} to {}", link.name, original),
+				Err(e) => report(
+					event("failed")
+						.str("interface", &link.name)
+						.str("error", &e.to_string()),
+					format!("Failed to roll back {}: {}", link.name.red().bold(), e),
+				),
+			}
+		}
+		let undone = match done.len() {
+			0 => "Nothing was changed".to_string(),
+			n => format!("Rolled back {} interface(s)", n),
+		};
+		note(format!("{}, use --keep-going to change the others anyway", undone).yellow());
+		return Err(e.into());
+	}
+	Ok(failed)
+}
+
+/// A duration in seconds, to the millisecond
+fn secs(duration: Duration) -> String { format!("{:.3}", duration.as_secs_f64()) }
+
+/// An interface as it is now, e.g. after changing it
+fn link_of(inter: &str) -> io::Result<Option<netlink::Link>> { Ok(Interfaces::load()?.take(inter)) }
+
+/// Set MAC address of an interface that is already down, leaving it down
+fn set_addr_down(inter: &str, addr: MacAddr, mode: restrictions::Mode) -> error::Result<()> {
+	let changed = change_addr(
+		inter,
+		addr,
+		Change::LeaveDown,
+		history::Kind::Set,
+		mode,
+		None,
+	)?;
+	report_set(inter, addr, &changed, None);
+	Ok(())
+}
+
+/// Whether an interface is Wi-Fi
+fn is_wireless(inter: &str) -> bool {
+	let dir = std::path::Path::new("/sys/class/net").join(inter);
+	dir.join("wireless").exists() || dir.join("phy80211").exists()
+}
+
+/// Check whether an interface is backed by a physical device
+fn is_physical(inter: &str) -> bool {
+	std::path::Path::new("/sys/class/net")
+		.join(inter)
+		.join("device")
+		.exists()
+}
+
+/// The device an interface's address depends on
+enum Upper {
+	Bridge(String),
+	/// A bond, with its `fail_over_mac` setting
+	Bond(String, String),
+	/// The parent of a VLAN
+	Vlan(String),
+}
+
+impl Upper {
+	fn name(&self) -> &str {
+		match self {
+			Upper::Bridge(name) | Upper::Bond(name, _) | Upper::Vlan(name) => name,
+		}
+	}
+}
+
+/// Find the bridge or bond an interface is enslaved to, or the parent of a VLAN
+fn upper_of(inter: &str) -> Option<Upper> {
+	let sys = std::path::Path::new("/sys/class/net");
+	if let Ok(master) = std::fs::read_link(sys.join(inter).join("master")) {
+		let name = master.file_name()?.to_string_lossy().into_owned();
+		if let Ok(mode) = std::fs::read_to_string(sys.join(&name).join("bonding/fail_over_mac")) {
+			// e.g. "none 0"
+			let mode = mode.split_whitespace().next().unwrap_or("none").to_string();
+			return Some(Upper::Bond(name, mode));
+		}
+		return Some(Upper::Bridge(name));
+	}
+	// "eth0.10  VID: 10	 REORDER_HDR: 1  dev->priv_flags: 1\n...\nDevice: eth0"
+	let vlan = std::fs::read_to_string(std::path::Path::new("/proc/net/vlan").join(inter)).ok()?;
+	vlan.lines()
+		.find_map(|line| line.strip_prefix("Device:"))
+		.map(|parent| Upper::Vlan(parent.trim().to_string()))
+}
+
+/// Warn about (or, with `--master`, switch to) the device an interface's
+/// address depends on, returning the interface to set the address on
+fn resolve_upper(inter: String, master: bool) -> String {
+	let upper = match upper_of(&inter) {
+		Some(upper) => upper,
+		None => return inter,
+	};
+	if master {
+		note(format!(
+			"{} depends on {}, setting the address there instead",
+			inter,
+			upper.name().bold()
+		));
+		return upper.name().to_string();
+	}
+	let warning = match &upper {
+		Upper::Bridge(bridge) => format!(
+			"{} is a port of bridge {}, which may take its address from its ports",
+			inter, bridge
+		),
+		// With fail_over_mac=none the bond gives every slave its own address
+		Upper::Bond(bond, mode) if mode == "none" => format!(
+			"{} is enslaved to bond {}, which will overwrite its address",
+			inter, bond
+		),
+		Upper::Bond(bond, mode) => format!(
+			"{} is enslaved to bond {} (fail_over_mac {}), which follows its slaves' addresses",
+			inter, bond, mode
+		),
+		Upper::Vlan(parent) => format!(
+			"{} is a VLAN on {}, which other VLANs may share an address with",
+			inter, parent
+		),
+	};
+	note(warning.yellow());
+	note(
+		format!(
+			"Use --master to set the address on {} instead",
+			upper.name()
+		)
+		.yellow(),
+	);
+	inter
+}
+
+/// Randomize every (physical) interface according to the configured policy
+fn randomize_all(
+	config: &config::Config,
+	physical: bool,
+	keep_going: bool,
+	policy: Option<&str>,
+) -> io::Result<()> {
+	let policy = policy
+		.map(|name| config.policy(name))
+		.transpose()
+		.map_err(RacError::Parse)?;
+	let links = Interfaces::load()?;
+	let mut skipped = 0;
+	let mut changes = Vec::new();
+
+	for link in links {
+		if link.is_loopback() || link.address.is_none() || (physical && !is_physical(&link.name)) {
+			continue;
+		}
+		if link.mac().is_none() {
+			report(
+				event("skipped")
+					.str("interface", &link.name)
+					.str("reason", "no MAC address"),
+				format!(
+					"Skipping {}, it doesn't use MAC addresses",
+					link.name.yellow()
+				),
+			);
+			skipped += 1;
+			continue;
+		}
+		if config.is_excluded(&link.name) {
+			report(
+				event("skipped")
+					.str("interface", &link.name)
+					.str("reason", "excluded"),
+				format!("Skipping excluded interface: {}", link.name.yellow()),
+			);
+			skipped += 1;
+			continue;
+		}
+		// Every address is generated before the first change
+		let (addr, mode) = match (policy, config.fixed_address(&link.name)) {
+			(Some(policy), None) => (
+				config.generate(policy, Some(&link.name), link.mac()),
+				policy.mode(),
+			),
+			_ => (
+				config.address_for(&link.name, link.mac()),
+				config.mode_for(&link.name),
+			),
+		};
+		let addr = addr.map_err(|e| {
+			report(
+				event("failed")
+					.str("interface", &link.name)
+					.str("error", &e.to_string()),
+				format!(
+					"Failed to generate an address for {}: {}",
+					link.name.red().bold(),
+					e
+				),
+			);
+			e
+		})?;
+		changes.push((link.name, addr, mode));
+	}
+
+	let failed = apply_all(&changes, keep_going)?;
+	report(
+		event("summary")
+			.num("changed", changes.len() - failed)
+			.num("skipped", skipped)
+			.num("failed", failed),
+		format!(
+			"{} changed, {} skipped, {} failed",
+			(changes.len() - failed).to_string().green().bold(),
+			skipped.to_string().yellow(),
+			failed.to_string().red()
+		),
+	);
+	if failed > 0 {
+		return Err(io::Error::other(format!("{} interface(s) failed", failed)));
+	}
+	Ok(())
+}
+
+/// Parse colon- or dash-separated hex bytes (e.g. `02:AB:CD`)
+fn parse_hex_bytes(input: &str) -> Option<Vec<u8>> {
+	input
+		.split([':', '-'])
+		.map(|byte| u8::from_str_radix(byte, 16).ok())
+		.collect()
+}
+
+/// Parse a three byte vendor prefix given on the command line
+fn parse_oui(input: &str) -> error::Result<[u8; 3]> {
+	parse_hex_bytes(input)
+		.and_then(|bytes| <[u8; 3]>::try_from(bytes).ok())
+		.ok_or_else(|| {
+			println!("Not a valid vendor prefix: '{}'", input.red().bold());
+			RacError::Parse(format!("invalid vendor prefix '{}'", input))
+		})
+}
+
+/// Parse a duration like `30s` (`--timeout`, `watch --interval`, ...)
+fn parse_duration(input: &str) -> Result<Duration, String> {
+	schedule::parse_interval(input)
+		.ok_or_else(|| format!("invalid duration '{}', expected e.g. 30s or 10m", input))
+}
+
+/// Parse a `--cooldown` like `5m`, or `0` for none
+fn parse_cooldown(input: &str) -> Result<Duration, String> {
+	cooldown::parse(input)
+		.ok_or_else(|| format!("invalid cooldown '{}', expected e.g. 5m or 0", input))
+}
+
+/// Parse a template like `02:DE:AD:xx:xx:xx`, where `x` nibbles are random
+fn parse_template(input: &str) -> Result<config::Policy, String> {
+	let err = || {
+		format!(
+			"invalid template '{}', expected e.g. 02:DE:AD:xx:xx:xx",
+			input
+		)
+	};
+	let parts: Vec<&str> = input.split([':', '-']).collect();
+	if parts.len() != 6 {
+		return Err(err());
+	}
+	let (mut base, mut mask) = ([0u8; 6], [0u8; 6]);
+	for (i, part) in parts.iter().enumerate() {
+		let nibbles: Vec<char> = part.chars().collect();
+		let [high, low] = nibbles[..] else {
+			return Err(err());
+		};
+		for (c, shift) in [(high, 4), (low, 0)] {
+			match c {
+				'x' | 'X' => mask[i] |= 0xf << shift,
+				c => base[i] |= (c.to_digit(16).ok_or_else(err)? as u8) << shift,
+			}
+		}
+	}
+	Ok(config::Policy::Masked {
+		base: Some(MacAddr::new(base)),
+		mask,
+	})
+}
+
+/// Parse a mask of the bits to randomize, like `00:00:00:ff:ff:ff`
+fn parse_mask(input: &str) -> Result<config::Policy, String> {
+	let mask = parse_hex_bytes(input)
+		.and_then(|bytes| <[u8; 6]>::try_from(bytes).ok())
+		.ok_or_else(|| format!("invalid mask '{}', expected e.g. 00:00:00:ff:ff:ff", input))?;
+	Ok(config::Policy::Masked { base: None, mask })
+}
+
+/// Policy for addresses generated on request, optionally under a vendor
+/// prefix or following a template
+fn random_policy(
+	config: &config::Config,
+	oui: Option<&str>,
+	mimic: Option<oui::Category>,
+	vendor: Option<&str>,
+	template: Option<config::Policy>,
+) -> error::Result<config::Policy> {
+	Ok(match (oui, mimic, vendor, template) {
+		(.., Some(template)) => template,
+		(Some(oui), ..) => config::Policy::Oui(parse_oui(oui)?),
+		(None, Some(category), ..) => config::Policy::Mimic(category),
+		(None, None, Some(vendor), _) => {
+			let db = oui::Database::load(config.general.oui_db.as_deref());
+			let prefixes = db.vendor(vendor).map_err(|e| {
+				println!("{}", e.red());
+				RacError::Parse(format!("unknown vendor '{}'", vendor))
+			})?;
+			config::Policy::Oui(prefixes[rand::random::<usize>() % prefixes.len()])
+		}
+		(None, None, None, None) => config::Policy::Random,
+	})
+}
+
+/// A fully random address with the given U/L (local) and I/G (multicast)
+/// bits, rather than the locally administered unicast of `Policy::Random`
+fn bits_policy(local: bool, multicast: bool) -> config::Policy {
+	let first = if local { 0x02 } else { 0 } | if multicast { 0x01 } else { 0 };
+	config::Policy::Masked {
+		base: Some(MacAddr::new([first, 0, 0, 0, 0, 0])),
+		mask: [0xfc, 0xff, 0xff, 0xff, 0xff, 0xff],
+	}
+}
+
+/// A random address in a SLAP quadrant: locally administered unicast, with
+/// the quadrant's Y and Z bits. An ELI keeps the Company ID `cid` (with its
+/// second hex digit made A) and randomizes the rest.
+fn slap_policy(quadrant: Slap, cid: Option<&str>) -> error::Result<config::Policy> {
+	let first = match (quadrant, cid) {
+		(Slap::Eli, Some(cid)) => {
+			let mut cid = parse_oui(cid)?;
+			cid[0] = cid[0] & 0xf0 | 0x0a;
+			return Ok(config::Policy::Masked {
+				base: Some(MacAddr::new([cid[0], cid[1], cid[2], 0, 0, 0])),
+				mask: [0, 0, 0, 0xff, 0xff, 0xff],
+			});
+		}
+		(Slap::Eli, None) => {
+			return Err(RacError::Parse(
+				"an ELI starts with a Company ID, give it with --oui".to_string(),
+			))
+		}
+		(_, Some(_)) => {
+			return Err(RacError::Parse(
+				"--oui only goes with --slap eli, the other quadrants have no Company ID"
+					.to_string(),
+			))
+		}
+		(Slap::Aai, None) => 0x02,
+		(Slap::Sai, None) => 0x06,
+	};
+	Ok(config::Policy::Masked {
+		base: Some(MacAddr::new([first, 0, 0, 0, 0, 0])),
+		mask: [0xf0, 0xff, 0xff, 0xff, 0xff, 0xff],
+	})
+}
+
+/// Format bytes as colon-separated hex
+fn hex_bytes(bytes: &[u8]) -> String {
+	bytes
+		.iter()
+		.map(|b| format!("{:02X}", b))
+		.collect::<Vec<_>>()
+		.join(":")
+}
+
+/// List every interface with its MAC and the link-local address it implies
+fn list(json: bool, format: Option<Format>) -> io::Result<()> {
+	let links = Interfaces::load()?;
+	let rows: Vec<_> = links
+		.iter()
+		.filter(|link| !link.is_loopback())
+		.filter_map(|link| {
+			let addr = link.address.as_ref()?;
+			let driver = ethtool::driver(&link.name).ok().flatten();
+			Some(ListRow {
+				name: &link.name,
+				addr,
+				state: sys_value(&link.name, "operstate").unwrap_or_else(|| "unknown".to_string()),
+				kind: inter_kind(&link.name, driver.as_deref()),
+				mtu: sys_value(&link.name, "mtu").and_then(|mtu| mtu.parse().ok()),
+				driver,
+			})
+		})
+		.collect();
+
+	if json {
+		println!(
+			"{}",
+			json::array(rows.iter().map(|row| {
+				let object = json::Object::new()
+					.str("interface", row.name)
+					.str("address", &row.addr.to_string())
+					.str("state", &row.state)
+					.str("type", row.kind)
+					.opt_str("driver", row.driver.as_deref());
+				match row.mtu {
+					Some(mtu) => object.num("mtu", mtu),
+					None => object.raw("mtu", "null"),
+				}
+				.opt_str(
+					"link_local",
+					row.addr
+						.mac()
+						.map(|mac| mac.to_link_local_ipv6().to_string())
+						.as_deref(),
+				)
+				.finish()
+			}))
+		);
+		return Ok(());
+	}
+	let values = |row: &ListRow| {
+		[
+			Some(row.name.to_string()),
+			Some(row.addr.to_string()),
+			Some(row.state.clone()),
+			Some(row.kind.to_string()),
+			row.driver.clone(),
+			row.mtu.map(|mtu| mtu.to_string()),
+			row.addr
+				.mac()
+				.map(|mac| mac.to_link_local_ipv6().to_string()),
+		]
+	};
+	match format {
+		Some(Format::Csv) => {
+			println!("{}", LIST_FIELDS.join(","));
+			for row in &rows {
+				let values = values(row).map(|value| csv_field(value.as_deref().unwrap_or("")));
+				println!("{}", values.join(","));
+			}
+			return Ok(());
+		}
+		Some(Format::Yaml) => {
+			if rows.is_empty() {
+				println!("[]");
+			}
+			for row in &rows {
+				for (i, (name, value)) in LIST_FIELDS.into_iter().zip(values(row)).enumerate() {
+					// JSON strings are YAML strings too, and keep addresses
+					// from reading as anything else
+					let value = match value {
+						Some(mtu) if name == "mtu" => mtu,
+						Some(value) => json::string(&value),
+						None => "null".to_string(),
+					};
+					println!("{} {}: {}", if i == 0 { "-" } else { " " }, name, value);
+				}
+			}
+			return Ok(());
+		}
+		_ => {}
+	}
+
+	let width = |column: &dyn Fn(&ListRow) -> usize, min: usize| {
+		rows.iter().map(column).max().unwrap_or(0).max(min)
+	};
+	let name_width = width(&|row| row.name.len(), 9);
+	let driver_width = width(&|row| row.driver.as_ref().map_or(1, String::len), 6);
+	println!(
+		"{:<name_width$}  {:<17}  {:<7}  {:<8}  {:<driver_width$}  {:>5}  LINK-LOCAL",
+		"INTERFACE",
+		"MAC",
+		"STATE",
+		"TYPE",
+		"DRIVER",
+		"MTU",
+		name_width = name_width,
+		driver_width = driver_width
+	);
+	for row in rows {
+		println!(
+			"{:<name_width$}  {}  {:<7}  {:<8}  {:<driver_width$}  {:>5}  {}",
+			row.name,
+			row.addr.to_string().green(),
+			row.state,
+			row.kind,
+			row.driver.as_deref().unwrap_or("-"),
+			row.mtu.map_or("-".to_string(), |mtu| mtu.to_string()),
+			row.addr
+				.mac()
+				.map_or("-".to_string(), |mac| mac.to_link_local_ipv6().to_string()),
+			name_width = name_width,
+			driver_width = driver_width
+		);
+	}
+	Ok(())
+}
+
+/// The fields of `rac list --output csv|yaml`, named like the JSON ones
+const LIST_FIELDS: [&str; 7] = [
+	"interface",
+	"address",
+	"state",
+	"type",
+	"driver",
+	"mtu",
+	"link_local",
+];
+
+/// Quote a CSV field if it needs it (RFC 4180)
+fn csv_field(value: &str) -> String {
+	if value.contains([',', '"', '\n']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}
+
+/// A line of `rac list`
+struct ListRow<'a> {
+	name:   &'a str,
+	addr:   &'a HwAddr,
+	/// Operational state (`up`, `down`, `dormant`, ...)
+	state:  String,
+	kind:   &'static str,
+	driver: Option<String>,
+	mtu:    Option<u32>,
+}
+
+/// Read an attribute of an interface from /sys/class/net
+fn sys_value(inter: &str, attr: &str) -> Option<String> {
+	let path = std::path::Path::new("/sys/class/net")
+		.join(inter)
+		.join(attr);
+	std::fs::read_to_string(path)
+		.ok()
+		.map(|value| value.trim().to_string())
+}
+
+/// Drivers of virtual interfaces, as reported by ethtool
+const VIRTUAL_DRIVERS: [&str; 6] = ["veth", "macvlan", "macvtap", "dummy", "vxlan", "ifb"];
+
+/// What kind of interface this is (`wifi`, `ethernet`, `bridge`, ...)
+fn inter_kind(inter: &str, driver: Option<&str>) -> &'static str {
+	let dir = std::path::Path::new("/sys/class/net").join(inter);
+	if is_wireless(inter) {
+		"wifi"
+	} else if dir.join("bridge").exists() {
+		"bridge"
+	} else if dir.join("bonding").exists() {
+		"bond"
+	} else if let Some(flags) = sys_value(inter, "tun_flags") {
+		// IFF_TAP
+		let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).unwrap_or(0);
+		if flags & 0x2 != 0 {
+			"tap"
+		} else {
+			"tun"
+		}
+	} else if std::path::Path::new("/proc/net/vlan").join(inter).exists() {
+		"vlan"
+	} else if let Some(kind) =
+		driver.and_then(|driver| VIRTUAL_DRIVERS.iter().find(|k| **k == driver))
+	{
+		// Virtual drivers are named after what they are
+		kind
+	} else if is_physical(inter) {
+		"ethernet"
+	} else {
+		"virtual"
+	}
+}
+
+/// Print the EUI-64 forms and IPv6 link-local address of a MAC address
+fn derive(addr: MacAddr) -> io::Result<()> {
+	let b = addr.bytes;
+	let eui64 = hex_bytes(&[b[0], b[1], b[2], 0xff, 0xfe, b[3], b[4], b[5]]);
+	match output() {
+		Output::Human => {}
+		Output::Plain => {
+			println!("{}", addr);
+			println!("{}", eui64);
+			println!("{}", hex_bytes(&addr.to_eui64()));
+			println!("{}", addr.to_link_local_ipv6());
+			return Ok(());
+		}
+		Output::Export => {
+			export(&[
+				("MAC", addr.to_string()),
+				("EUI64", eui64),
+				("MODIFIED_EUI64", hex_bytes(&addr.to_eui64())),
+				("LINK_LOCAL", addr.to_link_local_ipv6().to_string()),
+			]);
+			return Ok(());
+		}
+	}
+	println!("MAC address:       {}", addr.to_string().green().bold());
+	println!("EUI-64:            {}", eui64);
+	println!("Modified EUI-64:   {}", hex_bytes(&addr.to_eui64()));
+	println!(
+		"IPv6 link-local:   {}",
+		addr.to_link_local_ipv6().to_string().bold()
+	);
+	Ok(())
+}
+
+/// Print the address `step` positions after a given one
+fn next(addr: MacAddr, step: i64) -> io::Result<()> {
+	let next = addr.checked_offset(step).ok_or_else(|| {
+		println!(
+			"{}",
+			format!("{} is out of range when moved by {}", addr, step).red()
+		);
+		RacError::Parse("address out of range".to_string())
+	})?;
+	match output() {
+		Output::Human => println!("Next MAC address: {}", next.to_string().green().bold()),
+		Output::Plain => println!("{}", next),
+		Output::Export => export(&[("MAC", next.to_string())]),
+	}
+	Ok(())
+}
+
+/// Print (and optionally apply) `count` consecutive addresses from `start`
+fn range(start: MacAddr, count: Option<u64>, apply: &[String], keep_going: bool) -> io::Result<()> {
+	let stdin;
+	let apply = if apply == ["-"] {
+		stdin = read_stdin_words()?;
+		&stdin
+	} else {
+		apply
+	};
+	let count = count.unwrap_or(apply.len() as u64);
+	if count < apply.len() as u64 {
+		println!(
+			"{}",
+			format!(
+				"{} addresses aren't enough for {} interfaces",
+				count,
+				apply.len()
+			)
+			.red()
+		);
+		return Err(RacError::Parse("count too small".to_string()).into());
+	}
+	// The last address has to fit as well, and no block is bigger than the
+	// address space
+	let fits = count == 0
+		|| i64::try_from(count)
+			.ok()
+			.filter(|count| *count <= 1 << 48)
+			.and_then(|count| start.checked_offset(count - 1))
+			.is_some();
+	if !fits {
+		println!(
+			"{}",
+			format!(
+				"{} addresses from {} don't fit in the address space",
+				count, start
+			)
+			.red()
+		);
+		return Err(RacError::Parse("range out of bounds".to_string()).into());
+	}
+	let addrs: Vec<_> = (0..count)
+		.filter_map(|i| start.checked_offset(i as i64))
+		.collect();
+
+	if apply.is_empty() {
+		for addr in addrs {
+			report(event("address").str("address", &addr.to_string()), addr);
+		}
+		return Ok(());
+	}
+	let changes: Vec<_> = apply
+		.iter()
+		.cloned()
+		.zip(addrs)
+		.map(|(inter, addr)| (inter, addr, restrictions::Mode::Address))
+		.collect();
+	match apply_all(&changes, keep_going)? {
+		0 => Ok(()),
+		failed => Err(io::Error::other(format!("{} interface(s) failed", failed))),
+	}
+}
+
+/// `rac pool ...`
+fn pool_cmd(config: &config::Config, command: PoolCmds) -> io::Result<()> {
+	let state_dir = &config.general.state_dir;
+	match command {
+		PoolCmds::Create { name, prefix } => {
+			pool::Pool::create(state_dir, &name, &prefix)?;
+			note(format!("Created pool '{}'", name.bold()));
+		}
+		PoolCmds::Allocate { name, label } => {
+			let mut pool = pool::Pool::open(state_dir, &name)?;
+			let addr = pool.allocate(label)?;
+			pool.save()?;
+			match output() {
+				Output::Human => println!(
+					"Allocated {} from pool '{}'",
+					addr.to_string().green().bold(),
+					name
+				),
+				Output::Plain => println!("{}", addr),
+				Output::Export => export(&[("POOL", name), ("MAC", addr.to_string())]),
+			}
+		}
+		PoolCmds::Release {
+			name,
+			address: addr,
+		} => {
+			let mut pool = pool::Pool::open(state_dir, &name)?;
+			if !pool.release(addr) {
+				return Err(io::Error::new(
+					io::ErrorKind::NotFound,
+					format!("{} isn't allocated from pool '{}'", addr, name),
+				));
+			}
+			pool.save()?;
+			note(format!("Released {} to pool '{}'", addr, name));
+		}
+		PoolCmds::List { name: None } => {
+			for name in pool::names(state_dir)? {
+				println!("{}", name);
+			}
+		}
+		PoolCmds::List { name: Some(name) } => {
+			let pool = pool::Pool::open(state_dir, &name)?;
+			match output() {
+				Output::Human => {
+					println!("Prefix: {}", hex_bytes(&pool.prefix).bold());
+					for (addr, label) in &pool.allocations {
+						println!("{}  {}", addr, label.as_deref().unwrap_or("-"));
+					}
+				}
+				Output::Plain => {
+					for (addr, _) in &pool.allocations {
+						println!("{}", addr);
+					}
+				}
+				Output::Export => export(&[
+					("POOL", name),
+					("PREFIX", hex_bytes(&pool.prefix)),
+					(
+						"MACS",
+						pool.allocations
+							.iter()
+							.map(|(addr, _)| addr.to_string())
+							.collect::<Vec<_>>()
+							.join(" "),
+					),
+				]),
+			}
+		}
+	}
+	Ok(())
+}
+
+/// `rac vm`: an address for a virtual machine's NIC, written to its libvirt
+/// domain or printed as a QEMU argument
+#[allow(clippy::too_many_arguments)]
+fn vm(
+	config: &config::Config,
+	domain: Option<&str>,
+	nic: usize,
+	oui: Option<&str>,
+	address: Option<MacAddr>,
+	pool: Option<&str>,
+	qemu: Option<&str>,
+) -> io::Result<()> {
+	let mut pool = pool
+		.map(|name| pool::Pool::open(&config.general.state_dir, name))
+		.transpose()?;
+	let addr = match (address, pool.as_mut()) {
+		(Some(addr), _) => addr,
+		(None, Some(pool)) => pool.allocate(domain.map(|domain| format!("{}/{}", domain, nic)))?,
+		(None, None) => {
+			config.generate(random_policy(config, oui, None, None, None)?, None, None)?
+		}
+	};
+	if addr.is_multicast() || addr.is_null() {
+		return Err(RacError::Unusable(format!("{} can't be a NIC's address", addr)).into());
+	}
+
+	let Some(domain) = domain else {
+		let arg = libvirt::qemu_arg(qemu.unwrap_or("virtio-net-pci"), nic, addr);
+		if let Some(pool) = pool.as_mut() {
+			pool.save()?;
+		}
+		println!("{}", arg);
+		return Ok(());
+	};
+	let (xml, old) = libvirt::definition(domain)
+		.map_err(io::Error::from)
+		.and_then(|xml| libvirt::set_mac(&xml, nic, addr).map_err(|e| RacError::Parse(e).into()))
+		.inspect_err(|e| println!("Failed to read the definition of {}: {}", domain, e))?;
+	libvirt::define(&xml).inspect_err(|e| println!("Failed to define {}: {}", domain, e))?;
+	// Only once the domain has it, so a failure leaves the address free
+	if let Some(pool) = pool.as_mut() {
+		pool.save()?;
+	}
+	match output() {
+		Output::Human => {
+			println!(
+				"{} NIC {}: {} → {}",
+				domain.bold(),
+				nic,
+				old.map_or("none".to_string(), |old| old.to_string()),
+				addr.to_string().green().bold()
+			);
+			if libvirt::is_running(domain) {
+				note(
+					format!(
+						"{} is running, the address applies from its next start",
+						domain
+					)
+					.yellow(),
+				);
+			}
+		}
+		Output::Plain => println!("{}", addr),
+		Output::Export => export(&[
+			("DOMAIN", domain.to_string()),
+			("NIC", nic.to_string()),
+			("MAC", addr.to_string()),
+		]),
+	}
+	Ok(())
+}
+
+/// `rac network ...`
+fn network_cmd(config: &config::Config, command: NetworkCmds) -> io::Result<()> {
+	let mut networks = networks::Networks::open(&config.general.state_dir)
+		.inspect_err(|e| println!("Failed to open the network assignments: {}", e))?;
+	match command {
+		NetworkCmds::List { json: true } => println!(
+			"{}",
+			json::array(networks.assignments.iter().map(|assignment| {
+				json::Object::new()
+					.str("interface", &assignment.inter)
+					.str("network", &assignment.network)
+					.str("address", &assignment.addr.to_string())
+					.finish()
+			}))
+		),
+		NetworkCmds::List { json: false } => {
+			let width = networks
+				.assignments
+				.iter()
+				.map(|assignment| assignment.inter.len())
+				.max()
+				.unwrap_or(0)
+				.max(9);
+			if output() == Output::Human {
+				println!(
+					"{}",
+					format!("{:<w$}  {:<17}  NETWORK", "INTERFACE", "ADDRESS", w = width).bold()
+				);
+			}
+			for assignment in &networks.assignments {
+				println!(
+					"{:<w$}  {}  {}",
+					assignment.inter,
+					assignment.addr,
+					assignment.network,
+					w = width
+				);
+			}
+		}
+		NetworkCmds::Assign {
+			interface,
+			address,
+			network,
+		} => {
+			let network = match network {
+				Some(network) => network,
+				None => networks::identify(&interface)?.ok_or_else(|| {
+					io::Error::new(
+						io::ErrorKind::NotFound,
+						format!(
+							"{} isn't connected to a network (or has no default route), pass \
+							 --network",
+							interface
+						),
+					)
+				})?,
+			};
+			let addr = match address {
+				Some(addr) => addr,
+				None => link_of(&interface)?
+					.and_then(|link| link.mac())
+					.ok_or_else(|| RacError::NoInterface(interface.clone()))?,
+			};
+			networks.assign(&interface, &network, addr);
+			networks.save()?;
+			println!(
+				"Assigned {} to {} on {}",
+				addr.to_string().green().bold(),
+				interface,
+				network
+			);
+		}
+		NetworkCmds::Forget { network, interface } => {
+			match networks.forget(interface.as_deref(), &network) {
+				0 => {
+					return Err(io::Error::new(
+						io::ErrorKind::NotFound,
+						format!("no address is assigned to {}", network),
+					))
+				}
+				forgotten => {
+					networks.save()?;
+					println!("Forgot {} address(es) for {}", forgotten, network);
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Show the driver name, version, firmware and bus of an interface
+fn driver(inter: &str, json: bool) -> io::Result<()> {
+	if !Interfaces::load()?.contains(inter) {
+		return Err(RacError::NoInterface(inter.to_string()).into());
+	}
+	let info = ethtool::driver_info(inter)?
+		.ok_or_else(|| RacError::Backend(format!("{} doesn't report its driver", inter)))?;
+	// Drivers leave fields they don't have empty
+	let or_dash = |value: &str| {
+		if value.is_empty() {
+			"-".to_string()
+		} else {
+			value.to_string()
+		}
+	};
+
+	if json {
+		fn opt(value: &str) -> Option<&str> { Some(value).filter(|value| !value.is_empty()) }
+		println!(
+			"{}",
+			json::Object::new()
+				.str("interface", inter)
+				.str("driver", &info.driver)
+				.opt_str("version", opt(&info.version))
+				.opt_str("firmware", opt(&info.fw_version))
+				.opt_str("bus", opt(&info.bus_info))
+				.finish()
+		);
+		return Ok(());
+	}
+	match output() {
+		Output::Human => {
+			println!("Driver:   {}", info.driver.bold());
+			println!("Version:  {}", or_dash(&info.version));
+			println!("Firmware: {}", or_dash(&info.fw_version));
+			println!("Bus:      {}", or_dash(&info.bus_info));
+		}
+		Output::Plain => {
+			for value in [
+				&info.driver,
+				&info.version,
+				&info.fw_version,
+				&info.bus_info,
+			] {
+				println!("{}", or_dash(value));
+			}
+		}
+		Output::Export => export(&[
+			("INTERFACE", inter.to_string()),
+			("DRIVER", info.driver),
+			("DRIVER_VERSION", info.version),
+			("FIRMWARE", info.fw_version),
+			("BUS", info.bus_info),
+		]),
+	}
+	Ok(())
+}
+
+/// `rac ctl ...`
+fn ctl_cmd(config: &config::Config, command: CtlCmds) -> io::Result<()> {
+	let request = match &command {
+		CtlCmds::Status => json::Object::new().str("command", "status"),
+		CtlCmds::Rotate { interface } => json::Object::new()
+			.str("command", "rotate")
+			.str("interface", interface),
+		CtlCmds::Restore { interface } => json::Object::new()
+			.str("command", "restore")
+			.str("interface", interface),
+	};
+	// The address before, since the daemon only replies with the new one
+	let old = match &command {
+		CtlCmds::Rotate { interface } | CtlCmds::Restore { interface } => {
+			link_of(interface)?.and_then(|link| link.mac())
+		}
+		CtlCmds::Status => None,
+	};
+	let reply = daemon::request(config, &request.finish())?;
+	let field = |key: &str| {
+		reply
+			.iter()
+			.find(|(k, _)| k == key)
+			.map(|(_, value)| value.clone())
+			.unwrap_or_default()
+	};
+
+	let origin = matches!(command, CtlCmds::Restore { .. }).then_some("permanent");
+	match command {
+		CtlCmds::Status => match output() {
+			Output::Export => export(&[
+				("PID", field("pid")),
+				("UPTIME", field("uptime")),
+				("POLICY", field("policy")),
+				("INTERFACES", field("interfaces")),
+			]),
+			_ => {
+				for key in ["pid", "uptime", "policy", "hotplug", "dbus", "interfaces"] {
+					println!("{:<12}{}", format!("{}:", key), field(key));
+				}
+			}
+		},
+		CtlCmds::Rotate { interface } | CtlCmds::Restore { interface } => {
+			let addr = MacAddr::from_str(&field("address")).map_err(|e| {
+				RacError::Backend(format!("invalid address in reply from the daemon: {}", e))
+			})?;
+			let changed = Changed {
+				old,
+				downtime: None,
+				unrecorded: None,
+			};
+			report_set(&interface, addr, &changed, origin);
+		}
+	}
+	Ok(())
+}
+
+/// Classify an address, failing unless it's usable as an interface address
+fn check(input: &str) -> io::Result<()> {
+	let addr = MacAddr::from_str(input)
+		.map_err(|e| RacError::Parse(format!("invalid MAC address '{}': {}", input, e)))?;
+	let kind = if addr.is_null() {
+		"null"
+	} else if addr.is_broadcast() {
+		"broadcast"
+	} else if addr.is_multicast() {
+		"multicast"
+	} else {
+		"unicast"
+	};
+	let administration = if addr.is_local() {
+		"local"
+	} else {
+		"universal"
+	};
+
+	match output() {
+		Output::Human => println!(
+			"{}: {}, {}ly administered",
+			addr.to_string().bold(),
+			if kind == "unicast" {
+				kind.green()
+			} else {
+				kind.yellow()
+			},
+			administration
+		),
+		Output::Plain => println!("{}\n{}", kind, administration),
+		Output::Export => export(&[
+			("MAC", addr.to_string()),
+			("KIND", kind.to_string()),
+			("ADMINISTRATION", administration.to_string()),
+		]),
+	}
+	if kind != "unicast" {
+		return Err(RacError::Unusable(format!("{} is a {} address", addr, kind)).into());
+	}
+	Ok(())
+}
+
+/// Well-known address ranges (first, last, what they're for)
+const RESERVED: &[([u8; 6], [u8; 6], &str)] = &[
+	(
+		[0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+		[0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+		"null address",
+	),
+	(
+		[0x00, 0x00, 0x5e, 0x00, 0x01, 0x00],
+		[0x00, 0x00, 0x5e, 0x00, 0x01, 0xff],
+		"VRRP IPv4 virtual router (RFC 5798)",
+	),
+	(
+		[0x00, 0x00, 0x5e, 0x00, 0x02, 0x00],
+		[0x00, 0x00, 0x5e, 0x00, 0x02, 0xff],
+		"VRRP IPv6 virtual router (RFC 5798)",
+	),
+	(
+		[0x00, 0x00, 0x5e, 0x00, 0x53, 0x00],
+		[0x00, 0x00, 0x5e, 0x00, 0x53, 0xff],
+		"documentation, unicast (RFC 7042)",
+	),
+	(
+		[0x01, 0x00, 0x0c, 0xcc, 0xcc, 0xcc],
+		[0x01, 0x00, 0x0c, 0xcc, 0xcc, 0xcd],
+		"Cisco CDP/VTP/PVST+",
+	),
+	(
+		[0x01, 0x00, 0x5e, 0x00, 0x00, 0x00],
+		[0x01, 0x00, 0x5e, 0x7f, 0xff, 0xff],
+		"IPv4 multicast (RFC 1112)",
+	),
+	(
+		[0x01, 0x00, 0x5e, 0x90, 0x10, 0x00],
+		[0x01, 0x00, 0x5e, 0x90, 0x10, 0xff],
+		"documentation, multicast (RFC 7042)",
+	),
+	(
+		[0x01, 0x80, 0xc2, 0x00, 0x00, 0x00],
+		[0x01, 0x80, 0xc2, 0x00, 0x00, 0x0f],
+		"IEEE 802.1 link-local protocols (STP, LACP, LLDP, ...)",
+	),
+	(
+		[0x33, 0x33, 0x00, 0x00, 0x00, 0x00],
+		[0x33, 0x33, 0xff, 0xff, 0xff, 0xff],
+		"IPv6 multicast (RFC 2464)",
+	),
+	(
+		[0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+		[0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+		"broadcast",
+	),
+];
+
+/// Print an address large enough to read from afar, or as a QR code
+fn show(addr: MacAddr, qr: bool) {
+	let text = addr.to_string();
+	let lines = if qr {
+		show::qr(&text).expect("an address always fits")
+	} else {
+		show::banner(&text)
+	};
+	for line in lines {
+		println!("{}", line);
+	}
+}
+
+/// Print a breakdown of every field of an address
+fn explain(config: &config::Config, addr: MacAddr) {
+	let b = addr.bytes;
+	let oui = hex_bytes(&b[..3]);
+	let nic = hex_bytes(&b[3..]);
+	// Group addresses are assigned under the same OUI, with the I/G bit set
+	let vendor = if addr.is_local() {
+		None
+	} else {
+		let db = oui::Database::load(config.general.oui_db.as_deref());
+		db.lookup(MacAddr::new([b[0] & !0x01, b[1], b[2], b[3], b[4], b[5]]))
+			.map(str::to_string)
+	};
+	let ig = if addr.is_multicast() {
+		"1 (group, multicast)"
+	} else {
+		"0 (individual, unicast)"
+	};
+	let ul = if addr.is_local() {
+		"1 (locally administered)"
+	} else {
+		"0 (universally administered)"
+	};
+	let eui64 = hex_bytes(&[b[0], b[1], b[2], 0xff, 0xfe, b[3], b[4], b[5]]);
+	let modified = hex_bytes(&addr.to_eui64());
+	let reserved = RESERVED
+		.iter()
+		.find(|(first, last, _)| (*first..=*last).contains(&b))
+		.map(|(.., what)| *what);
+
+	match output() {
+		Output::Human => {
+			println!("Address:          {}", addr.to_string().green().bold());
+			println!(
+				"OUI:              {} ({})",
+				oui.bold(),
+				match (&vendor, addr.is_local()) {
+					(_, true) => "none, locally administered",
+					(Some(vendor), _) => vendor,
+					(None, _) => "unknown vendor",
+				}
+			);
+			println!("NIC-specific:     {}", nic);
+			println!("I/G bit:          {}", ig);
+			println!("U/L bit:          {}", ul);
+			println!("EUI-64:           {}", eui64);
+			println!("Modified EUI-64:  {}", modified);
+			if let Some(reserved) = reserved {
+				println!("Reserved range:   {}", reserved.yellow());
+			}
+		}
+		Output::Plain => {
+			for value in [&addr.to_string(), &oui, &nic, &eui64, &modified] {
+				println!("{}", value);
+			}
+		}
+		Output::Export => {
+			let mut vars = vec![
+				("MAC", addr.to_string()),
+				("OUI", oui),
+				("NIC", nic),
+				("MULTICAST", (addr.is_multicast() as u8).to_string()),
+				("LOCAL", (addr.is_local() as u8).to_string()),
+				("EUI64", eui64),
+				("MODIFIED_EUI64", modified),
+			];
+			vars.extend(vendor.map(|vendor| ("VENDOR", vendor)));
+			vars.extend(reserved.map(|reserved| ("RESERVED", reserved.to_string())));
+			export(&vars);
+		}
+	}
+}
+
+/// Print every vendor prefix whose vendor name matches a query
+fn vendors(config: &config::Config, query: &str) -> io::Result<()> {
+	let db = oui::Database::load(config.general.oui_db.as_deref());
+	let matches = db.search(query);
+	if matches.is_empty() {
+		println!("{}", format!("No vendors matching '{}'", query).red());
+		return Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			"no matching vendors",
+		));
+	}
+	for (prefix, name) in matches {
+		println!("{}  {}", hex_bytes(&prefix).green(), name);
+	}
+	Ok(())
 }
 
-#[derive(Debug, Subcommand)]
-enum SubCmds {
-	Set {
-		/// New MAC address to use
-		#[clap(short, long)]
-		address: Option<String>,
+/// Whether a current address differs from the permanent one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpoofState {
+	/// No permanent address to compare against
+	Unknown,
+	Original,
+	Spoofed,
+}
 
-		/// Interface to use (name)
-		#[clap(short, long)]
-		interface: Option<String>,
+impl SpoofState {
+	fn of(current: &HwAddr, permanent: Option<MacAddr>) -> SpoofState {
+		match permanent {
+			None => SpoofState::Unknown,
+			Some(permanent) if current.mac() == Some(permanent) => SpoofState::Original,
+			Some(_) => SpoofState::Spoofed,
+		}
+	}
 
-		/// Use a random MAC address
-		#[clap(short, long)]
-		random: bool,
-	},
+	fn as_str(&self) -> &'static str {
+		match self {
+			SpoofState::Unknown => "unknown",
+			SpoofState::Original => "original",
+			SpoofState::Spoofed => "spoofed",
+		}
+	}
 }
 
-enum MacParseError {
-	/// Parsing of the MAC address contained an invalid digit.
-	InvalidDigit,
-	/// The MAC address did not have the correct length.
-	InvalidLength,
+/// Put back the hostname saved by `--randomize-hostname`, if any
+fn reset_hostname(config: &config::Config, all: bool) -> io::Result<()> {
+	match hostname::restore(&config.general.state_dir)
+		.inspect_err(|e| println!("Failed to restore the hostname: {}", e))?
+	{
+		Some(name) => note(format!("Restored hostname {}", name.green().bold())),
+		None if !all => note("The hostname was never randomized, nothing to restore".yellow()),
+		None => {}
+	}
+	Ok(())
 }
 
-impl std::fmt::Display for MacParseError {
-	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		f.write_str(match *self {
-			MacParseError::InvalidDigit => "invalid digit",
-			MacParseError::InvalidLength => "invalid length",
-		})
+/// Put back the permanent address of one interface, or of every spoofed one
+/// with `all`, and with `hostname` (or `all`) the original hostname. Unlike
+/// `randomize-all`, a failure doesn't undo the other resets: getting as many
+/// real addresses back as possible is the point.
+fn reset(
+	config: &config::Config,
+	inter: Option<String>,
+	all: bool,
+	hostname: bool,
+) -> io::Result<()> {
+	if hostname || all {
+		reset_hostname(config, all)?;
+	}
+	if inter.is_none() && !all {
+		return Ok(());
+	}
+	let links = Interfaces::load()?;
+	if let Some(inter) = &inter {
+		if !links.iter().any(|link| &link.name == inter) {
+			return Err(RacError::NoInterface(inter.clone()).into());
+		}
+	}
+	let (mut original, mut unknown) = (0, 0);
+	let mut changes = Vec::new();
+	let mut names = Vec::new();
+	for link in links {
+		if link.is_loopback() || inter.as_ref().is_some_and(|inter| *inter != link.name) {
+			continue;
+		}
+		names.push(link.name.clone());
+		let Some(current) = link.address else {
+			continue;
+		};
+		let permanent = ethtool::permanent_addr(&link.name).unwrap_or(None);
+		match (SpoofState::of(&current, permanent), permanent) {
+			(SpoofState::Spoofed, Some(permanent)) => {
+				changes.push((link.name, permanent, restrictions::Mode::Restore))
+			}
+			(SpoofState::Original, _) => {
+				note(format!("{} already has its permanent address", link.name));
+				original += 1;
+			}
+			_ => {
+				note(format!("Skipping {}, it has no permanent address", link.name).yellow());
+				unknown += 1;
+			}
+		}
+	}
+
+	let failed = apply_all(&changes, true)?;
+	// With its permanent address back, NetworkManager can have it again
+	for name in names {
+		match nm::restore(&name) {
+			Ok(true) => note(format!("NetworkManager manages {} again", name)),
+			Ok(false) => {}
+			Err(e) => {
+				note(format!("Couldn't hand {} back to NetworkManager: {}", name, e).yellow())
+			}
+		}
+	}
+	note(format!(
+		"{} reset, {} already original, {} without a permanent address, {} failed",
+		(changes.len() - failed).to_string().green().bold(),
+		original,
+		unknown.to_string().yellow(),
+		failed.to_string().red()
+	));
+	if failed > 0 {
+		return Err(io::Error::other(format!("{} interface(s) failed", failed)));
+	}
+	match inter {
+		Some(inter) if unknown > 0 => {
+			Err(RacError::Backend(format!("{} has no permanent address", inter)).into())
+		}
+		_ => Ok(()),
 	}
 }
 
-struct MacAddr {
-	bytes: [u8; 6],
+/// Every interface with its current and permanent address
+fn addresses() -> io::Result<Vec<(String, HwAddr, Option<MacAddr>)>> {
+	let links = Interfaces::load()?;
+	let mut rows = Vec::new();
+	for link in links.into_iter().filter(|link| !link.is_loopback()) {
+		if let Some(current) = link.address {
+			let permanent = ethtool::permanent_addr(&link.name).unwrap_or(None);
+			rows.push((link.name, current, permanent));
+		}
+	}
+	Ok(rows)
 }
 
-impl MacAddr {
-	fn new(bytes: [u8; 6]) -> MacAddr { MacAddr { bytes } }
+/// Show only the interfaces whose address differs from the permanent one
+fn diff(json: bool) -> io::Result<()> {
+	let rows: Vec<_> = addresses()?
+		.into_iter()
+		.filter_map(
+			|(name, current, permanent)| match SpoofState::of(&current, permanent) {
+				SpoofState::Spoofed => Some((name, permanent?, current)),
+				_ => None,
+			},
+		)
+		.collect();
+
+	if json {
+		println!(
+			"{}",
+			json::array(rows.iter().map(|(name, permanent, current)| {
+				json::Object::new()
+					.str("interface", name)
+					.str("permanent", &permanent.to_string())
+					.str("current", &current.to_string())
+					.finish()
+			}))
+		);
+		return Ok(());
+	}
+	if rows.is_empty() {
+		note("Every interface has its permanent address (or none to compare with)".green());
+		return Ok(());
+	}
+
+	let width = rows
+		.iter()
+		.map(|(name, ..)| name.len())
+		.max()
+		.unwrap_or(0)
+		.max(9);
+	println!(
+		"{:<width$}  {:<17}  CURRENT",
+		"INTERFACE",
+		"PERMANENT",
+		width = width
+	);
+	for (name, permanent, current) in rows {
+		println!(
+			"{:<width$}  {}  {}",
+			name,
+			permanent,
+			current.to_string().yellow().bold(),
+			width = width
+		);
+	}
+	Ok(())
 }
 
-impl std::fmt::Display for MacAddr {
-	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-		write!(
-			f,
-			"{:<02X}:{:<02X}:{:<02X}:{:<02X}:{:<02X}:{:<02X}",
-			self.bytes[0],
-			self.bytes[1],
-			self.bytes[2],
-			self.bytes[3],
-			self.bytes[4],
-			self.bytes[5]
-		)?;
-		Ok(())
+/// Show, for every interface, the current and permanent address
+fn status(json: bool) -> io::Result<()> {
+	let rows = addresses()?;
+
+	if json {
+		println!(
+			"{}",
+			json::array(rows.iter().map(|(name, current, permanent)| {
+				json::Object::new()
+					.str("interface", name)
+					.str("current", &current.to_string())
+					.opt_str("permanent", permanent.map(|a| a.to_string()).as_deref())
+					.str("status", SpoofState::of(current, *permanent).as_str())
+					.finish()
+			}))
+		);
+		return Ok(());
+	}
+
+	let width = rows
+		.iter()
+		.map(|(name, ..)| name.len())
+		.max()
+		.unwrap_or(0)
+		.max(9);
+	println!(
+		"{:<width$}  {:<17}  {:<17}  STATUS",
+		"INTERFACE",
+		"CURRENT",
+		"PERMANENT",
+		width = width
+	);
+	for (name, current, permanent) in rows {
+		let status = match SpoofState::of(&current, permanent) {
+			SpoofState::Spoofed => "spoofed".yellow().bold(),
+			SpoofState::Original => "original".green(),
+			SpoofState::Unknown => "unknown".normal(),
+		};
+		println!(
+			"{:<width$}  {}  {:<17}  {}",
+			name,
+			current,
+			permanent.map_or("-".to_string(), |a| a.to_string()),
+			status,
+			width = width
+		);
 	}
+	Ok(())
 }
 
-impl std::str::FromStr for MacAddr {
-	type Err = MacParseError;
+/// `rac neigh`
+fn neighbors(
+	config: &config::Config,
+	inter: Option<&str>,
+	sweep: bool,
+	json: bool,
+) -> io::Result<()> {
+	if let Some(inter) = inter {
+		if link_of(inter)?.is_none() {
+			return Err(RacError::NoInterface(inter.to_string()).into());
+		}
+	}
+	if sweep {
+		let (swept, skipped) =
+			neigh::sweep(inter).inspect_err(|e| println!("Failed to sweep: {}", e))?;
+		for subnet in skipped {
+			note(
+				format!(
+					"Not sweeping {}, only subnets of /{} or smaller are",
+					subnet,
+					neigh::MIN_SWEEP_PREFIX
+				)
+				.yellow(),
+			);
+		}
+		if swept.is_empty() {
+			note("No IPv4 subnet to sweep".yellow());
+		}
+	}
+	let mut rows = neigh::table(inter)
+		.inspect_err(|e| println!("Failed to read the neighbor table: {}", e))?;
+	rows.sort_by(|a, b| (&a.inter, a.ip).cmp(&(&b.inter, b.ip)));
+	let db = oui::Database::load(config.general.oui_db.as_deref());
 
-	fn from_str(input: &str) -> Result<Self, Self::Err> {
-		let mut array = [0u8; 6];
+	if json {
+		println!(
+			"{}",
+			json::array(rows.iter().map(|row| {
+				json::Object::new()
+					.str("address", &row.ip.to_string())
+					.str("mac", &row.mac.to_string())
+					.opt_str("vendor", db.lookup(row.mac))
+					.str("interface", &row.inter)
+					.str("state", &row.state.to_lowercase())
+					.finish()
+			}))
+		);
+		return Ok(());
+	}
+	if rows.is_empty() {
+		note("No neighbors seen yet (--sweep looks for them)".yellow());
+		return Ok(());
+	}
+	let width = rows
+		.iter()
+		.map(|row| row.ip.to_string().len())
+		.max()
+		.unwrap_or(0)
+		.max(7);
+	let inter_width = rows
+		.iter()
+		.map(|row| row.inter.len())
+		.max()
+		.unwrap_or(0)
+		.max(9);
+	println!(
+		"{:<width$}  {:<17}  {:<inter_width$}  {:<10}  VENDOR",
+		"ADDRESS",
+		"MAC",
+		"INTERFACE",
+		"STATE",
+		width = width,
+		inter_width = inter_width
+	);
+	for row in rows {
+		println!(
+			"{:<width$}  {}  {:<inter_width$}  {:<10}  {}",
+			row.ip.to_string(),
+			row.mac.to_string().bold(),
+			row.inter,
+			row.state.to_lowercase(),
+			db.lookup(row.mac).unwrap_or(if row.mac.is_local() {
+				"(locally administered)"
+			} else {
+				"-"
+			}),
+			width = width,
+			inter_width = inter_width
+		);
+	}
+	Ok(())
+}
 
-		let mut nth = 0;
-		for byte in input.split(|c| c == ':' || c == '-') {
-			if nth == 6 {
-				return Err(MacParseError::InvalidLength);
+/// What an interface's address should be, and how the one it has falls
+/// short, if it does
+fn audit_interface(
+	config: &config::Config,
+	name: &str,
+	current: MacAddr,
+	permanent: Option<MacAddr>,
+) -> (String, Option<String>) {
+	let spoofed = permanent.is_some_and(|permanent| current != permanent);
+	// A change the restrictions don't allow shouldn't have been made
+	if let Err(e) = restrictions::check_interface(name) {
+		let violation = spoofed.then(|| format!("spoofed, but {}", e));
+		return ("permanent".to_string(), violation);
+	}
+	if config.is_excluded(name) {
+		return ("excluded".to_string(), None);
+	}
+	if let Some(fixed) = config.fixed_address(name) {
+		let violation = (current != fixed).then(|| format!("isn't the fixed address {}", fixed));
+		return (format!("fixed {}", fixed), violation);
+	}
+	let policy = config.policy_for(name);
+	let expected = policy.name().to_string();
+	if !spoofed && permanent.is_some() {
+		return (expected, Some("still the permanent address".to_string()));
+	}
+	if config.is_blacklisted(current) {
+		return (expected, Some("blacklisted".to_string()));
+	}
+	let violation = match policy {
+		config::Policy::Random if !current.is_local() => {
+			Some("not locally administered".to_string())
+		}
+		config::Policy::KeepOui => permanent
+			.filter(|permanent| current.bytes[..3] != permanent.bytes[..3])
+			.map(|_| "doesn't keep the vendor prefix".to_string()),
+		config::Policy::Oui(oui) if current.bytes[..3] != oui => {
+			Some(format!("doesn't start with {}", hex_bytes(&oui)))
+		}
+		config::Policy::Mimic(category) if !category.has_prefix(&current.bytes[..3]) => {
+			Some("not the prefix of a mimicked vendor".to_string())
+		}
+		config::Policy::Masked { base, mask } => base.or(permanent).and_then(|base| {
+			(0..6)
+				.any(|i| (current.bytes[i] ^ base.bytes[i]) & !mask[i] != 0)
+				.then(|| "changes bits outside the mask".to_string())
+		}),
+		config::Policy::StablePerNetwork | config::Policy::StablePerDay => {
+			match config.generate(policy, Some(name), Some(current)) {
+				Ok(stable) if stable == current => None,
+				Ok(stable) => Some(format!("isn't the stable address {}", stable)),
+				Err(e) => Some(format!("the stable address is unknown: {}", e)),
 			}
+		}
+		_ => None,
+	};
+	(expected, violation)
+}
 
-			array[nth] = u8::from_str_radix(byte, 16).map_err(|_| MacParseError::InvalidDigit)?;
+/// `rac export`
+fn export_state(
+	config: &config::Config,
+	config_path: Option<&std::path::Path>,
+	file: Option<PathBuf>,
+) -> io::Result<()> {
+	let document = backup::export(
+		&config.general.state_dir,
+		config_path.unwrap_or(config::DEFAULT_PATH.as_ref()),
+	)
+	.inspect_err(|e| println!("Failed to read the state: {}", e))?;
+	let Some(file) = file else {
+		println!("{}", document);
+		return Ok(());
+	};
+	// It has the secret of the stable addresses
+	std::fs::OpenOptions::new()
+		.write(true)
+		.create(true)
+		.truncate(true)
+		.mode(0o600)
+		.open(&file)
+		.and_then(|mut out| writeln!(out, "{}", document))
+		.inspect_err(|e| println!("Failed to write {}: {}", file.display(), e))?;
+	note(format!("Exported the state to {}", file.display()));
+	Ok(())
+}
 
-			nth += 1;
+/// `rac import`, which may bring the configuration file along and so doesn't
+/// need one to load
+fn import_state(
+	config_path: Option<&std::path::Path>,
+	file: Option<PathBuf>,
+	force: bool,
+) -> io::Result<()> {
+	let document = match file.filter(|file| file.as_os_str() != "-") {
+		Some(file) => std::fs::read_to_string(&file)
+			.inspect_err(|e| println!("Failed to read {}: {}", file.display(), e))?,
+		None => {
+			let mut document = String::new();
+			io::stdin().read_to_string(&mut document)?;
+			document
 		}
+	};
+	let state_dir = config::Config::load(config_path).map_or_else(
+		|_| PathBuf::from(config::DEFAULT_STATE_DIR),
+		|config| config.general.state_dir,
+	);
+	let written = backup::import(
+		&state_dir,
+		config_path.unwrap_or(config::DEFAULT_PATH.as_ref()),
+		&document,
+		force,
+	)
+	.inspect_err(|e| println!("Failed to import the state: {}", e))?;
+	for path in &written {
+		note(format!("Wrote {}", path.display()));
+	}
+	println!("Imported {} file(s)", written.len());
+	Ok(())
+}
 
-		if nth != 6 {
-			return Err(MacParseError::InvalidLength);
+/// `rac config check`: everything wrong with the configuration file, with
+/// the lines it's on
+fn config_check(path: Option<&std::path::Path>, json: bool) -> io::Result<()> {
+	let name = path.map_or(config::DEFAULT_PATH.into(), |p| p.display().to_string());
+	let text = match std::fs::read_to_string(path.unwrap_or(config::DEFAULT_PATH.as_ref())) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound && path.is_none() => {
+			note(format!("There's no {}, the defaults apply", name));
+			String::new()
 		}
+		result => result.inspect_err(|e| println!("Failed to read {}: {}", name, e))?,
+	};
+	let present: Option<Vec<_>> = Interfaces::load().ok().map(|links| {
+		links
+			.iter()
+			.map(|link| {
+				let permanent = ethtool::permanent_addr(&link.name).ok().flatten();
+				(link.name.clone(), permanent)
+			})
+			.collect()
+	});
+	let problems = config::Config::check(&text, present.as_deref());
+	let errors = problems.iter().filter(|problem| problem.error).count();
+	let severity = |error: bool| if error { "error" } else { "warning" };
 
-		Ok(MacAddr::new(array))
+	if json {
+		println!(
+			"{}",
+			json::Object::new()
+				.str("file", &name)
+				.raw("valid", &(errors == 0).to_string())
+				.raw(
+					"problems",
+					&json::array(problems.iter().map(|problem| {
+						json::Object::new()
+							.num("line", problem.line)
+							.str("severity", severity(problem.error))
+							.str("message", &problem.message)
+							.finish()
+					}))
+				)
+				.finish()
+		);
+	} else {
+		let lines: Vec<&str> = text.lines().collect();
+		for problem in &problems {
+			let label = severity(problem.error);
+			let label = if problem.error {
+				label.red().bold()
+			} else {
+				label.yellow().bold()
+			};
+			println!("{}:{}: {}: {}", name, problem.line, label, problem.message);
+			if let Some(line) = problem.line.checked_sub(1).and_then(|i| lines.get(i)) {
+				note(format!("{:>5} | {}", problem.line, line).dimmed());
+			}
+		}
+		let warnings = problems.len() - errors;
+		if problems.is_empty() {
+			note(format!("{} is valid", name).green());
+		} else {
+			note(format!(
+				"{} error(s) and {} warning(s) in {}",
+				errors, warnings, name
+			));
+		}
 	}
+	if errors > 0 {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("{} has {} error(s)", name, errors),
+		));
+	}
+	Ok(())
 }
 
-/// Check if a wireless interface exists (given the name)
-fn inter_exists(inter: &str) -> io::Result<bool> {
-	let ifiter = getifaddrs()?;
-	for interface in ifiter {
-		if interface.interface_name == inter {
-			return Ok(true);
+/// Check every interface against the configuration and restrictions,
+/// failing if one doesn't match
+fn audit(config: &config::Config, json: bool) -> io::Result<()> {
+	let rows: Vec<_> = addresses()?
+		.into_iter()
+		.filter_map(|(name, current, permanent)| {
+			let (expected, violation) = audit_interface(config, &name, current.mac()?, permanent);
+			Some((name, current, permanent, expected, violation))
+		})
+		.collect();
+	let violations = rows
+		.iter()
+		.filter(|(.., violation)| violation.is_some())
+		.count();
+
+	if json {
+		println!(
+			"{}",
+			json::array(
+				rows.iter()
+					.map(|(name, current, permanent, expected, violation)| {
+						json::Object::new()
+							.str("interface", name)
+							.str("current", &current.to_string())
+							.opt_str("permanent", permanent.map(|a| a.to_string()).as_deref())
+							.str("status", SpoofState::of(current, *permanent).as_str())
+							.str("expected", expected)
+							.opt_str("violation", violation.as_deref())
+							.finish()
+					})
+			)
+		);
+	} else {
+		let width = rows
+			.iter()
+			.map(|(name, ..)| name.len())
+			.max()
+			.unwrap_or(0)
+			.max(9);
+		let expected_width = rows
+			.iter()
+			.map(|(.., expected, _)| expected.len())
+			.max()
+			.unwrap_or(0)
+			.max(8);
+		println!(
+			"{:<width$}  {:<17}  {:<17}  {:<expected_width$}  RESULT",
+			"INTERFACE",
+			"CURRENT",
+			"PERMANENT",
+			"EXPECTED",
+			width = width,
+			expected_width = expected_width
+		);
+		for (name, current, permanent, expected, violation) in &rows {
+			println!(
+				"{:<width$}  {}  {:<17}  {:<expected_width$}  {}",
+				name,
+				current,
+				permanent.map_or("-".to_string(), |a| a.to_string()),
+				expected,
+				match violation {
+					Some(violation) => violation.red().bold(),
+					None => "ok".green(),
+				},
+				width = width,
+				expected_width = expected_width
+			);
 		}
 	}
-	Ok(false)
+	if violations > 0 {
+		return Err(io::Error::other(format!(
+			"{} interface(s) violate the policy",
+			violations
+		)));
+	}
+	Ok(())
+}
+
+/// Print a single MAC address change, as text or JSON, with the same
+/// `event` as the other events of `rac monitor`
+fn print_change(json: bool, link: &netlink::Link, old: Option<HwAddr>, new: &HwAddr) {
+	if json {
+		let name = MonitorEvent::AddrChange.name();
+		let object = if ndjson() {
+			event(name)
+		} else {
+			json::Object::new().str("event", name)
+		};
+		println!(
+			"{}",
+			object
+				.str("interface", &link.name)
+				.num("index", link.index)
+				.opt_str("old", old.map(|a| a.to_string()).as_deref())
+				.str("new", &new.to_string())
+				.finish()
+		);
+	} else {
+		println!(
+			"{}: {} -> {}",
+			link.name.bold(),
+			old.map_or("none".to_string(), |a| a.to_string()).red(),
+			new.to_string().green().bold()
+		);
+	}
 }
 
-/// Get current network info (interface, address)
-fn get_info(name: Option<&str>) -> io::Result<Option<(String, MacAddr)>> {
-	let ifiter = getifaddrs()?;
+/// `rac watch`: redraw the interface table every `interval`, like watch(1)
+fn watch(config: &config::Config, interval: Duration) -> io::Result<()> {
+	if !io::stdout().is_terminal() {
+		return Err(io::Error::other(
+			"rac watch needs a terminal, `rac monitor` prints changes as they happen",
+		));
+	}
+	let db = oui::Database::load(config.general.oui_db.as_deref());
+	// The address of every interface at the last refresh, by index
+	let mut previous: HashMap<u32, Option<HwAddr>> = HashMap::new();
+	loop {
+		let links: Vec<_> = Interfaces::load()?
+			.into_iter()
+			.filter(|link| !link.is_loopback() && link.address.is_some())
+			.collect();
+		let name_width = links
+			.iter()
+			.map(|link| link.name.len())
+			.max()
+			.unwrap_or(0)
+			.max(9);
+		// Move to the top left and clear the screen
+		let mut screen = String::from("\x1b[H\x1b[2J");
+		screen += &format!(
+			"Every {}s, changed addresses highlighted (Ctrl-C to quit)\n\n",
+			interval.as_secs()
+		);
+		screen += &format!(
+			"{:<name_width$}  {:<17}  {:<7}  VENDOR\n",
+			"INTERFACE",
+			"MAC",
+			"STATE",
+			name_width = name_width
+		);
+		for link in &links {
+			let Some(addr) = &link.address else { continue };
+			let vendor = match addr.mac() {
+				Some(mac) if mac.is_local() => "locally administered",
+				Some(mac) => db.lookup(mac).unwrap_or("unknown vendor"),
+				None => "-",
+			};
+			// Interfaces that are new since the last refresh aren't highlighted
+			let changed = previous
+				.get(&link.index)
+				.is_some_and(|old| old.as_ref() != Some(addr));
+			let mac = format!("{:<17}", addr.to_string());
+			screen += &format!(
+				"{:<name_width$}  {}  {:<7}  {}\n",
+				link.name,
+				if changed {
+					mac.black().on_yellow()
+				} else {
+					mac.green()
+				},
+				sys_value(&link.name, "operstate").unwrap_or_else(|| "unknown".to_string()),
+				vendor,
+				name_width = name_width
+			);
+		}
+		print!("{}", screen);
+		io::stdout().flush()?;
+		previous = links
+			.into_iter()
+			.map(|link| (link.index, link.address))
+			.collect();
+		std::thread::sleep(interval);
+	}
+}
 
-	for interface in ifiter {
-		if let Some(address) = interface.address {
-			if let SockAddr::Link(link) = address {
-				let bytes = link.addr();
+/// Watch rtnetlink link events and print every hardware address change
+fn monitor(json: bool, patterns: &[String], events: &[MonitorEvent]) -> io::Result<()> {
+	let json = json || ndjson();
+	let events = if events.is_empty() {
+		&[MonitorEvent::AddrChange]
+	} else {
+		events
+	};
+	let wanted = |event: MonitorEvent, link: &netlink::Link| {
+		events.contains(&event)
+			&& (patterns.is_empty()
+				|| patterns
+					.iter()
+					.any(|pattern| restrictions::matches(pattern.as_bytes(), link.name.as_bytes())))
+	};
+	// Up with a carrier, like `ip link` shows UP
+	let running = |link: &netlink::Link| link.is_up() && link.has_carrier();
+	let mut socket = netlink::Socket::new(netlink::RTMGRP_LINK).map_err(|e| {
+		println!("Failed to open netlink socket: {}", e);
+		e
+	})?;
+	// Last known address of every interface and whether it was running, by
+	// index
+	let mut known: HashMap<u32, (Option<HwAddr>, bool)> = socket
+		.links()?
+		.into_iter()
+		.map(|link| (link.index, (link.address.clone(), running(&link))))
+		.collect();
 
-				if let Some(name) = name {
-					if interface.interface_name == name {
-						return Ok(Some((name.to_string(), MacAddr { bytes })));
+	if !json {
+		let names: Vec<&str> = events
+			.iter()
+			.filter_map(|event| event.to_possible_value())
+			.map(|value| value.get_name())
+			.collect();
+		println!(
+			"{}",
+			format!("Watching for {} (Ctrl-C to stop)", names.join(", ")).yellow()
+		);
+	}
+	loop {
+		for message in socket.recv()? {
+			match message {
+				netlink::Message::NewLink(link) => {
+					let now = running(&link);
+					let (old, was) = match known.insert(link.index, (link.address.clone(), now)) {
+						Some(previous) => previous,
+						None => {
+							if wanted(MonitorEvent::Added, &link) {
+								print_link_event(json, MonitorEvent::Added, &link);
+							}
+							(None, false)
+						}
+					};
+					if let Some(new) = link
+						.address
+						.as_ref()
+						.filter(|new| old.as_ref() != Some(new))
+					{
+						if wanted(MonitorEvent::AddrChange, &link) {
+							print_change(json, &link, old, new);
+						}
+					}
+					let event = match (was, now) {
+						(false, true) => MonitorEvent::LinkUp,
+						(true, false) => MonitorEvent::LinkDown,
+						_ => continue,
+					};
+					if wanted(event, &link) {
+						print_link_event(json, event, &link);
+					}
+				}
+				netlink::Message::DelLink(link) => {
+					known.remove(&link.index);
+					if wanted(MonitorEvent::Removed, &link) {
+						print_link_event(json, MonitorEvent::Removed, &link);
 					}
-				} else if bytes.iter().any(|&x| x != 0) {
-					return Ok(Some((
-						interface.interface_name.to_string(),
-						MacAddr { bytes },
-					)));
 				}
+				_ => {}
 			}
 		}
 	}
-	Ok(None)
 }
 
-/// Generate a valid MAC address
-fn new_addr() -> MacAddr {
-	let mut addr: MacAddr = MacAddr { bytes: [0; 6] };
-	for i in 0..6 {
-		addr.bytes[i] = random::<u8>();
+/// Print an event of `rac monitor` other than an address change
+fn print_link_event(json: bool, kind: MonitorEvent, link: &netlink::Link) {
+	if json {
+		let object = if ndjson() {
+			event(kind.name())
+		} else {
+			json::Object::new().str("event", kind.name())
+		};
+		println!(
+			"{}",
+			object
+				.str("interface", &link.name)
+				.num("index", link.index)
+				.finish()
+		);
+	} else {
+		let what = match kind {
+			MonitorEvent::LinkUp => "link up".green(),
+			MonitorEvent::LinkDown => "link down".red(),
+			MonitorEvent::Added => "added".green(),
+			MonitorEvent::Removed => "removed".red(),
+			MonitorEvent::AddrChange => "changed".normal(),
+		};
+		println!("{}: {}", link.name.bold(), what);
 	}
-	addr.bytes[0] &= 0xfe; // clear multicast bit
-	addr.bytes[0] |= 0x02; // set local assignment bit (IEEE802)
-	addr
 }
 
-/// Set MAC address, given an interface name and a MAC address
-fn set_addr(inter: &str, addr: MacAddr) -> io::Result<()> {
-	// sudo ip link set [interface] down
-	Command::new("sudo")
-		.args(["ip", "link", "set", inter, "down"])
-		.output()?;
-	// sudo ip link set [interface] address [MAC address]
-	Command::new("sudo")
-		.args(["ip", "link", "set", inter, "address", &addr.to_string()])
-		.output()?;
-	// sudo ip link set [interface] up
-	Command::new("sudo")
-		.args(["ip", "link", "set", inter, "up"])
-		.output()?;
-	println!(
-		"Set MAC address ({}) to {}",
-		inter,
-		addr.to_string().green().bold()
+/// `rac set`: pick the address and interface, then apply
+fn set(config: &config::Config, select: SelectArgs, args: SetArgs) -> io::Result<()> {
+	// Generate a random MAC
+	// A policy is only resolved here, named ones come from the configuration
+	let policy = match &args.policy {
+		Some(name) => Some(config.policy(name).map_err(RacError::Parse)?),
+		None if args.random => match (args.mask, args.slap) {
+			(Some(policy), _) => Some(policy),
+			(None, Some(quadrant)) => Some(slap_policy(quadrant, args.oui.as_deref())?),
+			_ if args.unicast_global => Some(bits_policy(false, false)),
+			_ => Some(random_policy(
+				config,
+				args.oui.as_deref(),
+				args.mimic,
+				args.vendor.as_deref(),
+				args.template,
+			)?),
+		},
+		None => None,
+	};
+	// Notify the user than -r takes precedence over -a
+	if args.random && args.address.is_some() {
+		note("Using a random MAC address even though the '--address' flag was specified".yellow());
+	}
+	if policy.is_none() && args.address.is_none() {
+		// If only the interface option is passed
+		if args.interface.is_some() || args.by_mac.is_some() || args.ifindex.is_some() {
+			println!(
+				"{}",
+				"You can't just pass an interface, use -r for a random address or use -a to \
+				 specify an address"
+					.red()
+			);
+			return Err(RacError::Parse("no address given".to_string()).into());
+		}
+		return Ok(());
+	}
+
+	if let (Some(inter), Some(timeout)) = (&args.interface, args.wait_for_interface) {
+		if !Interfaces::load()?.contains(inter) {
+			note(format!(
+				"Waiting up to {:?} for {} to appear",
+				timeout, inter
+			));
+		}
+		if !Interfaces::wait_for(inter, timeout)
+			.inspect_err(|e| println!("Failed to wait for {}: {}", inter, e))?
+		{
+			return Err(RacError::NoInterface(format!("{} (waited {:?})", inter, timeout)).into());
+		}
+	}
+	let interfaces = Interfaces::load().map_err(|e| {
+		println!("Failed to get interface information: {}", e);
+		e
+	})?;
+	let inter = if let Some(inter) = args.interface {
+		// Use the interface provided, if it exists
+		if !interfaces.contains(&inter) {
+			return Err(RacError::NoInterface(inter).into());
+		}
+		inter
+	} else if let Some(addr) = args.by_mac {
+		interfaces.by_mac(addr)?
+	} else if let Some(index) = args.ifindex {
+		match interfaces.by_index(index) {
+			Some(link) => link.name.clone(),
+			None => return Err(RacError::NoInterface(format!("#{}", index)).into()),
+		}
+	}
+	// If no interface is provided, pick one
+	else {
+		match interfaces.pick(select) {
+			Some((inter, _)) => {
+				note(format!("No interface provided, using {}", inter).yellow());
+				inter
+			}
+			None => {
+				return Err(io::Error::new(
+					io::ErrorKind::NotFound,
+					"no physical interface is up, pass one with -i (or use --include-virtual)",
+				))
+			}
+		}
+	};
+	let mut inter = resolve_upper(inter, args.master);
+
+	// Only MAC addresses can be set, not e.g. 20-byte InfiniBand ones
+	let current = interfaces.address(&inter).cloned();
+	if let Some(current) = current.as_ref().filter(|current| current.mac().is_none()) {
+		println!(
+			"{} uses {}-byte hardware addresses, which can't be set to a MAC address",
+			inter.red().bold(),
+			current.len()
+		);
+		return Err(RacError::Parse(format!("{} doesn't use MAC addresses", inter)).into());
+	}
+	// Some policies depend on the interface, so the address comes after it
+	let mut addr = match (policy, args.address) {
+		(Some(policy), _) => config
+			.generate(policy, Some(&inter), current.as_ref().and_then(HwAddr::mac))
+			.inspect_err(|e| println!("Failed to generate an address: {}", e))?,
+		(None, Some(addr)) => addr,
+		(None, None) => unreachable!("checked above"),
+	};
+	if args.probe {
+		addr = probe_addr(
+			config,
+			&inter,
+			policy,
+			addr,
+			current.as_ref().and_then(HwAddr::mac),
+		)?;
+	}
+	let mode = match policy {
+		Some(policy) => policy.mode(),
+		None => restrictions::Mode::given(&inter, addr),
+	};
+
+	// Warn before a change that's likely not to stick
+	if let Some((driver, problem)) = quirks::lookup(&inter) {
+		note(
+			format!(
+				"Warning: {} (the driver of {}) is known to {}. The change will be verified; if \
+				 it doesn't stick, `rac clone --parent {}` creates a macvlan with its own address \
+				 instead",
+				driver, inter, problem, inter
+			)
+			.yellow(),
+		);
+	}
+
+	// Look up the iwd network before changing anything
+	let iwd_file = args
+		.iwd
+		.as_deref()
+		.map(iwd::network_file)
+		.transpose()
+		.inspect_err(|e| println!("{}", e.to_string().red()))?;
+	let wpa = args
+		.wpa_supplicant
+		.map(|mode| wpa::Control::open(&inter).map(|control| (control, mode)))
+		.transpose()
+		.inspect_err(|e| println!("{}", e.to_string().red()))?;
+
+	if let Some(name) = args
+		.rename
+		.as_deref()
+		.filter(|name| interfaces.contains(name))
+	{
+		println!("Interface {} already exists", name.red().bold());
+		return Err(RacError::Parse(format!("can't rename {} to {}", inter, name)).into());
+	}
+	// Before the change, the lease is still the one for the old address
+	let lease_warning = args
+		.check_lease
+		.then(|| lease::current(&inter))
+		.flatten()
+		.map(|lease| {
+			format!(
+				"{} holds a DHCP lease for {} ({}), with a new MAC address it will likely get \
+				 another IP address, and a reservation for the old one won't apply",
+				inter,
+				lease.address,
+				lease.source.display()
+			)
+		});
+	if let Some(warning) = lease_warning.as_ref().filter(|_| !args.json) {
+		note(warning.yellow());
+	}
+	if args.check {
+		// What `change_addr` would refuse, but without taking the lock
+		restrictions::check(&inter, mode)?;
+		cooldown::check(&inter, addr, history::Kind::Set)?;
+		check_privileges()?;
+		note(format!(
+			"Check passed, would set MAC address ({}) to {}",
+			inter,
+			addr.to_string().green().bold()
+		));
+		return Ok(());
+	}
+	if args.force {
+		ssh::force();
+	}
+	let how = match (args.no_down, args.no_up) {
+		(true, _) => Change::Live,
+		(_, true) => Change::LeaveDown,
+		_ => Change::Bounce,
+	};
+	if args.when_idle {
+		idle::wait(&inter, args.idle_timeout)?;
+	}
+	// Before the change, so the addresses configured as the link comes back up
+	// already follow it
+	if let Some(privacy) = args.ipv6_privacy {
+		set_ipv6_privacy(&inter, privacy)
+			.inspect_err(|e| println!("Failed to set IPv6 privacy extensions: {}", e))?;
+		note(format!(
+			"IPv6 privacy extensions are now {} for {}",
+			if privacy == Privacy::On { "on" } else { "off" },
+			inter
+		));
+	}
+	let nm_managed = nm::is_managed(&inter).unwrap_or(false);
+	match args.nm_unmanage.filter(|_| nm_managed) {
+		Some(nm::Unmanage::During) => nm::set_managed(&inter, false)
+			.inspect_err(|e| println!("Failed to unmanage {}: {}", inter, e))?,
+		Some(nm::Unmanage::Permanent) => {
+			let path = nm::unmanage_permanently(&inter)
+				.inspect_err(|e| println!("Failed to unmanage {}: {}", inter, e))?;
+			note(format!(
+				"NetworkManager no longer manages {} ({}, `rac reset` hands it back)",
+				inter,
+				path.display()
+			));
+		}
+		None => {}
+	}
+	let changed = change_addr(
+		&inter,
+		addr,
+		how,
+		history::Kind::Set,
+		mode,
+		args.rename.as_deref(),
 	);
+	// Even after a failure, NetworkManager gets the interface back
+	if nm_managed && args.nm_unmanage == Some(nm::Unmanage::During) {
+		let inter = args.rename.as_deref().unwrap_or(&inter);
+		if let Err(e) = nm::set_managed(inter, true) {
+			note(format!("Couldn't hand {} back to NetworkManager: {}", inter, e).yellow());
+		}
+	}
+	let changed = changed?;
+	let renamed = args.rename.map(|name| std::mem::replace(&mut inter, name));
+	let hostname = if args.randomize_hostname {
+		let (old, new) = hostname::randomize(&config.general.state_dir).inspect_err(|e| {
+			println!(
+				"Set the MAC address, but failed to randomize the hostname: {}",
+				e
+			)
+		})?;
+		Some((old, new))
+	} else {
+		None
+	};
+	if args.json {
+		let mut object = json::Object::new()
+			.str("interface", &inter)
+			.opt_str("old", changed.old.map(|old| old.to_string()).as_deref())
+			.str("address", &addr.to_string())
+			.raw(
+				"downtime",
+				&changed.downtime.map_or("null".to_string(), secs),
+			)
+			.raw("up", &(!args.no_up).to_string())
+			.opt_str("hostname", hostname.as_ref().map(|(_, new)| new.as_str()));
+		if args.check_lease {
+			object = object.raw(
+				"warnings",
+				&json::array(lease_warning.iter().map(|warning| json::string(warning))),
+			);
+		}
+		println!("{}", object.finish());
+	} else {
+		let origin = policy.map_or("given", |policy| policy.name());
+		report_set(&inter, addr, &changed, Some(origin));
+	}
+	if let Some(old) = renamed {
+		note(format!("Renamed {} to {}", old, inter.bold()));
+	}
+	if let Some((old, new)) = hostname {
+		note(format!(
+			"Set hostname to {} (was {}, `rac reset --hostname` puts it back)",
+			new.green().bold(),
+			old
+		));
+	}
+	if args.rotate_client_id {
+		let changed = dhcp::rotate(&inter, addr)
+			.inspect_err(|e| println!("Failed to rotate the DHCP client-id: {}", e))?;
+		if changed.is_empty() {
+			note("No DHCP client-id found to rotate".yellow());
+		}
+		for what in changed {
+			note(format!(
+				"Rotated {}, used from the next connection on",
+				what
+			));
+		}
+	}
+	if args.no_up {
+		note(format!("Left {} down", inter));
+	}
+
+	// Keep wpa_supplicant's own address handling consistent with the change
+	if let Some((control, mode)) = wpa {
+		control
+			.set_mode(mode, args.wpa_network)
+			.inspect_err(|e| println!("Failed to configure wpa_supplicant: {}", e))?;
+		match control.save() {
+			Ok(()) => note(format!("Configured wpa_supplicant ({:?})", mode)),
+			Err(_) => note(
+				"Configured wpa_supplicant, but couldn't save its configuration (is \
+				 update_config=1 set?)"
+					.yellow(),
+			),
+		}
+	}
+
+	// Keep iwd from reverting the change on the next connection
+	if let Some(path) = iwd_file {
+		iwd::pin_address(&path, addr).map_err(|e| {
+			println!("Failed to update iwd settings: {}", e);
+			e
+		})?;
+		note(format!("Pinned {} in iwd ({})", addr, path.display()));
+	} else if iwd::is_running() {
+		note(
+			"iwd is running and may revert this on reconnect, use --iwd <SSID> to pin the address"
+				.yellow(),
+		);
+	}
+	if nm_managed && args.nm_unmanage.is_none() {
+		note(
+			format!(
+				"NetworkManager manages {} and may revert this on reconnect, use --nm-unmanage to \
+				 take it out of its hands",
+				inter
+			)
+			.yellow(),
+		);
+	}
+	Ok(())
+}
+
+/// How long ago a time in seconds since the epoch was, roughly
+fn ago(time: u64) -> String {
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs();
+	match now.saturating_sub(time) {
+		secs if secs < 60 => format!("{}s", secs),
+		secs if secs < 60 * 60 => format!("{}m", secs / 60),
+		secs if secs < 24 * 60 * 60 => format!("{}h", secs / (60 * 60)),
+		secs => format!("{}d", secs / (24 * 60 * 60)),
+	}
+}
+
+/// Revert the latest change rac made to an interface that isn't undone yet
+fn undo(inter: Option<String>) -> io::Result<()> {
+	let entries = history::entries()?;
+	let Some(inter) = inter.or_else(|| entries.last().map(|entry| entry.inter.clone())) else {
+		return Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			format!(
+				"nothing to undo, no changes are recorded in {}",
+				history::path().display()
+			),
+		));
+	};
+	let Some(entry) = history::last_change(&entries, &inter) else {
+		return Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("nothing to undo on {}", inter),
+		));
+	};
+	let link = link_of(&inter)?.ok_or_else(|| RacError::NoInterface(inter.clone()))?;
+	if let Some(current) = link.mac().filter(|current| *current != entry.new) {
+		note(
+			format!(
+				"{} was changed to {} since, putting back its address from before anyway",
+				inter, current
+			)
+			.yellow(),
+		);
+	}
+	undo_addr(&link, entry.old).inspect_err(|e| println!("Failed to undo the change: {}", e))?;
+	note(format!(
+		"Undid the change to {} made {} ago",
+		entry.new,
+		ago(entry.time)
+	));
+	Ok(())
+}
+
+fn main() {
+	if let Err(e) = run() {
+		eprintln!("{} {}", "Error:".red().bold(), e);
+		process::exit(error::exit_code(&e));
+	}
+}
+
+/// Run the same command with rac on another host, through ssh
+fn remote(host: &str, command: &Option<SubCmds>) -> io::Result<()> {
+	if !matches!(
+		command,
+		Some(
+			SubCmds::List { .. }
+				| SubCmds::Status { .. }
+				| SubCmds::Diff { .. }
+				| SubCmds::Audit { .. }
+				| SubCmds::Set(_)
+				| SubCmds::Undo { .. }
+				| SubCmds::Reset { .. }
+		)
+	) {
+		println!(
+			"{}",
+			"--host only applies to list, status, diff, audit, set, undo and reset".red()
+		);
+		return Err(RacError::Parse("unsupported --host".to_string()).into());
+	}
+	// Everything but --host itself goes along
+	let mut words = vec!["rac".to_string()];
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		if arg == "--host" {
+			args.next();
+		} else if arg == "-" || arg.ends_with("=-") {
+			// The other end would read its own stdin, not what was piped
+			// in here
+			println!(
+				"{}",
+				"'-' doesn't work with --host, give the values themselves".red()
+			);
+			return Err(RacError::Parse("'-' with --host".to_string()).into());
+		} else if !arg.starts_with("--host=") {
+			words.push(dispatcher::quote(&arg));
+		}
+	}
+	let mut ssh = Command::new("ssh");
+	// A terminal on the other end too, for colors, progress and sudo's
+	// password prompt
+	if io::stdin().is_terminal() && io::stdout().is_terminal() {
+		ssh.arg("-t");
+	}
+	let status = ssh
+		.args(["--", host, &words.join(" ")])
+		.status()
+		.inspect_err(|e| println!("Failed to run ssh: {}", e))?;
+	if !status.success() {
+		// The error has been printed on the other end, only its exit code
+		// (or ssh's own 255) is left
+		process::exit(status.code().unwrap_or(1));
+	}
 	Ok(())
 }
 
-fn main() -> io::Result<()> {
+fn run() -> io::Result<()> {
 	let args = Args::parse();
+	if let Some(host) = &args.host {
+		return remote(host, &args.command);
+	}
+	// JSON results come without the notes around them, like --plain
+	let json = matches!(
+		&args.command,
+		Some(SubCmds::Set(SetArgs { json: true, .. }))
+	);
+	let output = if args.plain || json || args.output.is_some() {
+		Output::Plain
+	} else if args.export {
+		Output::Export
+	} else {
+		Output::Human
+	};
+	OUTPUT.set(output).ok();
+	let streams = matches!(
+		&args.command,
+		Some(
+			SubCmds::Monitor { .. }
+				| SubCmds::Daemon
+				| SubCmds::RandomizeAll { .. }
+				| SubCmds::Range { .. }
+				| SubCmds::Reset { .. }
+		)
+	);
+	let supported = match args.output {
+		None => true,
+		Some(Format::Ndjson) => streams,
+		Some(_) => matches!(&args.command, Some(SubCmds::List { json: false })),
+	};
+	if !supported || (args.output.is_some() && (args.current || args.random)) {
+		println!(
+			"{}",
+			"--output ndjson only applies to monitor, daemon, randomize-all, range and reset, and \
+			 table, csv and yaml only to list"
+				.red()
+		);
+		return Err(RacError::Parse("unsupported --output".to_string()).into());
+	}
+	if let Some(format) = args.output {
+		FORMAT.set(format).ok();
+	}
+	lock::set_wait(args.wait);
+	if output != Output::Human {
+		colored::control::set_override(false);
+	}
+	// The configuration is checked or imported rather than loaded
+	if let Some(SubCmds::Config {
+		command: ConfigCmds::Check { json },
+	}) = &args.command
+	{
+		return config_check(args.config.as_deref(), *json);
+	}
+	if let Some(SubCmds::Import { file, force }) = &args.command {
+		return import_state(args.config.as_deref(), file.clone(), *force);
+	}
+	let config = config::Config::load(args.config.as_deref()).map_err(|e| {
+		println!("Failed to load configuration: {}", e);
+		e
+	})?;
+	restrictions::load().inspect_err(|e| println!("Failed to load the restrictions: {}", e))?;
+	history::set_dir(config.general.state_dir.clone());
+	timeout::set(args.timeout.unwrap_or(config.general.timeout));
+	escalate::set(args.escalate.unwrap_or(config.general.escalate));
+	// --cooldown applies to every interface, over the configured ones
+	match args.cooldown {
+		Some(cooldown) => cooldown::set(cooldown, Default::default()),
+		None => cooldown::set(
+			config.general.cooldown,
+			config
+				.interfaces
+				.iter()
+				.filter_map(|(name, inter)| Some((name.clone(), inter.cooldown?)))
+				.collect(),
+		),
+	}
+	hooks::set_dir(config.general.hooks_dir.clone());
+	if let Some(post_hook) = args.post_hook.clone() {
+		hooks::set_post_hook(post_hook);
+	}
 	// Print current MAC
 	if args.current {
-		if let Some((current_inter, addr)) = get_info(None).map_err(|e| {
+		let interfaces = Interfaces::load().map_err(|e| {
 			println!("Failed to get MAC and interface info: {}", e);
 			e
-		})? {
-			println!(
-				"Your current MAC address ({}): {}",
-				current_inter,
-				addr.to_string().green().bold()
-			);
+		})?;
+		if let Some((current_inter, addr)) = interfaces.pick(args.select) {
+			// Only universally administered addresses belong to a vendor
+			let origin = match addr.mac() {
+				Some(mac) if mac.is_local() => "locally administered".to_string(),
+				Some(mac) => {
+					let db = oui::Database::load(config.general.oui_db.as_deref());
+					format!(
+						"{}, universally administered",
+						db.lookup(mac).unwrap_or("unknown vendor")
+					)
+				}
+				None => format!("{}-byte hardware address", addr.len()),
+			};
+			match output {
+				Output::Human => println!(
+					"Your current MAC address ({}): {} ({})",
+					current_inter,
+					addr.to_string().green().bold(),
+					origin
+				),
+				Output::Plain => println!("{}", addr),
+				Output::Export => {
+					export(&[("INTERFACE", current_inter), ("MAC", addr.to_string())])
+				}
+			}
 			return Ok(());
 		} else {
 			println!("{}", "No MAC address found :(".red().bold());
@@ -198,92 +4365,196 @@ fn main() -> io::Result<()> {
 	}
 	// Generate a random MAC address
 	else if args.random {
-		let addr = new_addr();
-		println!("Random MAC address: {}", addr.to_string().green().bold());
-	}
-	// Set MAC
-	else if let Some(SubCmds::Set {
-		address,
-		interface,
-		random,
-	}) = args.set
-	{
-		// If only the interface option is passed
-		if interface.is_some() && address.is_none() && !random {
-			println!("{}", "You can't just pass an interface, use -r for a random address or use -a to specify an address".red());
+		let policy = match (
+			args.unicast_global,
+			args.multicast_local,
+			args.multicast_global,
+			args.slap,
+		) {
+			(true, ..) => bits_policy(false, false),
+			(_, true, ..) => bits_policy(true, true),
+			(_, _, true, _) => bits_policy(false, true),
+			(.., Some(quadrant)) => slap_policy(quadrant, args.oui.as_deref())?,
+			_ => random_policy(
+				&config,
+				args.oui.as_deref(),
+				args.mimic,
+				args.vendor.as_deref(),
+				args.template,
+			)?,
+		};
+		let addr = config.generate(policy, None, None)?;
+		match output {
+			Output::Human => println!("Random MAC address: {}", addr.to_string().green().bold()),
+			Output::Plain => println!("{}", addr),
+			Output::Export => export(&[("MAC", addr.to_string())]),
 		}
-		// Generate and set a random MAC
-		else if random {
-			// Notify the user than -r takes precedence over -i
-			if address.is_some() {
-				println!(
-					"{}",
-					"Using a random MAC address even though the '--address' flag was specified"
-						.yellow()
-				);
-			}
-			// Generate MAC address
-			let new_addr = new_addr();
-			// Check for provided interface
-			if let Some(inter) = interface {
-				if inter_exists(&inter)? {
-					set_addr(&inter, new_addr)?;
-				} else {
-					println!("Interface doesn't exist: '{}'", inter.red().bold());
-				}
+		if args.copy {
+			let how = clipboard::copy(&addr.to_string())
+				.inspect_err(|e| println!("Failed to copy to the clipboard: {}", e))?;
+			note(format!("Copied to the clipboard ({})", how));
+		}
+	}
+	// Subcommands
+	else if let Some(mut command) = args.command {
+		resolve_aliases(&config, &mut command)?;
+		match command {
+			SubCmds::Set(set_args) => set(&config, args.select, set_args)?,
+			SubCmds::Undo { interface } => undo(interface)?,
+			SubCmds::Reset {
+				interface,
+				all,
+				hostname,
+			} => reset(&config, interface, all, hostname)?,
+			SubCmds::Clone(args) => clone(&config, args)?,
+			SubCmds::Daemon => daemon::run(args.config.as_deref(), config)?,
+			SubCmds::RandomizeAll {
+				physical,
+				keep_going,
+				policy,
+			} => randomize_all(&config, physical, keep_going, policy.as_deref())?,
+			SubCmds::List { json } => list(json, args.output)?,
+			SubCmds::Derive { address } => derive(address)?,
+			SubCmds::Next { address, step } => next(address, step)?,
+			SubCmds::Range {
+				start,
+				count,
+				apply,
+				keep_going,
+			} => range(start, count, &apply, keep_going)?,
+			SubCmds::Network { command } => network_cmd(&config, command)?,
+			SubCmds::Pool { command } => {
+				pool_cmd(&config, command).inspect_err(|e| println!("{}", e.to_string().red()))?
 			}
-			// If no valid interface is provided, fall back to the first valid one
-			else {
-				println!(
-					"{}",
-					"No interface provided, using the first valid interface".yellow()
-				);
-				if let Some((inter, _)) = get_info(None).map_err(|e| {
-					println!("Failed to get interface info: {}", e);
-					e
-				})? {
-					set_addr(&inter, new_addr)?;
-				} else {
-					unreachable!("Issue getting interface info");
+			SubCmds::Vm {
+				domain,
+				nic,
+				random: _,
+				oui,
+				address,
+				pool,
+				qemu,
+			} => vm(
+				&config,
+				domain.as_deref(),
+				nic,
+				oui.as_deref(),
+				address,
+				pool.as_deref(),
+				qemu.as_deref(),
+			)?,
+			SubCmds::Check { address } => check(&address)?,
+			SubCmds::Explain { address } => explain(&config, address),
+			SubCmds::Show { address, qr } => show(address, qr),
+			SubCmds::Vendors { query } => vendors(&config, &query)?,
+			SubCmds::CompleteVendor { start } => {
+				let db = oui::Database::load(config.general.oui_db.as_deref());
+				for name in db.complete(&start) {
+					println!("{}", name);
 				}
 			}
-		}
-		// Set a given MAC
-		else if let Some(addr) = address {
-			// Get the address
-			let addr: MacAddr = MacAddr::from_str(&addr).map_err(|e| {
-				let e = format!("Invalid MAC address: {e}");
-				println!("Not a valid MAC address: '{}'", addr.red().bold());
-				io::Error::new(io::ErrorKind::InvalidInput, e)
-			})?;
-			// Use interface provided
-			if let Some(inter) = interface {
-				// Set address if valid interface
-				if inter_exists(&inter)? {
-					set_addr(&inter, addr)?;
-				} else {
-					println!("Interface doesn't exist: '{}'", inter.red().bold());
-				}
+			SubCmds::Status { json } => status(json)?,
+			SubCmds::Diff { json } => diff(json)?,
+			SubCmds::Audit { json } => audit(&config, json)?,
+			SubCmds::Export { file } => export_state(&config, args.config.as_deref(), file)?,
+			// Handled before loading the configuration
+			SubCmds::Config { .. } | SubCmds::Import { .. } => {}
+			SubCmds::Neigh {
+				interface,
+				sweep,
+				json,
+			} => neighbors(&config, interface.as_deref(), sweep, json)?,
+			SubCmds::Monitor {
+				json,
+				interface,
+				event,
+			} => monitor(json, &interface, &event)?,
+			SubCmds::Watch { interval } => watch(&config, interval)?,
+			SubCmds::Driver { interface, json } => driver(&interface, json)?,
+			SubCmds::ScanRandom { interface, state } => scan_random(&interface, state)?,
+			SubCmds::Doctor { json } => doctor::run(json)?,
+			SubCmds::Selftest { in_netns: false } => selftest::run()?,
+			SubCmds::Selftest { in_netns: true } => selftest::in_netns()?,
+			SubCmds::InstallDispatcher { remove } => {
+				install_dispatcher(args.config.as_deref(), remove)?
 			}
-			// No interface provided
-			else {
-				println!(
-					"{}",
-					"No interface provided, using the first valid interface".yellow()
-				);
-				// Get first valid interface
-				if let Some((inter, _)) = get_info(None).map_err(|e| {
-					println!("Failed to get interface information: {}", e);
-					e
-				})? {
-					set_addr(&inter, addr)?;
-				} else {
-					unreachable!("Issue getting interface information");
-				}
+			SubCmds::InstallPolkit { remove } => install_polkit(remove)?,
+			SubCmds::Dispatch { interface, random } => dispatch(&config, &interface, random)?,
+			SubCmds::InstallEarly {
+				random,
+				target,
+				remove,
+			} => install_early(&config, args.config.as_deref(), random, target, remove)?,
+			SubCmds::Service { init, mode, dir } => {
+				write_service(args.config.as_deref(), init, mode, dir.as_deref())?
 			}
+			#[cfg(feature = "self-update")]
+			SubCmds::SelfUpdate { check, url } => self_update(check, &url)?,
+			SubCmds::Ctl { command } => ctl_cmd(&config, command)?,
 		}
 	} else {
 		unreachable!("You shouldn't be here");
 	}
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn templates() {
+		assert_eq!(
+			parse_template("02:DE:AD:xx:xx:xx"),
+			Ok(config::Policy::Masked {
+				base: Some(MacAddr::new([0x02, 0xde, 0xad, 0, 0, 0])),
+				mask: [0, 0, 0, 0xff, 0xff, 0xff],
+			})
+		);
+		// Single random nibbles, either case, and dashes
+		assert_eq!(
+			parse_template("0x-de-aD-Xf-00-1x"),
+			Ok(config::Policy::Masked {
+				base: Some(MacAddr::new([0x00, 0xde, 0xad, 0x0f, 0x00, 0x10])),
+				mask: [0x0f, 0, 0, 0xf0, 0, 0x0f],
+			})
+		);
+		for invalid in [
+			"",
+			"02:DE:AD:xx:xx",
+			"02:DE:AD:xx:xx:xx:xx",
+			"02:DE:AD:x:xx:xx",
+			"02:DE:AG:xx:xx:xx",
+			"02:DE:AD:xxx:xx:xx",
+		] {
+			assert!(parse_template(invalid).is_err(), "{}", invalid);
+		}
+	}
+
+	#[test]
+	fn masks() {
+		assert_eq!(
+			parse_mask("00:00:00:ff:ff:ff"),
+			Ok(config::Policy::Masked {
+				base: None,
+				mask: [0, 0, 0, 0xff, 0xff, 0xff],
+			})
+		);
+		assert_eq!(
+			parse_mask("0f-00-00-00-00-01"),
+			Ok(config::Policy::Masked {
+				base: None,
+				mask: [0x0f, 0, 0, 0, 0, 0x01],
+			})
+		);
+		for invalid in [
+			"",
+			"00:00:00:ff:ff",
+			"00:00:00:ff:ff:ff:ff",
+			"00:00:00:ff:ff:fg",
+			"00:00:00:ff:ff:100",
+		] {
+			assert!(parse_mask(invalid).is_err(), "{}", invalid);
+		}
+	}
+}
@@ -0,0 +1,134 @@
+//! Prometheus metrics of the daemon, served over plain HTTP
+//!
+//! Only `GET /metrics` is answered, one request per connection, which is all
+//! a Prometheus scraper needs. The daemon reads and writes the connections,
+//! this only makes up the response.
+
+use std::{
+	collections::BTreeMap,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Counters for a single interface
+#[derive(Debug, Default)]
+struct Interface {
+	rotations:     u64,
+	failures:      u64,
+	/// When the address was last changed, in seconds since the epoch
+	last_rotation: Option<f64>,
+}
+
+/// Everything the daemon has done since it started
+#[derive(Debug)]
+pub struct Metrics {
+	started:    f64,
+	interfaces: BTreeMap<String, Interface>,
+}
+
+fn now() -> f64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs_f64()
+}
+
+impl Metrics {
+	pub fn new() -> Metrics {
+		Metrics {
+			started:    now(),
+			interfaces: BTreeMap::new(),
+		}
+	}
+
+	/// Count an attempt to give an interface a new address
+	pub fn record<T, E>(&mut self, inter: &str, result: &Result<T, E>) {
+		let counters = self.interfaces.entry(inter.to_string()).or_default();
+		match result {
+			Ok(_) => {
+				counters.rotations += 1;
+				counters.last_rotation = Some(now());
+			}
+			Err(_) => counters.failures += 1,
+		}
+	}
+
+	/// The metrics in Prometheus' text format
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		let mut family = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+			out.push_str(&format!(
+				"# HELP {} {}\n# TYPE {} {}\n",
+				name, help, name, kind
+			));
+			for (labels, value) in samples {
+				out.push_str(&format!("{}{} {}\n", name, labels, value));
+			}
+		};
+		let per_interface = |value: &dyn Fn(&Interface) -> Option<String>| {
+			self.interfaces
+				.iter()
+				.filter_map(|(name, counters)| {
+					Some((
+						format!("{{interface=\"{}\"}}", escape(name)),
+						value(counters)?,
+					))
+				})
+				.collect()
+		};
+
+		family(
+			"rac_start_time_seconds",
+			"gauge",
+			"When the daemon started, in seconds since the epoch",
+			vec![(String::new(), self.started.to_string())],
+		);
+		family(
+			"rac_rotations_total",
+			"counter",
+			"Addresses set by the daemon",
+			per_interface(&|counters| Some(counters.rotations.to_string())),
+		);
+		family(
+			"rac_rotation_failures_total",
+			"counter",
+			"Failed attempts to set an address",
+			per_interface(&|counters| Some(counters.failures.to_string())),
+		);
+		family(
+			"rac_last_rotation_timestamp_seconds",
+			"gauge",
+			"When the address was last set, in seconds since the epoch",
+			per_interface(&|counters| counters.last_rotation.map(|time| time.to_string())),
+		);
+		out
+	}
+
+	/// The response to an HTTP request, of which only the first line matters
+	pub fn answer(&self, request: &str) -> Vec<u8> {
+		let (status, body) = match request.split_whitespace().collect::<Vec<_>>()[..] {
+			["GET", "/metrics", ..] => ("200 OK", self.render()),
+			_ => ("404 Not Found", "Only /metrics is served\n".to_string()),
+		};
+		format!(
+			"HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+			 {}\r\nConnection: close\r\n\r\n{}",
+			status,
+			body.len(),
+			body
+		)
+		.into_bytes()
+	}
+}
+
+/// Whether a request has arrived whole, up to the blank line after the headers
+pub fn complete(request: &[u8]) -> bool {
+	request.windows(4).any(|end| end == b"\r\n\r\n") || request.windows(2).any(|end| end == b"\n\n")
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
@@ -0,0 +1,139 @@
+//! The kernel's neighbor table: the hosts seen on the local networks, with
+//! their IP and MAC addresses (`rac neigh`)
+//!
+//! The table only has the hosts something talked to lately. A sweep fills it
+//! first: a UDP datagram to every address of each local IPv4 subnet makes the
+//! kernel resolve them all over ARP, and an IPv6 ping to every node on each
+//! link (`ff02::1`) gets the IPv6 hosts to answer.
+
+use std::{
+	io,
+	net::{IpAddr, Ipv4Addr, UdpSocket},
+	process::Command,
+	thread,
+	time::Duration,
+};
+
+use nix::{ifaddrs::getifaddrs, sys::socket::SockAddr};
+
+use crate::{timeout, MacAddr};
+
+/// Subnets with a shorter prefix than this are too large to sweep
+pub const MIN_SWEEP_PREFIX: u32 = 22;
+
+/// How long answers to a sweep get to arrive
+const SWEEP_WAIT: Duration = Duration::from_secs(1);
+
+/// An entry of the neighbor table
+pub struct Neighbor {
+	pub ip:    IpAddr,
+	pub inter: String,
+	pub mac:   MacAddr,
+	/// `REACHABLE`, `STALE`, ...
+	pub state: String,
+}
+
+/// The neighbors with a known MAC address, on one interface or all of them
+pub fn table(inter: Option<&str>) -> io::Result<Vec<Neighbor>> {
+	let output = timeout::output(Command::new("ip").args(["neigh", "show"]))?;
+	if !output.status.success() {
+		return Err(io::Error::other(format!(
+			"ip neigh failed: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		)));
+	}
+	// e.g. `fe80::1 dev wlan0 lladdr 3c:22:fb:12:34:56 router REACHABLE`
+	let neighbors = String::from_utf8_lossy(&output.stdout)
+		.lines()
+		.filter_map(|line| {
+			let words: Vec<&str> = line.split_whitespace().collect();
+			let after = |key: &str| {
+				let at = words.iter().position(|word| *word == key)?;
+				words.get(at + 1).copied()
+			};
+			Some(Neighbor {
+				ip:    words.first()?.parse().ok()?,
+				inter: after("dev")?.to_string(),
+				mac:   after("lladdr")?.parse().ok()?,
+				state: words.last()?.to_string(),
+			})
+		})
+		.filter(|neighbor| inter.is_none_or(|inter| neighbor.inter == inter))
+		.collect();
+	Ok(neighbors)
+}
+
+/// Ask every IPv6 node on a link to answer, putting them all in the neighbor
+/// table. Whether anyone answers doesn't matter.
+pub fn ping_all(inter: &str) -> io::Result<()> {
+	match timeout::output(Command::new("ping").args([
+		"-6", "-c", "2", "-i", "0.2", "-W", "1", "-I", inter, "ff02::1",
+	])) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			"ping isn't installed",
+		)),
+		result => result.map(|_| ()),
+	}
+}
+
+/// The IPv4 subnets of the interfaces that are up, with their prefix length
+fn subnets(inter: Option<&str>) -> io::Result<Vec<(String, Ipv4Addr, u32)>> {
+	Ok(getifaddrs()?
+		.filter(|ifaddr| inter.is_none_or(|inter| ifaddr.interface_name == inter))
+		.filter(|ifaddr| {
+			ifaddr.flags.contains(nix::net::if_::InterfaceFlags::IFF_UP)
+				&& !ifaddr
+					.flags
+					.contains(nix::net::if_::InterfaceFlags::IFF_LOOPBACK)
+		})
+		.filter_map(|ifaddr| {
+			let (SockAddr::Inet(addr), SockAddr::Inet(mask)) = (ifaddr.address?, ifaddr.netmask?)
+			else {
+				return None;
+			};
+			let (IpAddr::V4(addr), IpAddr::V4(mask)) = (addr.to_std().ip(), mask.to_std().ip())
+			else {
+				return None;
+			};
+			Some((ifaddr.interface_name, addr, u32::from(mask).count_ones()))
+		})
+		.collect())
+}
+
+/// Get every host on the local networks into the neighbor table, returning
+/// the interfaces swept and the subnets skipped for being too large
+pub fn sweep(inter: Option<&str>) -> io::Result<(Vec<String>, Vec<String>)> {
+	let socket = UdpSocket::bind("0.0.0.0:0")?;
+	let (mut swept, mut skipped) = (Vec::new(), Vec::new());
+	for (name, addr, prefix) in subnets(inter)? {
+		if prefix < MIN_SWEEP_PREFIX {
+			skipped.push(format!("{}/{} on {}", addr, prefix, name));
+			continue;
+		}
+		let network = u32::from(addr) & (u32::MAX << (32 - prefix));
+		let size = 1u32 << (32 - prefix);
+		// Neither the network nor the broadcast address, unless there's
+		// nothing else
+		let hosts = if size > 2 { 1..size - 1 } else { 0..size };
+		for host in hosts.map(|host| Ipv4Addr::from(network + host)) {
+			if host != addr {
+				// To the discard port: the datagram itself doesn't matter,
+				// the ARP request the kernel sends for it does
+				socket.send_to(&[], (host, 9)).ok();
+			}
+		}
+		if !swept.contains(&name) {
+			swept.push(name);
+		}
+	}
+	let links: Vec<String> = match inter {
+		Some(inter) => vec![inter.to_string()],
+		None => swept.clone(),
+	};
+	for link in &links {
+		ping_all(link).ok();
+	}
+	thread::sleep(SWEEP_WAIT);
+	Ok((swept, skipped))
+}
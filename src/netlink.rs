@@ -0,0 +1,289 @@
+//! Minimal rtnetlink (`NETLINK_ROUTE`) client, used for link dumps, to
+//! listen for link events and (in `rac selftest`) to set addresses
+
+use std::{
+	io,
+	mem::size_of,
+	os::unix::io::{AsRawFd, RawFd},
+};
+
+use nix::{
+	libc,
+	sys::socket::{
+		bind, recv, send, socket, AddressFamily, MsgFlags, SockAddr, SockFlag, SockProtocol,
+		SockType,
+	},
+	unistd::close,
+};
+
+use crate::MacAddr;
+
+/// Multicast group for link (interface) notifications
+pub const RTMGRP_LINK: u32 = 1;
+
+const NLMSG_HDRLEN: usize = size_of::<libc::nlmsghdr>();
+const IFINFOMSG_LEN: usize = 16;
+const RTA_HDRLEN: usize = 4;
+
+/// Round a length up to the 4-byte netlink alignment
+fn align(len: usize) -> usize { (len + 3) & !3 }
+
+/// A hardware address of any length: a MAC address for Ethernet and Wi-Fi,
+/// but e.g. 8 bytes for FireWire and 20 for InfiniBand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HwAddr {
+	Mac(MacAddr),
+	Other(Vec<u8>),
+}
+
+impl HwAddr {
+	pub fn from_bytes(bytes: &[u8]) -> HwAddr {
+		match <[u8; 6]>::try_from(bytes) {
+			Ok(bytes) => HwAddr::Mac(MacAddr::new(bytes)),
+			Err(_) => HwAddr::Other(bytes.to_vec()),
+		}
+	}
+
+	/// The MAC address, if this is one
+	pub fn mac(&self) -> Option<MacAddr> {
+		match self {
+			HwAddr::Mac(addr) => Some(*addr),
+			HwAddr::Other(_) => None,
+		}
+	}
+
+	// Never empty, a link without an address has no `HwAddr` at all
+	#[allow(clippy::len_without_is_empty)]
+	pub fn len(&self) -> usize {
+		match self {
+			HwAddr::Mac(addr) => addr.bytes.len(),
+			HwAddr::Other(bytes) => bytes.len(),
+		}
+	}
+
+	pub fn is_zero(&self) -> bool {
+		match self {
+			HwAddr::Mac(addr) => addr.bytes.iter().all(|&b| b == 0),
+			HwAddr::Other(bytes) => bytes.iter().all(|&b| b == 0),
+		}
+	}
+}
+
+impl std::fmt::Display for HwAddr {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			HwAddr::Mac(addr) => addr.fmt(f),
+			HwAddr::Other(bytes) => {
+				let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+				f.write_str(&hex.join(":"))
+			}
+		}
+	}
+}
+
+/// A link (interface) as reported by the kernel
+#[derive(Debug, Clone)]
+pub struct Link {
+	pub index:   u32,
+	pub name:    String,
+	/// `IFF_*` device flags
+	pub flags:   u32,
+	pub address: Option<HwAddr>,
+}
+
+impl Link {
+	pub fn is_up(&self) -> bool { self.flags & libc::IFF_UP as u32 != 0 }
+
+	pub fn is_loopback(&self) -> bool { self.flags & libc::IFF_LOOPBACK as u32 != 0 }
+
+	/// Whether the link has a carrier (cable plugged in, associated to Wi-Fi)
+	pub fn has_carrier(&self) -> bool { self.flags & libc::IFF_LOWER_UP as u32 != 0 }
+
+	/// The link's address, if it's a MAC address
+	pub fn mac(&self) -> Option<MacAddr> { self.address.as_ref().and_then(HwAddr::mac) }
+}
+
+/// A single decoded netlink message
+#[derive(Debug)]
+pub enum Message {
+	NewLink(Link),
+	DelLink(Link),
+	Done,
+	Other,
+}
+
+/// A bound `NETLINK_ROUTE` socket
+pub struct Socket {
+	fd:  RawFd,
+	seq: u32,
+}
+
+impl Socket {
+	/// Open a netlink socket, subscribed to the given multicast groups
+	pub fn new(groups: u32) -> io::Result<Socket> {
+		let fd = socket(
+			AddressFamily::Netlink,
+			SockType::Raw,
+			SockFlag::SOCK_CLOEXEC,
+			SockProtocol::NetlinkRoute,
+		)?;
+		let socket = Socket { fd, seq: 0 };
+		bind(fd, &SockAddr::new_netlink(0, groups))?;
+		Ok(socket)
+	}
+
+	/// Ask the kernel for a dump of every link on the system
+	pub fn request_links(&mut self) -> io::Result<()> {
+		self.seq += 1;
+		let len = NLMSG_HDRLEN + IFINFOMSG_LEN;
+		let mut buf = Vec::with_capacity(len);
+		buf.extend_from_slice(&(len as u32).to_ne_bytes());
+		buf.extend_from_slice(&libc::RTM_GETLINK.to_ne_bytes());
+		buf.extend_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+		buf.extend_from_slice(&self.seq.to_ne_bytes());
+		buf.extend_from_slice(&0u32.to_ne_bytes());
+		// struct ifinfomsg, all zeroes (AF_UNSPEC, any interface)
+		buf.resize(len, 0);
+		send(self.fd, &buf, MsgFlags::empty())?;
+		Ok(())
+	}
+
+	/// Set the address of a link (`RTM_SETLINK`), waiting for the kernel to
+	/// acknowledge it
+	pub fn set_address(&mut self, index: u32, addr: MacAddr) -> io::Result<()> {
+		self.seq += 1;
+		let attr_len = RTA_HDRLEN + addr.bytes.len();
+		let len = NLMSG_HDRLEN + IFINFOMSG_LEN + align(attr_len);
+		let mut buf = Vec::with_capacity(len);
+		buf.extend_from_slice(&(len as u32).to_ne_bytes());
+		buf.extend_from_slice(&libc::RTM_SETLINK.to_ne_bytes());
+		buf.extend_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16).to_ne_bytes());
+		buf.extend_from_slice(&self.seq.to_ne_bytes());
+		buf.extend_from_slice(&0u32.to_ne_bytes());
+		// struct ifinfomsg: family, padding, type, index, flags, change
+		buf.extend_from_slice(&[0; 4]);
+		buf.extend_from_slice(&index.to_ne_bytes());
+		buf.extend_from_slice(&[0; 8]);
+		buf.extend_from_slice(&(attr_len as u16).to_ne_bytes());
+		buf.extend_from_slice(&libc::IFLA_ADDRESS.to_ne_bytes());
+		buf.extend_from_slice(&addr.bytes);
+		buf.resize(len, 0);
+		send(self.fd, &buf, MsgFlags::empty())?;
+		// The acknowledgement is an error message with errno 0, which
+		// `parse_messages` turns into an error otherwise
+		self.recv()?;
+		Ok(())
+	}
+
+	/// Dump every link on the system
+	pub fn links(&mut self) -> io::Result<Vec<Link>> {
+		self.request_links()?;
+		let mut links = Vec::new();
+		loop {
+			for message in self.recv()? {
+				match message {
+					Message::NewLink(link) => links.push(link),
+					Message::Done => return Ok(links),
+					_ => {}
+				}
+			}
+		}
+	}
+
+	/// Block until the next datagram arrives and decode every message in it
+	pub fn recv(&self) -> io::Result<Vec<Message>> {
+		let mut buf = vec![0u8; 32 * 1024];
+		let len = recv(self.fd, &mut buf, MsgFlags::empty())?;
+		parse_messages(&buf[..len])
+	}
+}
+
+impl AsRawFd for Socket {
+	fn as_raw_fd(&self) -> RawFd { self.fd }
+}
+
+impl Drop for Socket {
+	fn drop(&mut self) { let _ = close(self.fd); }
+}
+
+fn u16_at(buf: &[u8], at: usize) -> u16 { u16::from_ne_bytes([buf[at], buf[at + 1]]) }
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+	u32::from_ne_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+fn parse_messages(mut buf: &[u8]) -> io::Result<Vec<Message>> {
+	let mut messages = Vec::new();
+	while buf.len() >= NLMSG_HDRLEN {
+		let len = u32_at(buf, 0) as usize;
+		let kind = u16_at(buf, 4);
+		if len < NLMSG_HDRLEN || len > buf.len() {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"truncated netlink message",
+			));
+		}
+		let payload = &buf[NLMSG_HDRLEN..len];
+		messages.push(match i32::from(kind) {
+			libc::NLMSG_DONE => Message::Done,
+			libc::NLMSG_ERROR => {
+				let errno = -(u32_at(payload, 0) as i32);
+				if errno != 0 {
+					return Err(io::Error::from_raw_os_error(errno));
+				}
+				Message::Other
+			}
+			_ if kind == libc::RTM_NEWLINK => Message::NewLink(parse_link(payload)?),
+			_ if kind == libc::RTM_DELLINK => Message::DelLink(parse_link(payload)?),
+			_ => Message::Other,
+		});
+		buf = &buf[align(len).min(buf.len())..];
+	}
+	Ok(messages)
+}
+
+fn parse_link(payload: &[u8]) -> io::Result<Link> {
+	if payload.len() < IFINFOMSG_LEN {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"truncated link message",
+		));
+	}
+	let mut link = Link {
+		index:   u32_at(payload, 4),
+		name:    String::new(),
+		flags:   u32_at(payload, 8),
+		address: None,
+	};
+	for (kind, data) in attributes(&payload[IFINFOMSG_LEN..]) {
+		match kind {
+			libc::IFLA_IFNAME => {
+				let name = data.split(|&b| b == 0).next().unwrap_or_default();
+				link.name = String::from_utf8_lossy(name).into_owned();
+			}
+			libc::IFLA_ADDRESS if !data.is_empty() => {
+				link.address = Some(HwAddr::from_bytes(data));
+			}
+			_ => {}
+		}
+	}
+	Ok(link)
+}
+
+/// Iterate over (type, payload) pairs of a run of route attributes
+fn attributes(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+	std::iter::from_fn(move || {
+		if buf.len() < RTA_HDRLEN {
+			return None;
+		}
+		let len = u16_at(buf, 0) as usize;
+		if len < RTA_HDRLEN || len > buf.len() {
+			return None;
+		}
+		// Mask out NLA_F_NESTED / NLA_F_NET_BYTEORDER
+		let kind = u16_at(buf, 2) & 0x3fff;
+		let data = &buf[RTA_HDRLEN..len];
+		buf = &buf[align(len).min(buf.len())..];
+		Some((kind, data))
+	})
+}
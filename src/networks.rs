@@ -0,0 +1,181 @@
+//! Addresses assigned per network (`rac network`, `per_network` in the daemon)
+//!
+//! `<state dir>/networks` maps each network an interface joined to the address
+//! it used there, so captive portals and paid Wi-Fi recognize the device when
+//! it comes back while other networks see other addresses. One line per
+//! assignment, with the interface, the address and the network: its SSID on
+//! Wi-Fi, the MAC address of its gateway on wired networks.
+//!
+//! ```text
+//! wlan0 02:AB:CD:EF:01:23 ssid:Airport Free Wi-Fi
+//! eth0 02:12:34:56:78:9A gateway:3C:22:FB:12:34:56
+//! ```
+
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{self, Read, Seek, SeekFrom, Write},
+	net::{IpAddr, UdpSocket},
+	os::unix::io::AsRawFd,
+	path::{Path, PathBuf},
+	process::Command,
+	thread,
+	time::Duration,
+};
+
+use nix::fcntl::{flock, FlockArg};
+
+use crate::{neigh, stable, timeout, MacAddr};
+
+/// How long the gateway gets to answer the ARP request for it
+const GATEWAY_WAIT: Duration = Duration::from_millis(200);
+
+/// An address an interface uses on a network
+#[derive(Debug, Clone)]
+pub struct Assignment {
+	pub inter:   String,
+	pub addr:    MacAddr,
+	/// `ssid:<SSID>` or `gateway:<MAC>`
+	pub network: String,
+}
+
+fn path(state_dir: &Path) -> PathBuf { state_dir.join("networks") }
+
+/// The gateway of the default route through an interface
+fn gateway(inter: &str) -> Option<IpAddr> {
+	let output =
+		timeout::output(Command::new("ip").args(["route", "show", "default", "dev", inter]))
+			.ok()?;
+	// e.g. `default via 192.168.1.1 proto dhcp metric 100`
+	let text = String::from_utf8_lossy(&output.stdout);
+	let words: Vec<&str> = text.split_whitespace().collect();
+	let at = words.iter().position(|word| *word == "via")?;
+	words.get(at + 1)?.parse().ok()
+}
+
+/// The MAC address of the gateway, from the neighbor table
+fn gateway_mac(inter: &str, gateway: IpAddr) -> io::Result<Option<MacAddr>> {
+	let find = || -> io::Result<Option<MacAddr>> {
+		Ok(neigh::table(Some(inter))?
+			.into_iter()
+			.find(|neighbor| neighbor.ip == gateway)
+			.map(|neighbor| neighbor.mac))
+	};
+	if let Some(mac) = find()? {
+		return Ok(Some(mac));
+	}
+	// Any datagram makes the kernel resolve it, see `neigh::sweep`
+	if let IpAddr::V4(gateway) = gateway {
+		UdpSocket::bind("0.0.0.0:0")?
+			.send_to(&[], (gateway, 9))
+			.ok();
+		thread::sleep(GATEWAY_WAIT);
+	}
+	find()
+}
+
+/// The network an interface is on, or `None` until it's connected (and on
+/// wired networks, has a default route)
+pub fn identify(inter: &str) -> io::Result<Option<String>> {
+	if crate::is_wireless(inter) {
+		return Ok(stable::ssid(inter).map(|ssid| format!("ssid:{}", ssid)));
+	}
+	match gateway(inter) {
+		Some(gateway) => Ok(gateway_mac(inter, gateway)?.map(|mac| format!("gateway:{}", mac))),
+		None => Ok(None),
+	}
+}
+
+/// The assignments, locked for exclusive use until dropped
+pub struct Networks {
+	pub assignments: Vec<Assignment>,
+	file:            File,
+}
+
+impl Networks {
+	/// Open and lock the assignments, creating the file on first use
+	pub fn open(state_dir: &Path) -> io::Result<Networks> {
+		let path = path(state_dir);
+		fs::create_dir_all(state_dir)?;
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(&path)?;
+		flock(file.as_raw_fd(), FlockArg::LockExclusive)?;
+
+		let mut text = String::new();
+		file.read_to_string(&mut text)?;
+		let assignments = text
+			.lines()
+			.filter(|line| !line.trim().is_empty())
+			.map(|line| {
+				let mut words = line.splitn(3, ' ');
+				let (Some(inter), Some(addr), Some(network)) =
+					(words.next(), words.next(), words.next())
+				else {
+					return Err(invalid_line(&path, line));
+				};
+				Ok(Assignment {
+					inter:   inter.to_string(),
+					addr:    addr.parse().map_err(|_| invalid_line(&path, line))?,
+					network: network.to_string(),
+				})
+			})
+			.collect::<io::Result<_>>()?;
+		Ok(Networks { assignments, file })
+	}
+
+	/// The address assigned to an interface on a network
+	pub fn get(&self, inter: &str, network: &str) -> Option<MacAddr> {
+		self.assignments
+			.iter()
+			.find(|assignment| assignment.inter == inter && assignment.network == network)
+			.map(|assignment| assignment.addr)
+	}
+
+	/// Assign an address to an interface on a network, replacing the one it
+	/// had
+	pub fn assign(&mut self, inter: &str, network: &str, addr: MacAddr) {
+		self.forget(Some(inter), network);
+		self.assignments.push(Assignment {
+			inter: inter.to_string(),
+			addr,
+			network: network.to_string(),
+		});
+	}
+
+	/// Drop the assignments for a network, of one interface or all of them,
+	/// returning how many there were
+	pub fn forget(&mut self, inter: Option<&str>, network: &str) -> usize {
+		let before = self.assignments.len();
+		self.assignments.retain(|assignment| {
+			assignment.network != network || inter.is_some_and(|inter| assignment.inter != inter)
+		});
+		before - self.assignments.len()
+	}
+
+	/// Write the assignments back to their file
+	pub fn save(&mut self) -> io::Result<()> {
+		let text: String = self
+			.assignments
+			.iter()
+			.map(|assignment| {
+				format!(
+					"{} {} {}\n",
+					assignment.inter, assignment.addr, assignment.network
+				)
+			})
+			.collect();
+		self.file.set_len(0)?;
+		self.file.seek(SeekFrom::Start(0))?;
+		self.file.write_all(text.as_bytes())
+	}
+}
+
+fn invalid_line(path: &Path, line: &str) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("{}: invalid line '{}'", path.display(), line),
+	)
+}
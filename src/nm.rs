@@ -0,0 +1,107 @@
+//! Keeping NetworkManager from reverting a change (`--nm-unmanage`)
+//!
+//! NetworkManager sets the address it's configured with (by default, the one
+//! the device had when it started managing it) every time it activates a
+//! connection, so a change made behind its back only lasts until the next
+//! reconnect. Taking the device out of its hands, for the change or for good
+//! through a file in `conf.d`, avoids that.
+
+use std::{fs, io, path::PathBuf, process::Command};
+
+use clap::ArgEnum;
+
+use crate::{escalate, timeout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Unmanage {
+	/// Only while the address changes, so NetworkManager starts over with the
+	/// new one
+	During,
+	/// Until `rac reset`, through a file in NetworkManager's conf.d
+	Permanent,
+}
+
+/// The device state NetworkManager reports for unmanaged devices
+const UNMANAGED: u32 = 10;
+
+/// Where `--nm-unmanage permanent` keeps an interface unmanaged
+fn conf_file(inter: &str) -> PathBuf {
+	PathBuf::from(format!(
+		"/etc/NetworkManager/conf.d/90-rac-unmanaged-{}.conf",
+		inter
+	))
+}
+
+/// Whether NetworkManager manages an interface (false without
+/// NetworkManager)
+pub fn is_managed(inter: &str) -> io::Result<bool> {
+	let output = match timeout::output(Command::new("nmcli").args([
+		"-g",
+		"GENERAL.STATE",
+		"device",
+		"show",
+		inter,
+	])) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+		result => result?,
+	};
+	// e.g. `100 (connected)`
+	let state = String::from_utf8_lossy(&output.stdout)
+		.split_whitespace()
+		.next()
+		.and_then(|state| state.parse::<u32>().ok());
+	Ok(output.status.success() && state.is_some_and(|state| state > UNMANAGED))
+}
+
+fn nmcli(args: &[&str]) -> io::Result<()> {
+	let status = timeout::status(escalate::command("nmcli").args(args))?;
+	if !status.success() {
+		return Err(io::Error::other(format!(
+			"`nmcli {}` failed",
+			args.join(" ")
+		)));
+	}
+	Ok(())
+}
+
+/// Hand an interface to NetworkManager, or take it back
+pub fn set_managed(inter: &str, managed: bool) -> io::Result<()> {
+	nmcli(&[
+		"device",
+		"set",
+		inter,
+		"managed",
+		if managed { "yes" } else { "no" },
+	])
+}
+
+/// Keep NetworkManager off an interface for good, returning the file that
+/// does it
+pub fn unmanage_permanently(inter: &str) -> io::Result<PathBuf> {
+	let path = conf_file(inter);
+	fs::write(
+		&path,
+		[
+			"# Written by `rac set --nm-unmanage permanent`, `rac reset` removes it",
+			"[keyfile]",
+			&format!("unmanaged-devices+=interface-name:{}", inter),
+			"",
+		]
+		.join("\n"),
+	)?;
+	set_managed(inter, false)?;
+	Ok(path)
+}
+
+/// Undo [`unmanage_permanently`], returning whether the interface was kept
+/// unmanaged
+pub fn restore(inter: &str) -> io::Result<bool> {
+	match fs::remove_file(conf_file(inter)) {
+		Ok(()) => {}
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+		Err(e) => return Err(e),
+	}
+	nmcli(&["general", "reload", "conf"])?;
+	set_managed(inter, true)?;
+	Ok(true)
+}
@@ -0,0 +1,285 @@
+//! OUI (vendor prefix) database
+//!
+//! The full IEEE registry is read from the first database found on the
+//! system (hwdata/ieee-data `oui.txt`, Wireshark's `manuf`, or nmap's
+//! `nmap-mac-prefixes`), falling back to a small built-in table of common
+//! vendors.
+
+use std::{fs, path::Path};
+
+use clap::ArgEnum;
+
+use crate::MacAddr;
+
+/// Where distributions install OUI databases
+const SYSTEM_PATHS: [&str; 6] = [
+	"/usr/share/hwdata/oui.txt",
+	"/usr/share/ieee-data/oui.txt",
+	"/usr/share/misc/oui.txt",
+	"/var/lib/ieee-data/oui.txt",
+	"/usr/share/wireshark/manuf",
+	"/usr/share/nmap/nmap-mac-prefixes",
+];
+
+/// Common vendors, used when no database is installed
+const BUILTIN: &[([u8; 3], &str)] = &[
+	([0x00, 0x00, 0x0C], "Cisco Systems, Inc"),
+	([0x00, 0x00, 0x5E], "ICANN, IANA Department"),
+	([0x00, 0x03, 0x7F], "Atheros Communications, Inc."),
+	([0x00, 0x03, 0x93], "Apple, Inc."),
+	([0x00, 0x05, 0x69], "VMware, Inc."),
+	([0x00, 0x09, 0xBF], "Nintendo Co., Ltd."),
+	([0x00, 0x0A, 0x95], "Apple, Inc."),
+	([0x00, 0x0C, 0x29], "VMware, Inc."),
+	([0x00, 0x0C, 0x6E], "ASUSTek COMPUTER INC."),
+	([0x00, 0x10, 0x18], "Broadcom"),
+	([0x00, 0x11, 0x2F], "ASUSTek COMPUTER INC."),
+	([0x00, 0x13, 0xE8], "Intel Corporate"),
+	([0x00, 0x14, 0x22], "Dell Inc."),
+	([0x00, 0x15, 0x00], "Intel Corporate"),
+	([0x00, 0x15, 0x5D], "Microsoft Corporation"),
+	([0x00, 0x15, 0x99], "Samsung Electronics Co.,Ltd"),
+	([0x00, 0x16, 0x32], "Samsung Electronics Co.,Ltd"),
+	([0x00, 0x16, 0xEA], "Intel Corporate"),
+	([0x00, 0x17, 0xAB], "Nintendo Co., Ltd."),
+	([0x00, 0x1B, 0x21], "Intel Corporate"),
+	([0x00, 0x1B, 0x63], "Apple, Inc."),
+	([0x00, 0x1C, 0x14], "VMware, Inc."),
+	([0x00, 0x1D, 0x25], "Samsung Electronics Co.,Ltd"),
+	([0x00, 0x1E, 0x4F], "Dell Inc."),
+	([0x00, 0x1E, 0xC2], "Apple, Inc."),
+	([0x00, 0x1F, 0x3B], "Intel Corporate"),
+	([0x00, 0x21, 0x19], "Samsung Electronics Co.,Ltd"),
+	([0x00, 0x21, 0x6A], "Intel Corporate"),
+	([0x00, 0x21, 0x70], "Dell Inc."),
+	([0x00, 0x24, 0xD7], "Intel Corporate"),
+	([0x00, 0x25, 0x00], "Apple, Inc."),
+	([0x00, 0x26, 0x37], "Samsung Electronics Co.,Ltd"),
+	([0x00, 0x26, 0xBB], "Apple, Inc."),
+	([0x00, 0x50, 0x56], "VMware, Inc."),
+	([0x00, 0x50, 0xF2], "Microsoft Corporation"),
+	([0x00, 0x80, 0xC2], "IEEE 802.1 Chair"),
+	([0x00, 0xE0, 0x4C], "Realtek Semiconductor Corp."),
+	([0x08, 0x00, 0x27], "PCS Systemtechnik GmbH"),
+	([0x14, 0xCC, 0x20], "TP-LINK TECHNOLOGIES CO.,LTD."),
+	([0x24, 0x0A, 0xC4], "Espressif Inc."),
+	([0x28, 0xCF, 0xE9], "Apple, Inc."),
+	([0x30, 0xAE, 0xA4], "Espressif Inc."),
+	([0x3C, 0x07, 0x54], "Apple, Inc."),
+	([0x3C, 0x5A, 0xB4], "Google, Inc."),
+	([0x3C, 0xA9, 0xF4], "Intel Corporate"),
+	([0x44, 0x65, 0x0D], "Amazon Technologies Inc."),
+	([0x50, 0xC7, 0xBF], "TP-LINK TECHNOLOGIES CO.,LTD."),
+	([0x64, 0x66, 0xB3], "TP-LINK TECHNOLOGIES CO.,LTD."),
+	([0x68, 0x37, 0xE9], "Amazon Technologies Inc."),
+	([0x84, 0xF3, 0xEB], "Espressif Inc."),
+	([0xA0, 0x88, 0xB4], "Intel Corporate"),
+	([0xA4, 0xCF, 0x12], "Espressif Inc."),
+	([0xB8, 0x27, 0xEB], "Raspberry Pi Foundation"),
+	([0xB8, 0xAC, 0x6F], "Dell Inc."),
+	([0xDC, 0xA6, 0x32], "Raspberry Pi Trading Ltd"),
+	([0xE4, 0x5F, 0x01], "Raspberry Pi Trading Ltd"),
+	([0xF0, 0x18, 0x98], "Apple, Inc."),
+	([0xF4, 0xF5, 0xD8], "Google, Inc."),
+	([0xF8, 0x16, 0x54], "Intel Corporate"),
+];
+
+/// Groups of popular vendors whose prefixes are common on everyday devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Category {
+	/// Laptops and phones
+	Consumer,
+	/// Laptop Wi-Fi and Ethernet chipsets
+	Laptop,
+	/// Phones and tablets
+	Phone,
+}
+
+const LAPTOP: &[[u8; 3]] = &[
+	[0x00, 0x13, 0xE8],
+	[0x00, 0x15, 0x00],
+	[0x00, 0x16, 0xEA],
+	[0x00, 0x1B, 0x21],
+	[0x00, 0x1F, 0x3B],
+	[0x00, 0x21, 0x6A],
+	[0x00, 0x24, 0xD7],
+	[0x3C, 0xA9, 0xF4],
+	[0xA0, 0x88, 0xB4],
+	[0xF8, 0x16, 0x54],
+	[0x00, 0x14, 0x22],
+	[0x00, 0x21, 0x70],
+	[0xB8, 0xAC, 0x6F],
+	[0x00, 0x0C, 0x6E],
+	[0x00, 0x11, 0x2F],
+	[0x00, 0xE0, 0x4C],
+];
+
+const PHONE: &[[u8; 3]] = &[
+	[0x00, 0x1E, 0xC2],
+	[0x00, 0x25, 0x00],
+	[0x00, 0x26, 0xBB],
+	[0x28, 0xCF, 0xE9],
+	[0x3C, 0x07, 0x54],
+	[0xF0, 0x18, 0x98],
+	[0x00, 0x15, 0x99],
+	[0x00, 0x16, 0x32],
+	[0x00, 0x1D, 0x25],
+	[0x00, 0x21, 0x19],
+	[0x00, 0x26, 0x37],
+	[0x3C, 0x5A, 0xB4],
+	[0xF4, 0xF5, 0xD8],
+];
+
+impl Category {
+	fn prefixes(&self) -> Vec<&'static [u8; 3]> {
+		match self {
+			Category::Consumer => LAPTOP.iter().chain(PHONE).collect(),
+			Category::Laptop => LAPTOP.iter().collect(),
+			Category::Phone => PHONE.iter().collect(),
+		}
+	}
+
+	/// Pick one of the category's vendor prefixes at random
+	pub fn random_prefix(&self) -> [u8; 3] {
+		let prefixes = self.prefixes();
+		*prefixes[rand::random::<usize>() % prefixes.len()]
+	}
+
+	/// Whether a prefix is one of the category's
+	pub fn has_prefix(&self, prefix: &[u8]) -> bool {
+		self.prefixes().iter().any(|known| known[..] == *prefix)
+	}
+}
+
+/// Vendor prefixes and names, sorted by prefix
+pub struct Database {
+	entries: Vec<([u8; 3], String)>,
+}
+
+impl Database {
+	/// Load the database at `path`, or the first one installed on the system
+	pub fn load(path: Option<&Path>) -> Database {
+		let text = path
+			.into_iter()
+			.chain(SYSTEM_PATHS.iter().map(Path::new))
+			.find_map(|path| fs::read_to_string(path).ok());
+
+		let mut entries: Vec<_> = match text {
+			Some(text) => text.lines().filter_map(parse_line).collect(),
+			None => BUILTIN
+				.iter()
+				.map(|(prefix, name)| (*prefix, name.to_string()))
+				.collect(),
+		};
+		entries.sort_by_key(|(prefix, _)| *prefix);
+		entries.dedup_by_key(|(prefix, _)| *prefix);
+		Database { entries }
+	}
+
+	/// The vendor an address was assigned to, if any
+	pub fn lookup(&self, addr: MacAddr) -> Option<&str> {
+		let prefix = [addr.bytes[0], addr.bytes[1], addr.bytes[2]];
+		self.entries
+			.binary_search_by_key(&prefix, |(prefix, _)| *prefix)
+			.ok()
+			.map(|i| self.entries[i].1.as_str())
+	}
+
+	/// Every vendor whose name contains `query` (ignoring case)
+	pub fn search(&self, query: &str) -> Vec<([u8; 3], &str)> {
+		let query = query.to_lowercase();
+		self.entries
+			.iter()
+			.filter(|(_, name)| name.to_lowercase().contains(&query))
+			.map(|(prefix, name)| (*prefix, name.as_str()))
+			.collect()
+	}
+
+	/// Every vendor name starting with `start` (ignoring case), once each, for
+	/// shell completion
+	pub fn complete(&self, start: &str) -> Vec<&str> {
+		let start = start.to_lowercase();
+		let mut names: Vec<&str> = self
+			.entries
+			.iter()
+			.map(|(_, name)| name.as_str())
+			.filter(|name| name.to_lowercase().starts_with(&start))
+			.collect();
+		names.sort_unstable();
+		names.dedup();
+		names
+	}
+
+	/// The prefixes of a vendor, given its whole name or a part of it only one
+	/// vendor's name has (ignoring case)
+	pub fn vendor(&self, query: &str) -> Result<Vec<[u8; 3]>, String> {
+		let of = |vendor: &str| -> Vec<[u8; 3]> {
+			self.entries
+				.iter()
+				.filter(|(_, name)| name.eq_ignore_ascii_case(vendor))
+				.map(|(prefix, _)| *prefix)
+				.collect()
+		};
+		let exact = of(query);
+		if !exact.is_empty() {
+			return Ok(exact);
+		}
+		let mut names: Vec<&str> = self
+			.search(query)
+			.into_iter()
+			.map(|(_, name)| name)
+			.collect();
+		names.sort_unstable();
+		names.dedup();
+		match &names[..] {
+			[] => Err(format!(
+				"No vendor matching '{}' (see `rac vendors`)",
+				query
+			)),
+			[name] => Ok(of(name)),
+			_ => Err(format!(
+				"'{}' matches {} vendors, e.g. {}",
+				query,
+				names.len(),
+				names[..names.len().min(3)].join(", ")
+			)),
+		}
+	}
+}
+
+/// Parse a 24-bit prefix written as `00-1B-21`, `00:1B:21` or `001B21`
+fn parse_prefix(input: &str) -> Option<[u8; 3]> {
+	let digits: String = input.chars().filter(|c| *c != '-' && *c != ':').collect();
+	if digits.len() != 6 {
+		return None;
+	}
+	let value = u32::from_str_radix(&digits, 16).ok()?;
+	let [_, a, b, c] = value.to_be_bytes();
+	Some([a, b, c])
+}
+
+/// Parse one line of any of the supported database formats
+fn parse_line(line: &str) -> Option<([u8; 3], String)> {
+	let line = line.trim();
+	if line.is_empty() || line.starts_with('#') || line.contains("(base 16)") {
+		return None;
+	}
+	// IEEE: "00-1B-21   (hex)		Intel Corporate"
+	if let Some((prefix, name)) = line.split_once("(hex)") {
+		return Some((parse_prefix(prefix.trim())?, name.trim().to_string()));
+	}
+	// Wireshark: "00:1B:21	Intel	Intel Corporate", longer masks are skipped
+	let mut fields = line.split('\t').filter(|field| !field.is_empty());
+	let prefix = fields.next()?;
+	if prefix.contains(':') {
+		if prefix.contains('/') {
+			return None;
+		}
+		let short = fields.next()?;
+		let name = fields.next().unwrap_or(short);
+		return Some((parse_prefix(prefix)?, name.trim().to_string()));
+	}
+	// nmap: "001B21 Intel Corporate"
+	let (prefix, name) = line.split_once(' ')?;
+	Some((parse_prefix(prefix)?, name.trim().to_string()))
+}
@@ -0,0 +1,178 @@
+//! Managed address pools (`rac pool`)
+//!
+//! Each pool is a file in `<state dir>/pools/`, holding the prefix addresses
+//! are allocated from and one line per allocated address:
+//!
+//! ```text
+//! prefix 02:AB:CD
+//! 02:AB:CD:00:00:00 vm-web
+//! 02:AB:CD:00:00:01
+//! ```
+
+use std::{
+	collections::HashSet,
+	fs::{self, File, OpenOptions},
+	io::{self, Read, Seek, SeekFrom, Write},
+	os::unix::io::AsRawFd,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
+
+use nix::fcntl::{flock, FlockArg};
+
+use crate::{error::RacError, hex_bytes, parse_hex_bytes, MacAddr};
+
+fn invalid(e: String) -> io::Error { RacError::Parse(e).into() }
+
+fn invalid_data(path: &Path, e: &str) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		format!("{}: {}", path.display(), e),
+	)
+}
+
+/// Parse a prefix of one to five bytes, e.g. `02:AB:CD`
+fn parse_prefix(input: &str) -> io::Result<Vec<u8>> {
+	let prefix =
+		parse_hex_bytes(input).ok_or_else(|| invalid(format!("invalid prefix '{}'", input)))?;
+	if prefix.is_empty() || prefix.len() > 5 {
+		return Err(invalid(format!(
+			"a prefix must be 1 to 5 bytes long, not {}",
+			prefix.len()
+		)));
+	}
+	if prefix[0] & 0x01 != 0 {
+		return Err(invalid(format!("prefix '{}' is multicast", input)));
+	}
+	Ok(prefix)
+}
+
+fn pools_dir(state_dir: &Path) -> PathBuf { state_dir.join("pools") }
+
+fn pool_path(state_dir: &Path, name: &str) -> io::Result<PathBuf> {
+	if name.is_empty() || name.starts_with('.') || name.contains('/') {
+		return Err(invalid(format!("invalid pool name '{}'", name)));
+	}
+	Ok(pools_dir(state_dir).join(name))
+}
+
+/// Names of every pool
+pub fn names(state_dir: &Path) -> io::Result<Vec<String>> {
+	let mut names = match fs::read_dir(pools_dir(state_dir)) {
+		Ok(entries) => entries
+			.flatten()
+			.map(|entry| entry.file_name().to_string_lossy().into_owned())
+			.collect(),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+		Err(e) => return Err(e),
+	};
+	names.sort();
+	Ok(names)
+}
+
+/// A pool, locked for exclusive use until dropped
+pub struct Pool {
+	pub prefix:      Vec<u8>,
+	/// Allocated addresses, with an optional label
+	pub allocations: Vec<(MacAddr, Option<String>)>,
+	file:            File,
+}
+
+impl Pool {
+	/// Create a new, empty pool
+	pub fn create(state_dir: &Path, name: &str, prefix: &str) -> io::Result<()> {
+		let path = pool_path(state_dir, name)?;
+		let prefix = parse_prefix(prefix)?;
+		fs::create_dir_all(pools_dir(state_dir))?;
+		let mut file = OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(&path)
+			.map_err(|e| match e.kind() {
+				io::ErrorKind::AlreadyExists => {
+					io::Error::new(e.kind(), format!("pool '{}' already exists", name))
+				}
+				_ => e,
+			})?;
+		writeln!(file, "prefix {}", hex_bytes(&prefix))
+	}
+
+	/// Open and lock an existing pool
+	pub fn open(state_dir: &Path, name: &str) -> io::Result<Pool> {
+		let path = pool_path(state_dir, name)?;
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.open(&path)
+			.map_err(|e| match e.kind() {
+				io::ErrorKind::NotFound => {
+					io::Error::new(e.kind(), format!("no pool called '{}'", name))
+				}
+				_ => e,
+			})?;
+		flock(file.as_raw_fd(), FlockArg::LockExclusive)?;
+
+		let mut text = String::new();
+		file.read_to_string(&mut text)?;
+		let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+		let prefix = lines
+			.next()
+			.and_then(|line| line.strip_prefix("prefix "))
+			.ok_or_else(|| invalid_data(&path, "missing prefix"))
+			.and_then(|prefix| parse_prefix(prefix.trim()))?;
+		let allocations = lines
+			.map(|line| {
+				let (addr, label) = match line.split_once(' ') {
+					Some((addr, label)) => (addr, Some(label.trim().to_string())),
+					None => (line, None),
+				};
+				MacAddr::from_str(addr.trim())
+					.map(|addr| (addr, label))
+					.map_err(|_| invalid_data(&path, &format!("invalid address '{}'", addr)))
+			})
+			.collect::<io::Result<_>>()?;
+
+		Ok(Pool {
+			prefix,
+			allocations,
+			file,
+		})
+	}
+
+	/// Allocate the lowest address that isn't in use yet
+	pub fn allocate(&mut self, label: Option<String>) -> io::Result<MacAddr> {
+		let used: HashSet<MacAddr> = self.allocations.iter().map(|(addr, _)| *addr).collect();
+		let mut base = [0u8; 6];
+		base[..self.prefix.len()].copy_from_slice(&self.prefix);
+		let base = MacAddr::new(base);
+		let size = 1u64 << (8 * (6 - self.prefix.len()));
+
+		let addr = (0..size)
+			.filter_map(|i| base.checked_offset(i as i64))
+			.find(|addr| !used.contains(addr))
+			.ok_or_else(|| io::Error::other("pool is exhausted"))?;
+		self.allocations.push((addr, label));
+		Ok(addr)
+	}
+
+	/// Return an address to the pool, `false` if it wasn't allocated
+	pub fn release(&mut self, addr: MacAddr) -> bool {
+		let before = self.allocations.len();
+		self.allocations.retain(|(allocated, _)| *allocated != addr);
+		self.allocations.len() != before
+	}
+
+	/// Write the pool back to its file
+	pub fn save(&mut self) -> io::Result<()> {
+		let mut text = format!("prefix {}\n", hex_bytes(&self.prefix));
+		for (addr, label) in &self.allocations {
+			match label {
+				Some(label) => text.push_str(&format!("{} {}\n", addr, label)),
+				None => text.push_str(&format!("{}\n", addr)),
+			}
+		}
+		self.file.set_len(0)?;
+		self.file.seek(SeekFrom::Start(0))?;
+		self.file.write_all(text.as_bytes())
+	}
+}
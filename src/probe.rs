@@ -0,0 +1,27 @@
+//! Looking for another host with an address before taking it (`--probe`)
+//!
+//! There's no ARP for a MAC address, so the probe gets the hosts on the
+//! segment to show theirs instead: an IPv6 ping to every node on the link
+//! (`ff02::1`) makes each one that speaks IPv6 answer, which puts it in the
+//! neighbor table along with every host that talked over IPv4 or IPv6
+//! lately. An address in that table is taken. Hosts that don't answer and
+//! have been quiet can't be found, so a probe that finds nothing makes a
+//! conflict unlikely rather than impossible.
+
+use std::io;
+
+use crate::{neigh, MacAddr};
+
+/// How many addresses are tried before giving up
+pub const ATTEMPTS: usize = 3;
+
+/// Whether another host on the interface's segment has an address
+pub fn in_use(inter: &str, addr: MacAddr) -> io::Result<bool> {
+	neigh::ping_all(inter).map_err(|e| match e.kind() {
+		io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, "--probe needs ping"),
+		_ => e,
+	})?;
+	Ok(neigh::table(Some(inter))?
+		.iter()
+		.any(|neighbor| neighbor.mac == addr))
+}
@@ -0,0 +1,51 @@
+//! Drivers known to ignore, revert or penalize MAC address changes
+//!
+//! Matched against the driver name ethtool reports. The list is short on
+//! purpose: it only holds drivers where the problem is common enough to be
+//! worth a warning before every change.
+
+use crate::ethtool;
+
+/// Driver names and what goes wrong with them
+const QUIRKS: [(&str, &str); 13] = [
+	// Realtek USB Wi-Fi, mostly out-of-tree drivers
+	("8188eu", "revert the address when (re)associating"),
+	("8192eu", "revert the address when (re)associating"),
+	("8812au", "revert the address when (re)associating"),
+	("88XXau", "revert the address when (re)associating"),
+	("rtl8xxxu", "ignore address changes on some chipsets"),
+	// Broadcom's proprietary STA driver
+	("wl", "ignore address changes while the interface is up"),
+	(
+		"ipheth",
+		"ignore address changes (the phone picks the address)",
+	),
+	// Hypervisor and cloud NICs, where the host filters on the assigned address
+	(
+		"hv_netvsc",
+		"drop traffic from other addresses unless MAC spoofing is enabled for the VM",
+	),
+	(
+		"vmxnet3",
+		"drop traffic from other addresses unless the port group allows MAC changes",
+	),
+	(
+		"ena",
+		"drop traffic from addresses other than the one AWS assigned",
+	),
+	(
+		"gve",
+		"drop traffic from addresses other than the one GCP assigned",
+	),
+	// SR-IOV virtual functions, whose address belongs to the physical function
+	("iavf", "reject address changes unless the VF is trusted"),
+	("ixgbevf", "reject address changes unless the VF is trusted"),
+];
+
+/// What's known to go wrong when changing the address of `inter`, with its
+/// driver
+pub fn lookup(inter: &str) -> Option<(String, &'static str)> {
+	let driver = ethtool::driver(inter).ok().flatten()?;
+	let (_, problem) = QUIRKS.iter().find(|(name, _)| *name == driver)?;
+	Some((driver, problem))
+}
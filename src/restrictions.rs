@@ -0,0 +1,312 @@
+//! What an administrator allows rac to change (`/etc/rac/restrictions.conf`)
+//!
+//! Unlike the configuration, which `--config` replaces, this file is always
+//! read, and rac refuses any change it doesn't allow, even as root:
+//!
+//! ```toml
+//! [interfaces]
+//! # Only these interfaces may be changed (every one by default)
+//! allow = ["wl*", "en*"]
+//! # These never may, even if allowed above
+//! deny = ["enp0s31f6"]
+//!
+//! [modes]
+//! # How the new address may come about
+//! allow = ["random", "stable", "restore"]
+//! ```
+//!
+//! Names may use `*` and `?`. The modes are `random` (generated, with any
+//! policy but the stable ones), `stable` (`stable-per-network` and
+//! `stable-per-day`), `address` (given explicitly, copied from another
+//! interface or fixed in the configuration) and `restore` (the permanent
+//! address, or `rac undo`). The file must be owned by root and writable by
+//! no one else, or rac doesn't run at all.
+
+use std::{fs, io, os::unix::fs::MetadataExt, sync::OnceLock};
+
+use crate::{error::RacError, ethtool, value::Value, MacAddr};
+
+/// Where the restrictions are
+pub const PATH: &str = "/etc/rac/restrictions.conf";
+
+/// How a new address came about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+	Random,
+	Stable,
+	Address,
+	Restore,
+}
+
+impl Mode {
+	/// How an address given for an interface (rather than generated) comes
+	/// about: restoring it if it's the permanent one
+	pub fn given(inter: &str, addr: MacAddr) -> Mode {
+		match ethtool::permanent_addr(inter) {
+			Ok(Some(permanent)) if permanent == addr => Mode::Restore,
+			_ => Mode::Address,
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Mode::Random => "random",
+			Mode::Stable => "stable",
+			Mode::Address => "address",
+			Mode::Restore => "restore",
+		}
+	}
+}
+
+/// A list of patterns, with the line of the file it's on
+struct Rule {
+	line:     usize,
+	patterns: Vec<String>,
+}
+
+#[derive(Default)]
+struct Restrictions {
+	allow_interfaces: Option<Rule>,
+	deny_interfaces:  Option<Rule>,
+	allow_modes:      Option<Rule>,
+	deny_modes:       Option<Rule>,
+}
+
+static RESTRICTIONS: OnceLock<Restrictions> = OnceLock::new();
+
+/// Whether a name matches a pattern with `*` and `?`
+pub fn matches(pattern: &[u8], name: &[u8]) -> bool {
+	match (pattern.split_first(), name.split_first()) {
+		(Some((b'*', rest)), _) => {
+			matches(rest, name) || (!name.is_empty() && matches(pattern, &name[1..]))
+		}
+		(Some((b'?', rest)), Some((_, name))) => matches(rest, name),
+		(Some((p, rest)), Some((n, name))) => p == n && matches(rest, name),
+		(None, None) => true,
+		_ => false,
+	}
+}
+
+impl Rule {
+	/// The pattern matching a name, if one does
+	fn matching(&self, name: &str) -> Option<&str> {
+		self.patterns
+			.iter()
+			.find(|pattern| matches(pattern.as_bytes(), name.as_bytes()))
+			.map(String::as_str)
+	}
+}
+
+fn parse(text: &str) -> Result<Restrictions, (usize, String)> {
+	let mut restrictions = Restrictions::default();
+	let mut section = String::new();
+	for (nth, line) in text.lines().enumerate() {
+		let line = line.split('#').next().unwrap_or_default().trim();
+		if line.is_empty() {
+			continue;
+		}
+		let err = |e: String| (nth + 1, e);
+		if let Some(header) = line.strip_prefix('[') {
+			section = header
+				.strip_suffix(']')
+				.ok_or_else(|| err("unterminated section header".to_string()))?
+				.trim()
+				.to_string();
+			continue;
+		}
+		let (key, raw) = line
+			.split_once('=')
+			.ok_or_else(|| err("expected 'key = value'".to_string()))?;
+		let key = key.trim();
+		let rule = Rule {
+			line:     nth + 1,
+			patterns: Value::parse(raw.trim())
+				.and_then(Value::into_list)
+				.map_err(err)?,
+		};
+		if section == "modes" {
+			if let Some(mode) = rule
+				.patterns
+				.iter()
+				.find(|mode| !["random", "stable", "address", "restore"].contains(&mode.as_str()))
+			{
+				return Err(err(format!(
+					"unknown mode '{}' (expected random, stable, address or restore)",
+					mode
+				)));
+			}
+		}
+		match (section.as_str(), key) {
+			("interfaces", "allow") => restrictions.allow_interfaces = Some(rule),
+			("interfaces", "deny") => restrictions.deny_interfaces = Some(rule),
+			("modes", "allow") => restrictions.allow_modes = Some(rule),
+			("modes", "deny") => restrictions.deny_modes = Some(rule),
+			_ => return Err(err(format!("unknown key '{}' in [{}]", key, section))),
+		}
+	}
+	Ok(restrictions)
+}
+
+/// Read the restrictions, if there are any. A file anyone but root can write
+/// is an error: whoever can write it could lift them.
+pub fn load() -> io::Result<()> {
+	let text = match fs::read_to_string(PATH) {
+		Ok(text) => text,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+		Err(e) => return Err(e),
+	};
+	let metadata = fs::metadata(PATH)?;
+	if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+		return Err(io::Error::new(
+			io::ErrorKind::PermissionDenied,
+			format!("{} must be owned by root and writable only by it", PATH),
+		));
+	}
+	let restrictions = parse(&text).map_err(|(line, e)| {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("{}:{}: {}", PATH, line, e),
+		)
+	})?;
+	RESTRICTIONS.set(restrictions).ok();
+	Ok(())
+}
+
+fn denied(rule: &Rule, why: String) -> Result<(), RacError> {
+	Err(RacError::PermissionDenied(format!(
+		"{} ({}:{})",
+		why, PATH, rule.line
+	)))
+}
+
+impl Restrictions {
+	fn check_interface(&self, inter: &str) -> Result<(), RacError> {
+		if let Some(rule) = &self.deny_interfaces {
+			if let Some(pattern) = rule.matching(inter) {
+				return denied(rule, format!("{} is denied by '{}'", inter, pattern));
+			}
+		}
+		if let Some(rule) = &self.allow_interfaces {
+			if rule.matching(inter).is_none() {
+				return denied(rule, format!("{} isn't an allowed interface", inter));
+			}
+		}
+		Ok(())
+	}
+
+	fn check(&self, inter: &str, mode: Mode) -> Result<(), RacError> {
+		self.check_interface(inter)?;
+		let mode = mode.name();
+		if let Some(rule) = &self.deny_modes {
+			if rule.matching(mode).is_some() {
+				return denied(rule, format!("mode '{}' is denied for {}", mode, inter));
+			}
+		}
+		if let Some(rule) = &self.allow_modes {
+			if rule.matching(mode).is_none() {
+				return denied(rule, format!("mode '{}' isn't allowed for {}", mode, inter));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Refuse any change to an interface the restrictions don't allow, naming the
+/// rule
+pub fn check_interface(inter: &str) -> Result<(), RacError> {
+	RESTRICTIONS
+		.get()
+		.map_or(Ok(()), |restrictions| restrictions.check_interface(inter))
+}
+
+/// Refuse a change the restrictions don't allow, naming the rule. The caller
+/// says how the address came about.
+pub fn check(inter: &str, mode: Mode) -> Result<(), RacError> {
+	RESTRICTIONS
+		.get()
+		.map_or(Ok(()), |restrictions| restrictions.check(inter, mode))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse_ok(text: &str) -> Restrictions {
+		parse(text).unwrap_or_else(|(line, e)| panic!("{}: {}", line, e))
+	}
+
+	#[test]
+	fn patterns() {
+		assert!(matches(b"wl*", b"wlan0"));
+		assert!(matches(b"wl*", b"wl"));
+		assert!(matches(b"en??s*", b"enp0s31f6"));
+		assert!(matches(b"*0", b"eth0"));
+		assert!(!matches(b"wl*", b"eth0"));
+		assert!(!matches(b"eth?", b"eth"));
+		assert!(!matches(b"eth0", b"eth00"));
+	}
+
+	#[test]
+	fn interfaces() {
+		let restrictions = parse_ok(
+			r#"
+			# Wi-Fi and wired, but not the docking station
+			[interfaces]
+			allow = ["wl*", "en*"]
+			deny = ["enx*"] # USB
+			"#,
+		);
+		assert!(restrictions.check_interface("wlan0").is_ok());
+		assert!(restrictions.check_interface("enp0s31f6").is_ok());
+		let denied = restrictions.check_interface("enx00e04c680001").unwrap_err();
+		assert_eq!(
+			denied.to_string(),
+			format!(
+				"permission denied: enx00e04c680001 is denied by 'enx*' ({}:5)",
+				PATH
+			)
+		);
+		let unlisted = restrictions.check_interface("eth0").unwrap_err();
+		assert!(matches!(unlisted, RacError::PermissionDenied(_)));
+		assert!(unlisted.to_string().ends_with(&format!("({}:4)", PATH)));
+	}
+
+	#[test]
+	fn modes() {
+		let restrictions = parse_ok("[modes]\nallow = [\"random\", \"restore\"]\n");
+		assert!(restrictions.check("wlan0", Mode::Random).is_ok());
+		assert!(restrictions.check("wlan0", Mode::Restore).is_ok());
+		assert!(restrictions.check("wlan0", Mode::Address).is_err());
+		assert!(restrictions.check("wlan0", Mode::Stable).is_err());
+
+		let restrictions = parse_ok("[modes]\ndeny = [\"address\"]\n");
+		assert!(restrictions.check("wlan0", Mode::Stable).is_ok());
+		assert!(restrictions.check("wlan0", Mode::Address).is_err());
+	}
+
+	#[test]
+	fn nothing_restricted() {
+		let restrictions = parse_ok("# Nothing yet\n\n[interfaces]\n");
+		assert!(restrictions.check("eth0", Mode::Address).is_ok());
+	}
+
+	#[test]
+	fn invalid() {
+		for (text, line) in [
+			("[interfaces\nallow = []", 1),
+			("[interfaces]\nallow", 2),
+			("[interfaces]\nallow = [\"wl*\"]\nmodes = []", 3),
+			("[interfaces]\nallow = \"wl*\"", 2),
+			("allow = [\"wl*\"]", 1),
+			("[modes]\nallow = [\"random\", \"any\"]", 2),
+			("[interfaces]\nallow = [\"wl*\"", 2),
+		] {
+			assert_eq!(
+				parse(text).err().map(|(line, _)| line),
+				Some(line),
+				"{}",
+				text
+			);
+		}
+	}
+}
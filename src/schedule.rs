@@ -0,0 +1,410 @@
+//! Rotation schedules of the daemon (`[schedule]` in the configuration)
+//!
+//! A schedule is either a fixed interval (`every 30m`) or a cron expression
+//! in local time, written out (`0 3 * * *`), as a macro (`@daily`) or in words
+//! (`daily at 03:00`, `every monday`).
+
+use std::{
+	str::FromStr,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use nix::libc;
+
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const MONTHS: [&str; 12] = [
+	"jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// The longest interval accepted, a hundred years
+const MAX_INTERVAL: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// When to rotate an interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+	/// Every so often, counted from the daemon's start or the last rotation
+	Every(Duration),
+	/// Whenever the local time matches
+	Cron(Cron),
+}
+
+/// A parsed cron expression, each field as a bit set of allowed values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+	minutes:  u64,
+	hours:    u64,
+	days:     u64,
+	months:   u64,
+	weekdays: u64,
+	/// Whether day of month and day of week were left unrestricted (`*`)
+	any_day:  (bool, bool),
+}
+
+/// The fields of a local time that cron expressions match against
+struct LocalTime {
+	minute:  u32,
+	hour:    u32,
+	day:     u32,
+	month:   u32,
+	weekday: u32,
+}
+
+impl LocalTime {
+	fn at(time: SystemTime) -> LocalTime {
+		let secs = time
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |since| since.as_secs()) as libc::time_t;
+		// SAFETY: both pointers are valid for the duration of the call, and an
+		// all-zero `tm` is a valid value
+		let tm = unsafe {
+			let mut tm = std::mem::zeroed::<libc::tm>();
+			libc::localtime_r(&secs, &mut tm);
+			tm
+		};
+		LocalTime {
+			minute:  tm.tm_min as u32,
+			hour:    tm.tm_hour as u32,
+			day:     tm.tm_mday as u32,
+			month:   tm.tm_mon as u32 + 1,
+			weekday: tm.tm_wday as u32,
+		}
+	}
+}
+
+impl Cron {
+	fn matches(&self, time: &LocalTime) -> bool {
+		let has = |set: u64, value: u32| set & (1 << value) != 0;
+		let day = has(self.days, time.day);
+		let weekday = has(self.weekdays, time.weekday);
+		// Like cron, a day matches either restriction when both are given
+		let day = match self.any_day {
+			(true, true) => true,
+			(true, false) => weekday,
+			(false, true) => day,
+			(false, false) => day || weekday,
+		};
+		has(self.minutes, time.minute)
+			&& has(self.hours, time.hour)
+			&& has(self.months, time.month)
+			&& day
+	}
+}
+
+impl Schedule {
+	/// How long from `now` until the schedule is due, given when it last ran
+	/// (or the daemon started)
+	pub fn wait(&self, last: SystemTime, now: SystemTime) -> Duration {
+		match self {
+			// Never due, if it's past what a `SystemTime` can hold
+			Schedule::Every(interval) => last.checked_add(*interval).map_or(Duration::MAX, |due| {
+				due.duration_since(now).unwrap_or_default()
+			}),
+			Schedule::Cron(cron) => {
+				let minute = |time: SystemTime| {
+					time.duration_since(UNIX_EPOCH)
+						.unwrap_or_default()
+						.as_secs() / 60
+				};
+				if minute(last) != minute(now) && cron.matches(&LocalTime::at(now)) {
+					return Duration::ZERO;
+				}
+				// Check again at the start of the next minute
+				let into = now
+					.duration_since(UNIX_EPOCH)
+					.unwrap_or_default()
+					.as_millis() % 60_000;
+				Duration::from_millis(60_000 - into as u64)
+			}
+		}
+	}
+}
+
+/// Parse a number or, if `names` are given, a name (`mon`, `monday`)
+/// standing for its index plus `offset`
+fn parse_value(value: &str, names: &[&str], offset: u32) -> Result<u32, String> {
+	let lower = value.to_ascii_lowercase();
+	let name = names
+		.iter()
+		.position(|name| lower.starts_with(name) && lower.chars().all(|c| c.is_ascii_alphabetic()));
+	match name {
+		Some(index) => Ok(index as u32 + offset),
+		None => value
+			.parse()
+			.map_err(|_| format!("invalid value '{}'", value)),
+	}
+}
+
+/// Parse one cron field (`*`, `5`, `1-5`, `*/15`, `mon,wed`, ...) into a bit
+/// set of the values within `min..=max`
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+	let mut set = 0;
+	for part in field.split(',') {
+		let (range, step) = match part.split_once('/') {
+			Some((range, step)) => (
+				range,
+				step.parse()
+					.ok()
+					.filter(|step| *step > 0)
+					.ok_or_else(|| format!("invalid step '{}'", step))?,
+			),
+			None => (part, 1),
+		};
+		let (start, end) = match range.split_once('-') {
+			_ if range == "*" => (min, max),
+			Some((start, end)) => (
+				parse_value(start, names, min)?,
+				parse_value(end, names, min)?,
+			),
+			// `5/10` means from 5 to the end, every 10
+			None if step > 1 => (parse_value(range, names, min)?, max),
+			None => {
+				let value = parse_value(range, names, min)?;
+				(value, value)
+			}
+		};
+		if start < min || end > max || start > end {
+			return Err(format!("'{}' is outside {}-{}", part, min, max));
+		}
+		for value in (start..=end).step_by(step) {
+			set |= 1 << value;
+		}
+	}
+	Ok(set)
+}
+
+impl FromStr for Cron {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let fields: Vec<&str> = s.split_whitespace().collect();
+		let [minutes, hours, days, months, weekdays_field] = fields[..] else {
+			return Err(format!(
+				"expected 5 fields (minute hour day month weekday), found {}",
+				fields.len()
+			));
+		};
+		// Sunday is both 0 and 7
+		let weekdays = parse_field(weekdays_field, 0, 7, &WEEKDAYS).map(|set| set | (set >> 7))?;
+		Ok(Cron {
+			minutes: parse_field(minutes, 0, 59, &[])?,
+			hours: parse_field(hours, 0, 23, &[])?,
+			days: parse_field(days, 1, 31, &[])?,
+			months: parse_field(months, 1, 12, &MONTHS)?,
+			weekdays,
+			any_day: (days == "*", weekdays_field == "*"),
+		})
+	}
+}
+
+/// Parse `HH:MM` into cron minute and hour fields
+fn parse_time(time: &str) -> Result<(u32, u32), String> {
+	let err = || format!("invalid time '{}', expected HH:MM", time);
+	let (hour, minute) = time.split_once(':').ok_or_else(err)?;
+	match (hour.parse(), minute.parse()) {
+		(Ok(hour), Ok(minute)) if hour < 24 && minute < 60 => Ok((hour, minute)),
+		_ => Err(err()),
+	}
+}
+
+/// Parse an interval like `30m`, of at most a hundred years
+pub fn parse_interval(interval: &str) -> Option<Duration> {
+	let unit = interval.find(|c: char| !c.is_ascii_digit())?;
+	let count: u64 = interval[..unit].parse().ok().filter(|count| *count > 0)?;
+	let secs = match &interval[unit..] {
+		"s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		"d" => 24 * 60 * 60,
+		_ => return None,
+	};
+	count
+		.checked_mul(secs)
+		.map(Duration::from_secs)
+		.filter(|interval| *interval <= MAX_INTERVAL)
+}
+
+impl FromStr for Schedule {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim().to_ascii_lowercase();
+		let cron = |cron: &str| cron.parse().map(Schedule::Cron);
+		// Words: "<when> [at HH:MM]"
+		let (when, at) = match s.split_once(" at ") {
+			Some((when, at)) => (when.trim(), Some(parse_time(at.trim())?)),
+			None => (s.as_str(), None),
+		};
+		let (hour, minute) = at.unwrap_or((0, 0));
+		match when.strip_prefix("every ").map(str::trim) {
+			Some(interval) if interval.starts_with(|c: char| c.is_ascii_digit()) => {
+				match (parse_interval(interval), at) {
+					(Some(interval), None) => Ok(Schedule::Every(interval)),
+					(Some(_), Some(_)) => Err("can't use 'at' with an interval".to_string()),
+					(None, _) => Err(format!(
+						"invalid interval '{}' (e.g. '30m', '12h', '1d')",
+						interval
+					)),
+				}
+			}
+			Some("day") => cron(&format!("{} {} * * *", minute, hour)),
+			Some(weekday) if parse_value(weekday, &WEEKDAYS, 0).is_ok() => {
+				cron(&format!("{} {} * * {}", minute, hour, weekday))
+			}
+			Some(other) => Err(format!("invalid interval or weekday '{}'", other)),
+			None => match when {
+				"daily" => cron(&format!("{} {} * * *", minute, hour)),
+				_ if at.is_some() => Err(format!("can't use 'at' with '{}'", when)),
+				"@hourly" => cron("0 * * * *"),
+				"@daily" | "@midnight" => cron("0 0 * * *"),
+				"@weekly" => cron("0 0 * * 0"),
+				"@monthly" => cron("0 0 1 * *"),
+				_ => cron(when),
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn cron(s: &str) -> Cron { s.parse().unwrap() }
+
+	fn at(minute: u32, hour: u32, day: u32, month: u32, weekday: u32) -> LocalTime {
+		LocalTime {
+			minute,
+			hour,
+			day,
+			month,
+			weekday,
+		}
+	}
+
+	#[test]
+	fn fields() {
+		let every = cron("* * * * *");
+		assert_eq!(every.minutes, (1 << 60) - 1);
+		assert_eq!(every.days, ((1 << 32) - 1) & !1);
+		assert_eq!(every.any_day, (true, true));
+
+		let cron = cron("*/15,7 1-3 10/10 jan,Dec mon-wed");
+		assert_eq!(cron.minutes, 1 | 1 << 7 | 1 << 15 | 1 << 30 | 1 << 45);
+		assert_eq!(cron.hours, 0b1110);
+		assert_eq!(cron.days, 1 << 10 | 1 << 20 | 1 << 30);
+		assert_eq!(cron.months, 1 << 1 | 1 << 12);
+		assert_eq!(cron.weekdays, 0b1110);
+		assert_eq!(cron.any_day, (false, false));
+	}
+
+	#[test]
+	fn sunday_is_0_and_7() {
+		for sunday in [
+			"0 0 * * 0",
+			"0 0 * * 7",
+			"0 0 * * sun",
+			"0 0 * * Sunday",
+			"0 0 * * 5-7",
+		] {
+			assert!(cron(sunday).matches(&at(0, 0, 4, 10, 0)), "{}", sunday);
+			assert!(!cron(sunday).matches(&at(0, 0, 4, 10, 4)), "{}", sunday);
+		}
+	}
+
+	#[test]
+	fn invalid() {
+		for invalid in [
+			"",
+			"* * * *",
+			"* * * * * *",
+			"60 * * * *",
+			"* 24 * * *",
+			"* * 0 * *",
+			"* * * 13 *",
+			"* * * * 8",
+			"5-1 * * * *",
+			"*/0 * * * *",
+			"x * * * *",
+			"* * * * someday",
+		] {
+			assert!(invalid.parse::<Cron>().is_err(), "{}", invalid);
+		}
+	}
+
+	#[test]
+	fn matching() {
+		let office = cron("*/15 9-17 * * mon-fri");
+		assert!(office.matches(&at(30, 9, 14, 10, 1)));
+		assert!(office.matches(&at(0, 17, 14, 10, 5)));
+		assert!(!office.matches(&at(31, 9, 14, 10, 1)));
+		assert!(!office.matches(&at(0, 18, 14, 10, 1)));
+		assert!(!office.matches(&at(0, 9, 14, 10, 0)));
+
+		let january = cron("0 0 * jan *");
+		assert!(january.matches(&at(0, 0, 31, 1, 3)));
+		assert!(!january.matches(&at(0, 0, 1, 2, 3)));
+	}
+
+	#[test]
+	fn day_or_weekday() {
+		// Either restriction matches when both are given, like cron
+		let both = cron("0 0 1 * mon");
+		assert!(both.matches(&at(0, 0, 1, 5, 3)));
+		assert!(both.matches(&at(0, 0, 5, 5, 1)));
+		assert!(!both.matches(&at(0, 0, 5, 5, 2)));
+
+		assert!(!cron("0 0 1 * *").matches(&at(0, 0, 5, 5, 1)));
+		assert!(!cron("0 0 * * mon").matches(&at(0, 0, 1, 5, 3)));
+	}
+
+	#[test]
+	fn schedules() {
+		let parse = |s: &str| s.parse::<Schedule>();
+		assert_eq!(
+			parse("every 30m"),
+			Ok(Schedule::Every(Duration::from_secs(30 * 60)))
+		);
+		assert_eq!(parse("daily at 03:00"), parse("0 3 * * *"));
+		assert_eq!(parse("Every Monday at 8:30"), parse("30 8 * * 1"));
+		assert_eq!(parse("every day"), parse("0 0 * * *"));
+		assert_eq!(parse("@weekly"), parse("0 0 * * 0"));
+		assert_eq!(parse("@midnight"), parse("@daily"));
+		for invalid in [
+			"every 0m",
+			"every 30",
+			"every 30m at 03:00",
+			"daily at 24:00",
+			"every someday",
+			"@hourly at 03:00",
+		] {
+			assert!(parse(invalid).is_err(), "{}", invalid);
+		}
+	}
+
+	#[test]
+	fn intervals() {
+		assert_eq!(parse_interval("90s"), Some(Duration::from_secs(90)));
+		assert_eq!(
+			parse_interval("2d"),
+			Some(Duration::from_secs(2 * 24 * 60 * 60))
+		);
+		assert_eq!(parse_interval("36500d"), Some(MAX_INTERVAL));
+		assert_eq!(parse_interval("36501d"), None);
+		assert_eq!(parse_interval("99999999999999999999d"), None);
+		assert_eq!(parse_interval("1w"), None);
+	}
+
+	#[test]
+	fn waiting_for_an_interval() {
+		let every = Schedule::Every(Duration::from_secs(30));
+		let now = SystemTime::now();
+		assert_eq!(
+			every.wait(now - Duration::from_secs(10), now),
+			Duration::from_secs(20)
+		);
+		assert_eq!(
+			every.wait(now - Duration::from_secs(40), now),
+			Duration::ZERO
+		);
+		assert_eq!(Schedule::Every(Duration::MAX).wait(now, now), Duration::MAX);
+	}
+}
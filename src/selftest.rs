@@ -0,0 +1,152 @@
+//! `rac selftest`: change addresses on a throwaway veth pair, in a network
+//! namespace of its own, with every backend
+//!
+//! The namespace is created through sudo, then rac runs itself inside it (as
+//! root, `--in-netns`) to try each backend, so nothing on the host is touched.
+//! The namespace, and the veth pair with it, is deleted afterwards.
+
+use std::{
+	env, io,
+	process::{self, Command},
+};
+
+use colored::Colorize;
+
+use crate::{error::RacError, escalate, ethtool, ip, netlink, MacAddr};
+
+/// The veth pair in the namespace
+const VETH: &str = "rac0";
+const PEER: &str = "rac1";
+
+/// Ways of setting an address
+#[derive(Debug, Clone, Copy)]
+enum Backend {
+	/// `ip link set`, what rac itself uses
+	Iproute2,
+	/// `RTM_SETLINK` over rtnetlink
+	Netlink,
+	/// The `SIOCSIFHWADDR` ioctl
+	Ioctl,
+}
+
+impl Backend {
+	const ALL: [Backend; 3] = [Backend::Iproute2, Backend::Netlink, Backend::Ioctl];
+
+	fn name(self) -> &'static str {
+		match self {
+			Backend::Iproute2 => "iproute2",
+			Backend::Netlink => "netlink",
+			Backend::Ioctl => "ioctl",
+		}
+	}
+
+	fn set(self, link: &netlink::Link, addr: MacAddr) -> io::Result<()> {
+		match self {
+			Backend::Iproute2 => {
+				let output = Command::new("ip")
+					.args(["link", "set", &link.name, "address", &addr.to_string()])
+					.output()?;
+				if !output.status.success() {
+					return Err(io::Error::other(
+						String::from_utf8_lossy(&output.stderr).trim().to_string(),
+					));
+				}
+				Ok(())
+			}
+			Backend::Netlink => netlink::Socket::new(0)?.set_address(link.index, addr),
+			Backend::Ioctl => ethtool::set_hwaddr(&link.name, addr),
+		}
+	}
+}
+
+/// The veth end that's changed, as the kernel reports it now
+fn veth() -> io::Result<netlink::Link> {
+	netlink::Socket::new(0)?
+		.links()?
+		.into_iter()
+		.find(|link| link.name == VETH)
+		.ok_or_else(|| RacError::NoInterface(VETH.to_string()).into())
+}
+
+/// Set a random address with a backend, and check the kernel reports it
+fn check(backend: Backend, up: bool) -> io::Result<()> {
+	let addr = MacAddr::random();
+	backend.set(&veth()?, addr)?;
+	let now = veth()?.mac();
+	if now != Some(addr) {
+		return Err(io::Error::other(format!(
+			"set {} but the interface has {}",
+			addr,
+			now.map_or("no address".to_string(), |now| now.to_string())
+		)));
+	}
+	if up != veth()?.is_up() {
+		return Err(io::Error::other("the link went up or down"));
+	}
+	Ok(())
+}
+
+/// Run inside the namespace, as root: try every backend on the veth pair,
+/// with the link down and up
+pub fn in_netns() -> io::Result<()> {
+	let mut failed = 0;
+	// Changing the address of a link that's up is what most often fails on
+	// hardware, so both are checked
+	for up in [false, true] {
+		let state = if up { "up" } else { "down" };
+		let status = Command::new("ip")
+			.args(["link", "set", VETH, state])
+			.status()?;
+		if !status.success() {
+			return Err(io::Error::other(format!(
+				"couldn't bring {} {}",
+				VETH, state
+			)));
+		}
+		for backend in Backend::ALL {
+			let label = format!("{} (link {})", backend.name(), state);
+			match check(backend, up) {
+				Ok(()) => println!("{:<20} {}", label, "ok".green().bold()),
+				Err(e) => {
+					println!("{:<20} {}: {}", label, "failed".red().bold(), e);
+					failed += 1;
+				}
+			}
+		}
+	}
+	if failed > 0 {
+		return Err(io::Error::other(format!("{} check(s) failed", failed)));
+	}
+	Ok(())
+}
+
+/// `rac selftest`: set up the namespace, run the checks in it and clean up
+pub fn run() -> io::Result<()> {
+	let netns = format!("rac-selftest-{}", process::id());
+	ip(&["netns", "add", &netns]).inspect_err(|e| {
+		println!("Failed to create a network namespace: {}", e);
+	})?;
+	let result = (|| -> io::Result<bool> {
+		ip(&[
+			"-n", &netns, "link", "add", VETH, "type", "veth", "peer", "name", PEER,
+		])?;
+		let status = escalate::command("ip")
+			.args(["netns", "exec", &netns])
+			.arg(env::current_exe()?)
+			.args(["selftest", "--in-netns"])
+			.status()?;
+		Ok(status.success())
+	})();
+	// Deleting the namespace deletes the veth pair in it too
+	if let Err(e) = ip(&["netns", "delete", &netns]) {
+		println!(
+			"Failed to delete the network namespace {}: {}",
+			netns.red().bold(),
+			e
+		);
+	}
+	if !result? {
+		return Err(io::Error::other("some backends don't work on this system"));
+	}
+	Ok(())
+}
@@ -0,0 +1,242 @@
+//! Service files for init systems (`rac service`)
+//!
+//! Each init system gets a service for either mode: the daemon, or
+//! `randomize-all --physical` once at boot, before the network comes up. The
+//! files run the same `rac` binary, with the same `--config`, that wrote them.
+
+use std::path::Path;
+
+use clap::ArgEnum;
+
+use crate::dispatcher::quote;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Init {
+	Systemd,
+	Openrc,
+	Runit,
+	/// s6-rc source definitions
+	S6,
+}
+
+/// What the service runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Mode {
+	/// `rac daemon`, randomizing interfaces as they appear
+	Daemon,
+	/// `rac randomize-all --physical`, once at boot
+	Boot,
+}
+
+/// A file of a service, with its path relative to where the init system
+/// looks for services
+pub struct File {
+	pub path:       String,
+	pub text:       String,
+	pub executable: bool,
+}
+
+impl File {
+	pub fn new(path: &str, lines: &[&str], executable: bool) -> File {
+		File {
+			path: path.to_string(),
+			text: lines.iter().map(|line| format!("{}\n", line)).collect(),
+			executable,
+		}
+	}
+}
+
+impl Mode {
+	fn name(self) -> &'static str {
+		match self {
+			Mode::Daemon => "rac",
+			Mode::Boot => "rac-boot",
+		}
+	}
+
+	fn description(self) -> &'static str {
+		match self {
+			Mode::Daemon => "Randomize MAC addresses as interfaces appear",
+			Mode::Boot => "Randomize the MAC addresses of physical interfaces at boot",
+		}
+	}
+
+	fn args(self) -> &'static str {
+		match self {
+			Mode::Daemon => "daemon",
+			Mode::Boot => "randomize-all --physical",
+		}
+	}
+}
+
+/// A word quoted for execline, which s6-rc's `up` files are written in
+fn quote_execline(word: &str) -> String {
+	format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The files of a service running `exe`, with the configuration file
+/// `config` if one was given
+pub fn files(init: Init, mode: Mode, exe: &Path, config: Option<&Path>) -> Vec<File> {
+	let mut words = vec![exe.to_string_lossy().into_owned()];
+	if let Some(config) = config {
+		words.push("--config".to_string());
+		words.push(config.to_string_lossy().into_owned());
+	}
+	let sh = words
+		.iter()
+		.map(|word| quote(word))
+		.collect::<Vec<_>>()
+		.join(" ");
+	let command = format!("{} {}", sh, mode.args());
+	let name = mode.name();
+	let description = mode.description();
+	match (init, mode) {
+		(Init::Systemd, Mode::Daemon) => vec![File::new(
+			&format!("{}.service", name),
+			&[
+				"[Unit]",
+				&format!("Description={}", description),
+				"Wants=network-pre.target",
+				"Before=network-pre.target",
+				"",
+				"[Service]",
+				&format!("ExecStart={}", command),
+				"ExecReload=/bin/kill -HUP $MAINPID",
+				"Restart=on-failure",
+				"",
+				"[Install]",
+				"WantedBy=multi-user.target",
+			],
+			false,
+		)],
+		(Init::Systemd, Mode::Boot) => vec![File::new(
+			&format!("{}.service", name),
+			&[
+				"[Unit]",
+				&format!("Description={}", description),
+				"Wants=network-pre.target",
+				"Before=network-pre.target",
+				"",
+				"[Service]",
+				"Type=oneshot",
+				&format!("ExecStart={}", command),
+				"RemainAfterExit=yes",
+				"",
+				"[Install]",
+				"WantedBy=multi-user.target",
+			],
+			false,
+		)],
+		(Init::Openrc, Mode::Daemon) => vec![File::new(
+			name,
+			&[
+				"#!/sbin/openrc-run",
+				&format!("description=\"{}\"", description),
+				// The daemon doesn't fork, and writes its own PID file
+				"supervisor=supervise-daemon",
+				&format!("command={}", quote(&words[0])),
+				&format!(
+					"command_args=\"{}\"",
+					words[1..]
+						.iter()
+						.map(|word| quote(word))
+						.chain([mode.args().to_string()])
+						.collect::<Vec<_>>()
+						.join(" ")
+				),
+				"extra_started_commands=\"reload\"",
+				"",
+				"depend() {",
+				"\tbefore net",
+				"}",
+				"",
+				"reload() {",
+				"\tebegin \"Reloading ${RC_SVCNAME}\"",
+				"\tsupervise-daemon \"${RC_SVCNAME}\" --signal HUP",
+				"\teend $?",
+				"}",
+			],
+			true,
+		)],
+		(Init::Openrc, Mode::Boot) => vec![File::new(
+			name,
+			&[
+				"#!/sbin/openrc-run",
+				&format!("description=\"{}\"", description),
+				"",
+				"depend() {",
+				"\tbefore net",
+				"}",
+				"",
+				"start() {",
+				"\tebegin \"Randomizing MAC addresses\"",
+				&format!("\t{}", command),
+				"\teend $?",
+				"}",
+			],
+			true,
+		)],
+		(Init::Runit, Mode::Daemon) => vec![File::new(
+			&format!("{}/run", name),
+			&["#!/bin/sh", &format!("exec {} 2>&1", command)],
+			true,
+		)],
+		// runit has no one-shot services, things done once at boot go in
+		// stage 1, before any service (and so the network) starts
+		(Init::Runit, Mode::Boot) => vec![File::new(
+			&format!("90-{}.sh", name),
+			&[&format!("# {}", description), &command],
+			false,
+		)],
+		(Init::S6, Mode::Daemon) => vec![
+			File::new(&format!("{}/type", name), &["longrun"], false),
+			File::new(
+				&format!("{}/run", name),
+				&["#!/bin/sh", &format!("exec {} 2>&1", command)],
+				true,
+			),
+		],
+		(Init::S6, Mode::Boot) => vec![
+			File::new(&format!("{}/type", name), &["oneshot"], false),
+			File::new(
+				&format!("{}/up", name),
+				&[&format!(
+					"{} {}",
+					words
+						.iter()
+						.map(|word| quote_execline(word))
+						.collect::<Vec<_>>()
+						.join(" "),
+					mode.args()
+				)],
+				false,
+			),
+		],
+	}
+}
+
+/// Where the files go, and how to turn the service on
+pub fn hint(init: Init, mode: Mode) -> String {
+	let name = mode.name();
+	match (init, mode) {
+		(Init::Systemd, _) => format!(
+			"Put it in /etc/systemd/system, then run `systemctl enable --now {}`",
+			name
+		),
+		(Init::Openrc, _) => format!(
+			"Put it in /etc/init.d, then run `rc-update add {} default`",
+			name
+		),
+		(Init::Runit, Mode::Daemon) => format!(
+			"Put it in /etc/sv, then link /etc/sv/{} into the service directory (e.g. \
+			 /var/service)",
+			name
+		),
+		(Init::Runit, Mode::Boot) => "Put it in /etc/runit/core-services".to_string(),
+		(Init::S6, _) => format!(
+			"Put it with your s6-rc source definitions, add {} to a bundle and recompile the \
+			 database",
+			name
+		),
+	}
+}
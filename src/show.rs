@@ -0,0 +1,483 @@
+//! Large renderings of an address (`rac show`), to read it across the room
+//! or scan it with a phone
+//!
+//! A MAC address (17 characters, all in QR's alphanumeric set) always fits a
+//! version 1 QR code at error correction level M, so that's the only one
+//! built here: 21x21 modules, no alignment patterns or version information.
+
+/// Modules per side of a version 1 code
+const SIZE: usize = 21;
+/// Data and error correction codewords of version 1-M
+const DATA_CODEWORDS: usize = 16;
+const ECC_CODEWORDS: usize = 10;
+/// Light modules around the code, as the standard asks for
+const QUIET_ZONE: usize = 4;
+
+const ALPHANUMERIC: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// A 3x5 font for the characters of an address
+const FONT: [(char, [&str; 5]); 17] = [
+	('0', ["███", "█ █", "█ █", "█ █", "███"]),
+	('1', [" █ ", "██ ", " █ ", " █ ", "███"]),
+	('2', ["███", "  █", "███", "█  ", "███"]),
+	('3', ["███", "  █", "███", "  █", "███"]),
+	('4', ["█ █", "█ █", "███", "  █", "  █"]),
+	('5', ["███", "█  ", "███", "  █", "███"]),
+	('6', ["███", "█  ", "███", "█ █", "███"]),
+	('7', ["███", "  █", "  █", "  █", "  █"]),
+	('8', ["███", "█ █", "███", "█ █", "███"]),
+	('9', ["███", "█ █", "███", "  █", "███"]),
+	('A', ["███", "█ █", "███", "█ █", "█ █"]),
+	('B', ["██ ", "█ █", "██ ", "█ █", "██ "]),
+	('C', ["███", "█  ", "█  ", "█  ", "███"]),
+	('D', ["██ ", "█ █", "█ █", "█ █", "██ "]),
+	('E', ["███", "█  ", "███", "█  ", "███"]),
+	('F', ["███", "█  ", "███", "█  ", "█  "]),
+	(':', [" ", "█", " ", "█", " "]),
+];
+
+/// Text in large block letters, one string per line
+pub fn banner(text: &str) -> Vec<String> {
+	(0..5)
+		.map(|row| {
+			text.chars()
+				.filter_map(|c| FONT.iter().find(|(glyph, _)| *glyph == c))
+				.map(|(_, rows)| rows[row])
+				.collect::<Vec<_>>()
+				.join(" ")
+		})
+		.collect()
+}
+
+/// Append the low `len` bits of `value` to `bits`
+fn push_bits(bits: &mut Vec<bool>, value: u32, len: usize) {
+	bits.extend((0..len).rev().map(|i| (value >> i) & 1 != 0));
+}
+
+/// Encode alphanumeric text into the data codewords
+fn encode(text: &str) -> Option<Vec<u8>> {
+	let values: Vec<u32> = text
+		.chars()
+		.map(|c| ALPHANUMERIC.find(c).map(|i| i as u32))
+		.collect::<Option<_>>()?;
+	let mut bits = Vec::new();
+	push_bits(&mut bits, 0b0010, 4);
+	push_bits(&mut bits, values.len() as u32, 9);
+	for pair in values.chunks(2) {
+		match pair {
+			[a, b] => push_bits(&mut bits, a * 45 + b, 11),
+			[a] => push_bits(&mut bits, *a, 6),
+			_ => unreachable!(),
+		}
+	}
+	if bits.len() > DATA_CODEWORDS * 8 {
+		return None;
+	}
+	// Terminator, then zeros to a byte boundary
+	let terminator = (DATA_CODEWORDS * 8 - bits.len()).min(4);
+	bits.extend(std::iter::repeat_n(false, terminator));
+	while bits.len() % 8 != 0 {
+		bits.push(false);
+	}
+	let mut codewords: Vec<u8> = bits
+		.chunks(8)
+		.map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+		.collect();
+	for pad in [0xEC, 0x11].into_iter().cycle() {
+		if codewords.len() == DATA_CODEWORDS {
+			break;
+		}
+		codewords.push(pad);
+	}
+	Some(codewords)
+}
+
+/// Multiply in GF(256) with QR's polynomial
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+	let mut product = 0;
+	while b != 0 {
+		if b & 1 != 0 {
+			product ^= a;
+		}
+		a = (a << 1) ^ if a & 0x80 != 0 { 0x1D } else { 0 };
+		b >>= 1;
+	}
+	product
+}
+
+/// The Reed-Solomon error correction codewords of `data`
+fn ecc(data: &[u8]) -> Vec<u8> {
+	// Generator polynomial (x - 1)(x - 2)...(x - 2^9), highest term left implicit
+	let mut generator = vec![0u8; ECC_CODEWORDS];
+	generator[ECC_CODEWORDS - 1] = 1;
+	let mut root = 1;
+	for _ in 0..ECC_CODEWORDS {
+		for i in 0..ECC_CODEWORDS {
+			generator[i] = gf_mul(generator[i], root);
+			if i + 1 < ECC_CODEWORDS {
+				generator[i] ^= generator[i + 1];
+			}
+		}
+		root = gf_mul(root, 2);
+	}
+	let mut remainder = vec![0u8; ECC_CODEWORDS];
+	for &byte in data {
+		let factor = byte ^ remainder.remove(0);
+		remainder.push(0);
+		for (r, g) in remainder.iter_mut().zip(&generator) {
+			*r ^= gf_mul(*g, factor);
+		}
+	}
+	remainder
+}
+
+/// A QR code being built: `dark[y][x]`, and which modules aren't data
+struct Code {
+	dark:     [[bool; SIZE]; SIZE],
+	function: [[bool; SIZE]; SIZE],
+}
+
+impl Code {
+	fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+		self.dark[y][x] = dark;
+		self.function[y][x] = true;
+	}
+
+	/// Timing and finder patterns (with their separators), and the dark
+	/// module
+	fn draw_patterns(&mut self) {
+		for i in 0..SIZE {
+			self.set_function(6, i, i % 2 == 0);
+			self.set_function(i, 6, i % 2 == 0);
+		}
+		for (cx, cy) in [(3, 3), (SIZE - 4, 3), (3, SIZE - 4)] {
+			for dy in -4..=4isize {
+				for dx in -4..=4isize {
+					let (x, y) = (cx as isize + dx, cy as isize + dy);
+					if (0..SIZE as isize).contains(&x) && (0..SIZE as isize).contains(&y) {
+						let distance = dx.abs().max(dy.abs());
+						self.set_function(x as usize, y as usize, distance != 2 && distance != 4);
+					}
+				}
+			}
+		}
+	}
+
+	/// Both copies of the format information for level M and a mask
+	fn draw_format(&mut self, mask: u32) {
+		let data = mask; // level M is 00
+		let mut remainder = data << 10;
+		for i in (10..15).rev() {
+			if remainder & (1 << i) != 0 {
+				remainder ^= 0x537 << (i - 10);
+			}
+		}
+		let bits = ((data << 10) | remainder) ^ 0x5412;
+		let bit = |i: usize| (bits >> i) & 1 != 0;
+
+		for i in 0..6 {
+			self.set_function(8, i, bit(i));
+		}
+		self.set_function(8, 7, bit(6));
+		self.set_function(8, 8, bit(7));
+		self.set_function(7, 8, bit(8));
+		for i in 9..15 {
+			self.set_function(14 - i, 8, bit(i));
+		}
+		for i in 0..8 {
+			self.set_function(SIZE - 1 - i, 8, bit(i));
+		}
+		for i in 8..15 {
+			self.set_function(8, SIZE - 15 + i, bit(i));
+		}
+		self.set_function(8, SIZE - 8, true);
+	}
+
+	/// Place the codewords in the zigzag over the non-function modules
+	fn draw_codewords(&mut self, codewords: &[u8]) {
+		let mut i = 0;
+		let mut right = SIZE - 1;
+		loop {
+			if right == 6 {
+				right = 5;
+			}
+			for vert in 0..SIZE {
+				for j in 0..2 {
+					let x = right - j;
+					let upward = (right + 1) & 2 == 0;
+					let y = if upward { SIZE - 1 - vert } else { vert };
+					if !self.function[y][x] && i < codewords.len() * 8 {
+						self.dark[y][x] = (codewords[i / 8] >> (7 - i % 8)) & 1 != 0;
+						i += 1;
+					}
+				}
+			}
+			if right < 2 {
+				break;
+			}
+			right -= 2;
+		}
+	}
+
+	/// Flip the data modules selected by a mask pattern (again to undo it)
+	fn apply_mask(&mut self, mask: u32) {
+		for y in 0..SIZE {
+			for x in 0..SIZE {
+				let flip = match mask {
+					0 => (x + y) % 2 == 0,
+					1 => y % 2 == 0,
+					2 => x % 3 == 0,
+					3 => (x + y) % 3 == 0,
+					4 => (x / 3 + y / 2) % 2 == 0,
+					5 => x * y % 2 + x * y % 3 == 0,
+					6 => (x * y % 2 + x * y % 3) % 2 == 0,
+					_ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+				};
+				if flip && !self.function[y][x] {
+					self.dark[y][x] = !self.dark[y][x];
+				}
+			}
+		}
+	}
+
+	/// How hard the code is to scan, by the standard's penalty rules
+	fn penalty(&self) -> usize {
+		let mut penalty = 0;
+		let row = |y: usize| -> Vec<bool> { self.dark[y].to_vec() };
+		let column = |x: usize| -> Vec<bool> { (0..SIZE).map(|y| self.dark[y][x]).collect() };
+		let finder = [true, false, true, true, true, false, true];
+		for line in (0..SIZE).map(row).chain((0..SIZE).map(column)) {
+			// Runs of five or more modules of the same color
+			let mut run = 1;
+			for i in 1..=SIZE {
+				if i < SIZE && line[i] == line[i - 1] {
+					run += 1;
+					continue;
+				}
+				if run >= 5 {
+					penalty += run - 2;
+				}
+				run = 1;
+			}
+			// Patterns that look like a finder, with four light modules beside
+			for start in 0..=SIZE - 7 {
+				if line[start..start + 7] != finder {
+					continue;
+				}
+				// Outside the code counts as light
+				let light = |from: isize, to: isize| {
+					(from..to).all(|i| i < 0 || i >= SIZE as isize || !line[i as usize])
+				};
+				let start = start as isize;
+				if light(start - 4, start) || light(start + 7, start + 11) {
+					penalty += 40;
+				}
+			}
+		}
+		// 2x2 blocks of the same color
+		for y in 0..SIZE - 1 {
+			for x in 0..SIZE - 1 {
+				let color = self.dark[y][x];
+				if self.dark[y][x + 1] == color
+					&& self.dark[y + 1][x] == color
+					&& self.dark[y + 1][x + 1] == color
+				{
+					penalty += 3;
+				}
+			}
+		}
+		// Balance of dark and light modules
+		let dark = self.dark.iter().flatten().filter(|dark| **dark).count();
+		penalty + (dark * 100 / (SIZE * SIZE)).abs_diff(50) / 5 * 10
+	}
+}
+
+/// Build the QR code of `text`, `None` if it doesn't fit
+fn qr_code(text: &str) -> Option<[[bool; SIZE]; SIZE]> {
+	let mut codewords = encode(text)?;
+	codewords.extend(ecc(&codewords));
+
+	let mut code = Code {
+		dark:     [[false; SIZE]; SIZE],
+		function: [[false; SIZE]; SIZE],
+	};
+	code.draw_patterns();
+	// Reserve the format areas before placing data around them
+	code.draw_format(0);
+	code.draw_codewords(&codewords);
+
+	let mask = (0..8)
+		.min_by_key(|&mask| {
+			code.apply_mask(mask);
+			code.draw_format(mask);
+			let penalty = code.penalty();
+			code.apply_mask(mask);
+			penalty
+		})
+		.expect("there are masks");
+	code.apply_mask(mask);
+	code.draw_format(mask);
+	Some(code.dark)
+}
+
+/// `text` as a QR code drawn with half blocks, one string per line. Light
+/// modules are drawn, so the code reads right on a dark terminal background.
+pub fn qr(text: &str) -> Option<Vec<String>> {
+	let dark = qr_code(text)?;
+	let width = SIZE + 2 * QUIET_ZONE;
+	// Outside the code (and its quiet zone) is the terminal's background
+	let light = |x: usize, y: usize| {
+		if y >= width {
+			return false;
+		}
+		let inside = |v: usize| (QUIET_ZONE..QUIET_ZONE + SIZE).contains(&v);
+		!(inside(x) && inside(y) && dark[y - QUIET_ZONE][x - QUIET_ZONE])
+	};
+	Some(
+		(0..width)
+			.step_by(2)
+			.map(|y| {
+				(0..width)
+					.map(|x| match (light(x, y), light(x, y + 1)) {
+						(true, true) => '█',
+						(true, false) => '▀',
+						(false, true) => '▄',
+						(false, false) => ' ',
+					})
+					.collect()
+			})
+			.collect(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The example of version 1-M from Thonky's QR code tutorial
+	const HELLO_WORLD: [u8; DATA_CODEWORDS] = [
+		32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+	];
+
+	/// The format information of level M with each mask, as the standard lists
+	/// it
+	const FORMATS: [&str; 8] = [
+		"101010000010010",
+		"101000100100101",
+		"101111001111100",
+		"101101101001011",
+		"100010111111001",
+		"100000011001110",
+		"100111110010111",
+		"100101010100000",
+	];
+
+	/// The format information of a code, from the copy by the bottom left and
+	/// top right finders
+	fn format(dark: &[[bool; SIZE]; SIZE]) -> u32 {
+		let bits = (0..8)
+			.map(|i| dark[8][SIZE - 1 - i])
+			.chain((8..15).map(|i| dark[SIZE - 15 + i][8]));
+		bits.enumerate()
+			.fold(0, |acc, (i, bit)| acc | (bit as u32) << i)
+	}
+
+	#[test]
+	fn encodes_alphanumeric() {
+		assert_eq!(encode("HELLO WORLD"), Some(HELLO_WORLD.to_vec()));
+		assert_eq!(encode("hello world"), None);
+		// 20 characters is all that fits
+		assert!(encode(&"A".repeat(20)).is_some());
+		assert_eq!(encode(&"A".repeat(21)), None);
+	}
+
+	#[test]
+	fn error_correction() {
+		assert_eq!(
+			ecc(&HELLO_WORLD),
+			[196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+		);
+		assert_eq!(gf_mul(0x80, 2), 0x1D);
+		assert_eq!(gf_mul(0x53, 0xCA), gf_mul(0xCA, 0x53));
+		assert_eq!(gf_mul(0x53, 1), 0x53);
+	}
+
+	#[test]
+	fn format_information() {
+		for (mask, expected) in FORMATS.iter().enumerate() {
+			let mut code = Code {
+				dark:     [[false; SIZE]; SIZE],
+				function: [[false; SIZE]; SIZE],
+			};
+			code.draw_format(mask as u32);
+			assert_eq!(
+				format(&code.dark),
+				u32::from_str_radix(expected, 2).unwrap()
+			);
+		}
+	}
+
+	#[test]
+	fn code_reads_back() {
+		let text = "02:DE:AD:BE:EF:01";
+		let dark = qr_code(text).unwrap();
+		let mask = FORMATS
+			.iter()
+			.position(|format_bits| u32::from_str_radix(format_bits, 2).unwrap() == format(&dark))
+			.expect("valid format information");
+
+		// The finders, and the dark module
+		for (cx, cy) in [(3, 3), (SIZE - 4, 3), (3, SIZE - 4)] {
+			assert!(dark[cy][cx] && !dark[cy - 2][cx] && dark[cy - 3][cx - 3]);
+		}
+		assert!(dark[SIZE - 8][8]);
+
+		// Which modules are data, as the code was built
+		let mut layout = Code {
+			dark:     [[false; SIZE]; SIZE],
+			function: [[false; SIZE]; SIZE],
+		};
+		layout.draw_patterns();
+		layout.draw_format(mask as u32);
+		let mut code = Code {
+			dark,
+			function: layout.function,
+		};
+		code.apply_mask(mask as u32);
+		// Read the zigzag of two module wide columns, right to left, skipping
+		// the vertical timing pattern
+		let mut bits = Vec::new();
+		let columns: Vec<usize> = (0..SIZE).rev().filter(|x| *x != 6).collect();
+		for (nth, pair) in columns.chunks(2).enumerate() {
+			let rows: Vec<usize> = if nth % 2 == 0 {
+				(0..SIZE).rev().collect()
+			} else {
+				(0..SIZE).collect()
+			};
+			for y in rows {
+				for &x in pair {
+					if !code.function[y][x] {
+						bits.push(code.dark[y][x]);
+					}
+				}
+			}
+		}
+		let codewords: Vec<u8> = bits
+			.chunks_exact(8)
+			.map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit as u8))
+			.collect();
+		let mut expected = encode(text).unwrap();
+		expected.extend(ecc(&expected));
+		assert_eq!(codewords, expected);
+	}
+
+	#[test]
+	fn renders_two_rows_per_line() {
+		let lines = qr("02:DE:AD:BE:EF:01").unwrap();
+		let width = SIZE + 2 * QUIET_ZONE;
+		assert_eq!(lines.len(), width.div_ceil(2));
+		assert!(lines.iter().all(|line| line.chars().count() == width));
+		// The quiet zone is light
+		assert!(lines[0].chars().all(|c| c == '█'));
+	}
+}
@@ -0,0 +1,66 @@
+//! Not cutting off the SSH session rac runs in
+//!
+//! sshd tells its sessions where they come from in `SSH_CONNECTION` (`<client
+//! address> <client port> <server address> <server port>`). The interface the
+//! route to the client goes through carries the session: taking it down for a
+//! change drops the connection, and with `--no-up` it stays dropped. Changes
+//! to it happen while it's up instead, unless `--force` says otherwise.
+//! Sessions that don't pass the variable on (`sudo` without `env_keep`,
+//! `su -`) aren't recognized.
+
+use std::{
+	env,
+	net::IpAddr,
+	process::Command,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		OnceLock,
+	},
+};
+
+use crate::timeout;
+
+/// The session this process runs in
+pub struct Session {
+	pub client: IpAddr,
+	/// The interface carrying it
+	pub inter:  String,
+}
+
+static SESSION: OnceLock<Option<Session>> = OnceLock::new();
+static FORCE: AtomicBool = AtomicBool::new(false);
+
+/// Take the interface carrying the session down anyway (`--force`)
+pub fn force() { FORCE.store(true, Ordering::Relaxed); }
+
+/// The interface the route to `client` goes through
+fn route(client: IpAddr) -> Option<String> {
+	let output =
+		timeout::output(Command::new("ip").args(["route", "get", &client.to_string()])).ok()?;
+	// e.g. `192.0.2.7 via 10.0.0.1 dev eth0 src 10.0.0.2 uid 1000`
+	let text = String::from_utf8_lossy(&output.stdout);
+	let words: Vec<&str> = text.split_whitespace().collect();
+	let at = words.iter().position(|word| *word == "dev")?;
+	words.get(at + 1).map(|inter| inter.to_string())
+}
+
+fn session() -> Option<&'static Session> {
+	SESSION
+		.get_or_init(|| {
+			let connection = env::var("SSH_CONNECTION").ok()?;
+			let client: IpAddr = connection.split_whitespace().next()?.parse().ok()?;
+			Some(Session {
+				client,
+				inter: route(client)?,
+			})
+		})
+		.as_ref()
+}
+
+/// The session, if it goes over `inter` and `--force` wasn't given
+pub fn guarding(inter: &str) -> Option<&'static Session> {
+	if FORCE.load(Ordering::Relaxed) {
+		return None;
+	}
+	session().filter(|session| session.inter == inter)
+}
@@ -0,0 +1,128 @@
+//! Deterministic addresses, for the `stable-per-network` and `stable-per-day`
+//! policies
+//!
+//! An address is derived from a secret kept in `<state dir>/secret`, the
+//! interface and the network it's on (or the date), so it's the same every
+//! time the host joins a network (or all day) and unrelated otherwise. Without
+//! the secret nobody can tell two of these addresses belong to the same host.
+
+use std::{
+	fs::{self, OpenOptions},
+	io::{self, Write},
+	os::unix::fs::OpenOptionsExt,
+	path::Path,
+	process::Command,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use nix::libc;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+use crate::{timeout, wpa, MacAddr};
+
+/// The secret addresses are derived from, created on first use
+pub fn secret(state_dir: &Path) -> io::Result<[u8; 32]> {
+	let path = state_dir.join("secret");
+	match fs::read(&path) {
+		Ok(bytes) => bytes.try_into().map_err(|_| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("{} isn't 32 bytes long", path.display()),
+			)
+		}),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {
+			let secret: [u8; 32] = rand::random();
+			fs::create_dir_all(state_dir)?;
+			OpenOptions::new()
+				.write(true)
+				.create_new(true)
+				.mode(0o600)
+				.open(&path)?
+				.write_all(&secret)?;
+			Ok(secret)
+		}
+		Err(e) => Err(e),
+	}
+}
+
+/// The SSID a Wi-Fi interface is connected to, asking wpa_supplicant and then
+/// `iw`
+pub fn ssid(inter: &str) -> Option<String> {
+	if let Some(ssid) = wpa::Control::open(inter)
+		.and_then(|control| control.ssid())
+		.ok()
+		.flatten()
+	{
+		return Some(ssid);
+	}
+	let output = timeout::output(Command::new("iw").args(["dev", inter, "link"])).ok()?;
+	String::from_utf8_lossy(&output.stdout)
+		.lines()
+		.find_map(|line| line.trim().strip_prefix("SSID: "))
+		.map(str::to_string)
+}
+
+/// What tells the networks of an interface apart: the SSID on Wi-Fi, while
+/// wired interfaces count as always being on the same network
+pub fn network(inter: &str) -> io::Result<String> {
+	if !crate::is_wireless(inter) {
+		return Ok("wired".to_string());
+	}
+	ssid(inter)
+		.map(|ssid| format!("ssid:{}", ssid))
+		.ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				format!(
+					"{} isn't connected to a network, a stable address needs one to derive it from",
+					inter
+				),
+			)
+		})
+}
+
+/// The local date, `YYYY-MM-DD`
+pub fn today() -> String {
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |since| since.as_secs()) as libc::time_t;
+	// SAFETY: both pointers are valid for the duration of the call, and an
+	// all-zero `tm` is a valid value
+	let tm = unsafe {
+		let mut tm = std::mem::zeroed::<libc::tm>();
+		libc::localtime_r(&now, &mut tm);
+		tm
+	};
+	format!(
+		"{:04}-{:02}-{:02}",
+		tm.tm_year + 1900,
+		tm.tm_mon + 1,
+		tm.tm_mday
+	)
+}
+
+/// FNV-1a, only used to spread the inputs over the seed
+fn fnv1a(bytes: &[u8], basis: u64) -> u64 {
+	bytes.iter().fold(basis, |hash, &b| {
+		(hash ^ b as u64).wrapping_mul(0x100_0000_01b3)
+	})
+}
+
+/// Derive an address from the secret and `parts`. `attempt` gives another,
+/// equally stable address when the first one is blacklisted.
+pub fn address(secret: &[u8; 32], parts: &[&str], attempt: usize) -> MacAddr {
+	let input = format!("{}\0{}", parts.join("\0"), attempt);
+	let mut seed = *secret;
+	for (i, chunk) in seed.chunks_mut(8).enumerate() {
+		let hash = fnv1a(input.as_bytes(), 0xcbf2_9ce4_8422_2325 ^ i as u64);
+		for (byte, h) in chunk.iter_mut().zip(hash.to_le_bytes()) {
+			*byte ^= h;
+		}
+	}
+	// The seed keys ChaCha, whose output gives nothing away about it
+	let mut bytes = [0; 6];
+	StdRng::from_seed(seed).fill_bytes(&mut bytes);
+	bytes[0] &= 0xfe; // clear multicast bit
+	bytes[0] |= 0x02; // set local assignment bit (IEEE802)
+	MacAddr::new(bytes)
+}
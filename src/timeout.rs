@@ -0,0 +1,89 @@
+//! A bound on the external commands rac runs (`--timeout`, `timeout` in the
+//! configuration)
+//!
+//! A hung `ip`, `sudo` or `nmcli` would otherwise block rac forever, so they
+//! all run through [`output`] or [`status`], which kill the command once the
+//! timeout passes.
+
+use std::{
+	io::{self, Read},
+	process::{Child, Command, ExitStatus, Output, Stdio},
+	sync::OnceLock,
+	thread,
+	time::{Duration, Instant},
+};
+
+/// How long an external command may take, unless configured otherwise
+pub const DEFAULT: Duration = Duration::from_secs(30);
+
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+pub fn set(timeout: Duration) { TIMEOUT.set(timeout).ok(); }
+
+pub fn get() -> Duration { TIMEOUT.get().copied().unwrap_or(DEFAULT) }
+
+/// The command line, for errors
+fn describe(command: &Command) -> String {
+	std::iter::once(command.get_program())
+		.chain(command.get_args())
+		.map(|arg| arg.to_string_lossy())
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Wait for a child, killing it once the timeout passes
+fn wait(command: &Command, child: &mut Child) -> io::Result<ExitStatus> {
+	let deadline = Instant::now() + get();
+	loop {
+		if let Some(status) = child.try_wait()? {
+			return Ok(status);
+		}
+		if Instant::now() >= deadline {
+			child.kill()?;
+			child.wait()?;
+			return Err(io::Error::new(
+				io::ErrorKind::TimedOut,
+				format!(
+					"`{}` didn't finish within {}s (see --timeout)",
+					describe(command),
+					get().as_secs()
+				),
+			));
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+}
+
+/// Like [`Command::status`], with the timeout
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+	let mut child = command.spawn()?;
+	wait(command, &mut child)
+}
+
+/// Like [`Command::output`], with the timeout
+pub fn output(command: &mut Command) -> io::Result<Output> {
+	let mut child = command
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()?;
+	// Read both pipes while waiting, so a chatty command can't fill one and
+	// block
+	let read = |pipe: Option<Box<dyn Read + Send>>| {
+		thread::spawn(move || {
+			let mut bytes = Vec::new();
+			if let Some(mut pipe) = pipe {
+				pipe.read_to_end(&mut bytes).ok();
+			}
+			bytes
+		})
+	};
+	let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+	let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+	let status = wait(command, &mut child)?;
+	Ok(Output {
+		status,
+		stdout: stdout.join().unwrap_or_default(),
+		stderr: stderr.join().unwrap_or_default(),
+	})
+}
@@ -0,0 +1,115 @@
+//! `rac self-update`, for a static binary installed by hand
+//!
+//! A release is a binary named `rac-<arch>-linux` next to
+//! `rac-<arch>-linux.sha256`, under [`RELEASES`] (or `--url`). The checksum
+//! decides both whether there's anything new (it differs from the running
+//! binary's) and whether the download is intact. curl downloads and
+//! sha256sum checks, and the new binary replaces the old one with a rename,
+//! so a failure anywhere leaves the old one in place.
+
+use std::{
+	env, fs, io,
+	os::unix::fs::PermissionsExt,
+	path::{Path, PathBuf},
+	process::{self, Command},
+};
+
+use crate::timeout;
+
+/// Where the latest release is published
+pub const RELEASES: &str = "https://github.com/conelul/rac/releases/latest/download";
+
+/// The release asset for this machine
+fn asset() -> String { format!("rac-{}-linux", env::consts::ARCH) }
+
+/// Run curl, failing on HTTP errors
+fn curl(args: &[&str]) -> io::Result<Vec<u8>> {
+	let output = match timeout::output(Command::new("curl").args(["-fsSL"]).args(args)) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {
+			return Err(io::Error::new(
+				io::ErrorKind::NotFound,
+				"self-update needs curl",
+			))
+		}
+		result => result?,
+	};
+	if !output.status.success() {
+		return Err(io::Error::other(format!(
+			"curl failed: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		)));
+	}
+	Ok(output.stdout)
+}
+
+/// The SHA-256 of a file, in hex
+fn sha256(path: &Path) -> io::Result<String> {
+	let output = timeout::output(Command::new("sha256sum").arg(path))?;
+	String::from_utf8_lossy(&output.stdout)
+		.split_whitespace()
+		.next()
+		.filter(|_| output.status.success())
+		.map(str::to_lowercase)
+		.ok_or_else(|| io::Error::other(format!("sha256sum couldn't read {}", path.display())))
+}
+
+/// What's available, compared to the running binary
+pub enum Status {
+	UpToDate,
+	/// A different binary, with its checksum
+	Available(String),
+}
+
+/// Compare the published checksum with the running binary's
+pub fn check(url: &str) -> io::Result<Status> {
+	let published = curl(&[&format!("{}/{}.sha256", url, asset())])?;
+	let published = String::from_utf8_lossy(&published)
+		.split_whitespace()
+		.next()
+		.filter(|sum| sum.len() == 64 && sum.chars().all(|c| c.is_ascii_hexdigit()))
+		.map(str::to_lowercase)
+		.ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("{}.sha256 isn't a SHA-256 checksum", asset()),
+			)
+		})?;
+	if sha256(&env::current_exe()?)? == published {
+		return Ok(Status::UpToDate);
+	}
+	Ok(Status::Available(published))
+}
+
+/// Download the release, check it and put it in place of the running binary
+pub fn install(url: &str, checksum: &str) -> io::Result<PathBuf> {
+	let exe = env::current_exe()?;
+	// In the same directory, so the rename can't cross filesystems
+	let tmp = exe.with_file_name(format!(".rac-update-{}", process::id()));
+	let result = (|| {
+		curl(&[
+			"-o",
+			&tmp.to_string_lossy(),
+			&format!("{}/{}", url, asset()),
+		])?;
+		if sha256(&tmp)? != checksum {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"the download doesn't match its checksum",
+			));
+		}
+		fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))?;
+		// A binary for another libc or architecture fails here rather than
+		// after replacing a working one
+		let runs = timeout::output(Command::new(&tmp).arg("--version"))?;
+		if !runs.status.success() {
+			return Err(io::Error::other(
+				"the new binary doesn't run on this system",
+			));
+		}
+		fs::rename(&tmp, &exe)
+	})();
+	if result.is_err() {
+		let _ = fs::remove_file(&tmp);
+	}
+	result.map(|()| exe)
+}
@@ -0,0 +1,73 @@
+//! Values in rac's TOML-like files, the configuration and the restrictions:
+//! quoted strings, booleans, numbers and lists of strings (`["a", "b"]`)
+
+/// A single parsed value
+#[derive(Debug)]
+pub enum Value {
+	Str(String),
+	Bool(bool),
+	Int(u64),
+	List(Vec<String>),
+}
+
+impl Value {
+	pub fn parse(raw: &str) -> Result<Value, String> {
+		if let Some(inner) = raw.strip_prefix('"') {
+			return inner
+				.strip_suffix('"')
+				.map(|s| Value::Str(s.to_string()))
+				.ok_or_else(|| "unterminated string".to_string());
+		}
+		if let Some(inner) = raw.strip_prefix('[') {
+			let inner = inner
+				.strip_suffix(']')
+				.ok_or_else(|| "unterminated list".to_string())?;
+			return inner
+				.split(',')
+				.map(str::trim)
+				.filter(|item| !item.is_empty())
+				.map(|item| match Value::parse(item)? {
+					Value::Str(s) => Ok(s),
+					_ => Err(format!("expected a string in list, found '{}'", item)),
+				})
+				.collect::<Result<_, _>>()
+				.map(Value::List);
+		}
+		match raw {
+			"true" => Ok(Value::Bool(true)),
+			"false" => Ok(Value::Bool(false)),
+			_ => raw
+				.parse()
+				.map(Value::Int)
+				.map_err(|_| format!("invalid value '{}'", raw)),
+		}
+	}
+
+	pub fn as_bool(&self) -> Result<bool, String> {
+		match self {
+			Value::Bool(b) => Ok(*b),
+			_ => Err("expected a boolean".to_string()),
+		}
+	}
+
+	pub fn as_int(&self) -> Result<u64, String> {
+		match self {
+			Value::Int(n) => Ok(*n),
+			_ => Err("expected a number".to_string()),
+		}
+	}
+
+	pub fn as_str(&self) -> Result<&str, String> {
+		match self {
+			Value::Str(s) => Ok(s),
+			_ => Err("expected a string".to_string()),
+		}
+	}
+
+	pub fn into_list(self) -> Result<Vec<String>, String> {
+		match self {
+			Value::List(list) => Ok(list),
+			_ => Err("expected a list of strings".to_string()),
+		}
+	}
+}
@@ -0,0 +1,123 @@
+//! wpa_supplicant integration, through its control socket
+//!
+//! wpa_supplicant can pick its own address per connection (`mac_addr`) and
+//! for scans before associating (`preassoc_mac_addr`), which either fights
+//! with or complements the address set by rac.
+
+use std::{
+	fs, io,
+	os::unix::net::UnixDatagram,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use clap::ArgEnum;
+
+/// Directories wpa_supplicant creates its control sockets in
+const CONTROL_DIRS: [&str; 2] = ["/run/wpa_supplicant", "/var/run/wpa_supplicant"];
+
+/// Address handling modes, as understood by `mac_addr`/`preassoc_mac_addr`
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum Mode {
+	/// Leave the address set by rac alone (0)
+	Keep,
+	/// Random address for every network (1)
+	Random,
+	/// Random address keeping the vendor prefix (2)
+	KeepOui,
+}
+
+impl Mode {
+	fn value(&self) -> u8 {
+		match self {
+			Mode::Keep => 0,
+			Mode::Random => 1,
+			Mode::KeepOui => 2,
+		}
+	}
+}
+
+/// A connection to wpa_supplicant's control interface for one interface
+pub struct Control {
+	socket: UnixDatagram,
+	local:  PathBuf,
+}
+
+impl Control {
+	pub fn open(inter: &str) -> io::Result<Control> {
+		let server = CONTROL_DIRS
+			.iter()
+			.map(|dir| Path::new(dir).join(inter))
+			.find(|path| path.exists())
+			.ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::NotFound,
+					format!("wpa_supplicant isn't controlling '{}'", inter),
+				)
+			})?;
+		// Replies are sent back to our own (bound) address
+		let local = std::env::temp_dir().join(format!("rac-wpa-{}", std::process::id()));
+		let _ = fs::remove_file(&local);
+		let socket = UnixDatagram::bind(&local)?;
+		let control = Control { socket, local };
+		control.socket.connect(server)?;
+		control
+			.socket
+			.set_read_timeout(Some(Duration::from_secs(5)))?;
+		Ok(control)
+	}
+
+	/// Send a command and return the reply, failing on `FAIL`
+	pub fn request(&self, command: &str) -> io::Result<String> {
+		self.socket.send(command.as_bytes())?;
+		let mut buf = [0u8; 4096];
+		let len = self.socket.recv(&mut buf)?;
+		let reply = String::from_utf8_lossy(&buf[..len]).trim().to_string();
+		if reply.starts_with("FAIL") {
+			return Err(io::Error::other(format!(
+				"wpa_supplicant rejected '{}'",
+				command
+			)));
+		}
+		Ok(reply)
+	}
+
+	/// Apply an address mode, globally or for a single configured network
+	pub fn set_mode(&self, mode: Mode, network: Option<u32>) -> io::Result<()> {
+		match network {
+			Some(id) => {
+				self.request(&format!("SET_NETWORK {} mac_addr {}", id, mode.value()))?;
+			}
+			None => {
+				self.request(&format!("SET mac_addr {}", mode.value()))?;
+				self.request(&format!("SET preassoc_mac_addr {}", mode.value()))?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Have the driver use random addresses for scans (`MAC_RAND_SCAN`), and
+	/// keep it that way across restarts once saved (`preassoc_mac_addr`)
+	pub fn set_scan_random(&self, enable: bool) -> io::Result<()> {
+		self.request(&format!("MAC_RAND_SCAN all enable={}", enable as u8))?;
+		let mode = if enable { Mode::Random } else { Mode::Keep };
+		self.request(&format!("SET preassoc_mac_addr {}", mode.value()))?;
+		Ok(())
+	}
+
+	/// The SSID of the network the interface is connected to, if any
+	pub fn ssid(&self) -> io::Result<Option<String>> {
+		let status = self.request("STATUS")?;
+		Ok(status
+			.lines()
+			.find_map(|line| line.strip_prefix("ssid="))
+			.map(str::to_string))
+	}
+
+	/// Write the running configuration back to disk (needs `update_config=1`)
+	pub fn save(&self) -> io::Result<()> { self.request("SAVE_CONFIG").map(|_| ()) }
+}
+
+impl Drop for Control {
+	fn drop(&mut self) { let _ = fs::remove_file(&self.local); }
+}